@@ -0,0 +1,309 @@
+//! アニメーション画像（GIF / APNG / アニメーション WebP）の全フレームデコードと再生状態。
+//!
+//! デコードは `decode_animation` で全フレームを合成済み RGBA として 1 枚ずつ取り出し（上限あり）、
+//! 再生は `Playback` が経過時間からフレーム番号を進める（UI スレッドは
+//! `time_to_next` を `ctx.request_repaint_after` に渡すだけでよい）。
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, Frames, RgbaImage};
+use log::info;
+use std::path::Path;
+use std::time::Duration;
+
+/// 再生速度の段階（[ / ] キーで切り替え）
+pub const SPEED_STEPS: &[f32] = &[0.25, 0.5, 1.0, 1.5, 2.0, 4.0];
+/// 遅延が極端に短いフレームの扱い。ブラウザと同じく 10ms 以下は 100ms とみなす
+/// （遅延 0 の GIF が全力で回ってしまうのを防ぐ）
+const MIN_DELAY_MS: u32 = 10;
+const FALLBACK_DELAY_MS: u32 = 100;
+
+/// アニメーションとして持つフレームの合計バイト数（合成済み RGBA）と枚数の上限。
+/// 超える長い・大きいアニメーションは先頭フレームの静止画として開く
+const MAX_ANIMATION_BYTES: usize = 512 << 20;
+const MAX_ANIMATION_FRAMES: usize = 10_000;
+
+/// decode_animation の結果
+pub enum Animation {
+    /// 2 フレーム以上のアニメーション（フレーム, 表示時間）
+    Frames(Vec<(RgbaImage, Duration)>),
+    /// 1 フレームだけ、または上限を超えた GIF の先頭フレーム（静止画として使い、デコードし直さない）
+    Still(RgbaImage),
+}
+
+/// アニメーションとしてデコードを試みる。フレームは 1 枚ずつ取り出し、上限を超えたらそこで止める。
+/// 静止画（または非対応形式）なら None を返して通常の読み込みに任せる。
+/// GIF は先頭フレームを Still で返す（APNG/WebP は ICC などを通常の読み込みで扱うので None）
+pub fn decode_animation(path: &Path) -> Result<Option<Animation>, String> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let open = || {
+        crate::archive::open(path).map_err(|e| format!("ファイルを開けません: {e}"))
+    };
+    let (frames, still) = match ext.as_str() {
+        "gif" => {
+            let decoder = GifDecoder::new(open()?).map_err(|e| e.to_string())?;
+            collect_frames(decoder.into_frames(), MAX_ANIMATION_BYTES, MAX_ANIMATION_FRAMES)?
+        }
+        "png" | "apng" => {
+            let decoder = PngDecoder::new(open()?).map_err(|e| e.to_string())?;
+            if !decoder.is_apng().map_err(|e| e.to_string())? {
+                return Ok(None);
+            }
+            let apng = decoder.apng().map_err(|e| e.to_string())?;
+            collect_frames(apng.into_frames(), MAX_ANIMATION_BYTES, MAX_ANIMATION_FRAMES)?
+        }
+        "webp" => {
+            let decoder = WebPDecoder::new(open()?).map_err(|e| e.to_string())?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            collect_frames(decoder.into_frames(), MAX_ANIMATION_BYTES, MAX_ANIMATION_FRAMES)?
+        }
+        _ => return Ok(None),
+    };
+    Ok(match still {
+        None => Some(Animation::Frames(frames)),
+        Some(first) if ext == "gif" => Some(Animation::Still(first)),
+        Some(_) => None,
+    })
+}
+
+/// (全フレーム, 静止画として使う先頭フレーム)
+type Collected = (Vec<(RgbaImage, Duration)>, Option<RgbaImage>);
+
+/// フレームを 1 枚ずつ取り出す。2 フレーム以上あり上限にも収まれば (全フレーム, None)、
+/// 1 フレームしかないか上限を超えたら (空, Some(先頭フレーム))
+fn collect_frames(frames: Frames<'_>, max_bytes: usize, max_frames: usize) -> Result<Collected, String> {
+    let mut collected: Vec<(RgbaImage, Duration)> = Vec::new();
+    let mut bytes = 0usize;
+    for frame in frames {
+        let frame = frame.map_err(|e| e.to_string())?;
+        bytes = bytes.saturating_add(frame.buffer().len());
+        if !collected.is_empty() && (bytes > max_bytes || collected.len() >= max_frames) {
+            info!(
+                "アニメーションが大きすぎるため先頭フレームだけを表示します（{} フレーム目で {} MB）",
+                collected.len() + 1,
+                bytes >> 20
+            );
+            return Ok((Vec::new(), collected.into_iter().next().map(|(first, _)| first)));
+        }
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        collected.push((frame.into_buffer(), frame_delay(numer, denom)));
+    }
+    if collected.len() < 2 {
+        return Ok((Vec::new(), collected.pop().map(|(first, _)| first)));
+    }
+    Ok((collected, None))
+}
+
+/// image の Delay（分数ミリ秒）を表示時間へ変換する
+fn frame_delay(numer: u32, denom: u32) -> Duration {
+    let ms = numer.checked_div(denom).unwrap_or(0);
+    if ms <= MIN_DELAY_MS {
+        Duration::from_millis(FALLBACK_DELAY_MS as u64)
+    } else {
+        Duration::from_millis(ms as u64)
+    }
+}
+
+/// アニメーションの再生状態（フレーム番号・一時停止・速度）
+pub struct Playback {
+    delays: Vec<Duration>,
+    current: usize,
+    paused: bool,
+    speed: f32,
+    /// 現在のフレームを表示し始めてからの経過時間（速度適用後）
+    elapsed: Duration,
+}
+
+impl Playback {
+    pub fn new(delays: Vec<Duration>) -> Self {
+        Self {
+            delays,
+            current: 0,
+            paused: false,
+            speed: 1.0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.delays.len()
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// 実時間 dt だけ再生を進める。フレームが切り替わったら true
+    pub fn advance(&mut self, dt: Duration) -> bool {
+        if self.paused || self.delays.is_empty() {
+            return false;
+        }
+        let before = self.current;
+        self.elapsed += dt.mul_f32(self.speed);
+        // 長時間止まっていた（ウィンドウ非表示等）場合に何周も回さないよう 1 周分で打ち切る
+        let mut guard = self.delays.len();
+        while self.elapsed >= self.delays[self.current] && guard > 0 {
+            self.elapsed -= self.delays[self.current];
+            self.current = (self.current + 1) % self.delays.len();
+            guard -= 1;
+        }
+        if guard == 0 {
+            self.elapsed = Duration::ZERO;
+        }
+        self.current != before
+    }
+
+    /// 次のフレームへ切り替わるまでの実時間。一時停止中は None
+    pub fn time_to_next(&self) -> Option<Duration> {
+        if self.paused || self.delays.is_empty() {
+            return None;
+        }
+        let remaining = self.delays[self.current].saturating_sub(self.elapsed);
+        Some(remaining.div_f32(self.speed))
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// 1 フレーム送り／戻し。コマ送りは一時停止状態で行う
+    pub fn step(&mut self, forward: bool) {
+        let n = self.delays.len();
+        if n == 0 {
+            return;
+        }
+        self.paused = true;
+        self.elapsed = Duration::ZERO;
+        self.current = if forward {
+            (self.current + 1) % n
+        } else {
+            (self.current + n - 1) % n
+        };
+    }
+
+    /// 再生速度を 1 段階上げる（faster=true）／下げる
+    pub fn change_speed(&mut self, faster: bool) {
+        let idx = SPEED_STEPS
+            .iter()
+            .position(|s| (*s - self.speed).abs() < f32::EPSILON)
+            .unwrap_or(2);
+        let idx = if faster {
+            (idx + 1).min(SPEED_STEPS.len() - 1)
+        } else {
+            idx.saturating_sub(1)
+        };
+        self.speed = SPEED_STEPS[idx];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(v: u64) -> Duration {
+        Duration::from_millis(v)
+    }
+
+    #[test]
+    fn zero_delay_falls_back_to_100ms() {
+        assert_eq!(frame_delay(0, 1), ms(100));
+        assert_eq!(frame_delay(10, 1), ms(100));
+        assert_eq!(frame_delay(50, 1), ms(50));
+        assert_eq!(frame_delay(100, 3), ms(33));
+    }
+
+    /// 灰色の濃さ values のフレームを並べた 4x2 の GIF
+    fn gif(values: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = image::codecs::gif::GifEncoder::new(&mut bytes);
+            let frames = values.iter().map(|&v| {
+                image::Frame::from_parts(
+                    RgbaImage::from_pixel(4, 2, image::Rgba([v, v, v, 255])),
+                    0,
+                    0,
+                    image::Delay::from_numer_denom_ms(50, 1),
+                )
+            });
+            encoder.encode_frames(frames).unwrap();
+        }
+        bytes
+    }
+
+    fn frames_of(bytes: &[u8]) -> Frames<'_> {
+        GifDecoder::new(std::io::Cursor::new(bytes)).unwrap().into_frames()
+    }
+
+    #[test]
+    fn frames_are_collected_within_limits_or_fall_back_to_the_first() {
+        let three = gif(&[0, 120, 240]);
+        let (frames, still) = collect_frames(frames_of(&three), MAX_ANIMATION_BYTES, MAX_ANIMATION_FRAMES).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert!(still.is_none());
+        assert_eq!(frames[1].0.get_pixel(0, 0).0, [120, 120, 120, 255]);
+        assert_eq!(frames[1].1, ms(50));
+
+        // 1 フレームだけなら、取り出したフレームを静止画として返す
+        let one = gif(&[120]);
+        let (frames, still) = collect_frames(frames_of(&one), MAX_ANIMATION_BYTES, MAX_ANIMATION_FRAMES).unwrap();
+        assert!(frames.is_empty());
+        assert_eq!(still.unwrap().get_pixel(0, 0).0, [120, 120, 120, 255]);
+
+        // 合計バイト数（1 フレーム 4x2x4 = 32 バイト）や枚数の上限を超えたら先頭フレームだけ
+        for (max_bytes, max_frames) in [(64, MAX_ANIMATION_FRAMES), (MAX_ANIMATION_BYTES, 2)] {
+            let (frames, still) = collect_frames(frames_of(&three), max_bytes, max_frames).unwrap();
+            assert!(frames.is_empty());
+            assert_eq!(still.unwrap().get_pixel(0, 0).0, [0, 0, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn advance_wraps_and_honors_speed() {
+        let mut p = Playback::new(vec![ms(100), ms(200), ms(100)]);
+        assert!(!p.advance(ms(50)));
+        assert!(p.advance(ms(60)));
+        assert_eq!(p.current(), 1);
+        let wait = p.time_to_next().unwrap();
+        assert!(wait.abs_diff(ms(190)) < ms(1), "{wait:?}");
+        p.change_speed(true); // 1.5x
+        p.change_speed(true); // 2x
+        assert_eq!(p.speed(), 2.0);
+        assert!(p.advance(ms(100))); // 200ms 相当進む
+        assert_eq!(p.current(), 2);
+        p.advance(ms(50));
+        assert_eq!(p.current(), 0, "末尾の次は先頭へ戻る");
+    }
+
+    #[test]
+    fn step_pauses_and_wraps() {
+        let mut p = Playback::new(vec![ms(100); 3]);
+        p.step(false);
+        assert!(p.paused());
+        assert_eq!(p.current(), 2);
+        assert_eq!(p.time_to_next(), None);
+        assert!(!p.advance(ms(1000)), "一時停止中は進まない");
+        p.step(true);
+        assert_eq!(p.current(), 0);
+    }
+
+    #[test]
+    fn long_stall_does_not_spin() {
+        let mut p = Playback::new(vec![ms(100), ms(100)]);
+        p.advance(Duration::from_secs(3600));
+        assert!(p.time_to_next().is_some());
+    }
+}
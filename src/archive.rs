@@ -0,0 +1,703 @@
+//! ZIP/CBZ・tar/CBT の中の画像を、展開せずに仮想フォルダとして扱う。
+//!
+//! アーカイブ内のエントリは「アーカイブのパス/エントリ名」という仮想パスで表すので、
+//! image_paths・キャッシュ・サムネイル等では普通のファイルと同じように扱える。
+//! 画像の読み込みは open / read を通し、仮想パスならエントリをメモリへ読み出して返す。
+//! 目次（ZIP の中央ディレクトリ・tar のヘッダ列）はアーカイブの更新日時ごとにキャッシュする。
+
+use flate2::read::DeflateDecoder;
+use log::debug;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// 仮想フォルダとして開く拡張子
+pub const ARCHIVE_EXTS: &[&str] = &["zip", "cbz", "tar", "cbt"];
+/// 目次をキャッシュするアーカイブ数
+const INDEX_CACHE_LEN: usize = 8;
+/// 読み出すエントリの最大サイズ（展開後）。これを超えるエントリは壊れたアーカイブとして扱う
+const MAX_ENTRY_SIZE: u64 = 2 << 30;
+/// ヘッダのサイズを信じて先に確保する上限（残りは読みながら伸ばす）
+const PREALLOC_LIMIT: u64 = 64 << 20;
+/// スレッドごとに使い回すエントリの最大サイズ（これより大きいものは毎回読み出す）
+const LAST_ENTRY_MAX: usize = 32 << 20;
+
+/// 読み込み元（ファイルか、メモリへ読み出したアーカイブのエントリ）
+pub enum Source {
+    File(BufReader<File>),
+    Memory(Cursor<Arc<[u8]>>),
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::File(r) => r.read(buf),
+            Source::Memory(r) => r.read(buf),
+        }
+    }
+}
+
+impl BufRead for Source {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Source::File(r) => r.fill_buf(),
+            Source::Memory(r) => r.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            Source::File(r) => r.consume(amt),
+            Source::Memory(r) => r.consume(amt),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Source::File(r) => r.seek(pos),
+            Source::Memory(r) => r.seek(pos),
+        }
+    }
+}
+
+/// path を読み込み用に開く（アーカイブ内のエントリならメモリへ読み出す）
+pub fn open(path: &Path) -> io::Result<Source> {
+    match split(path) {
+        Some((archive, entry)) => {
+            read_entry(archive, &entry).map(|bytes| Source::Memory(Cursor::new(bytes)))
+        }
+        None => File::open(path).map(|f| Source::File(BufReader::new(f))),
+    }
+}
+
+/// path の内容をすべて読む（アーカイブ内のエントリにも使える fs::read）
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    match split(path) {
+        Some((archive, entry)) => read_entry(archive, &entry).map(|bytes| bytes.to_vec()),
+        None => fs::read(path),
+    }
+}
+
+/// path のメタデータ。アーカイブ内のエントリはアーカイブ自体の更新日時・サイズで代用する
+pub fn metadata(path: &Path) -> io::Result<fs::Metadata> {
+    match split(path) {
+        Some((archive, _)) => fs::metadata(archive),
+        None => fs::metadata(path),
+    }
+}
+
+/// 仮想フォルダとして開くファイルか
+pub fn is_archive_path(path: &Path) -> bool {
+    has_archive_ext(path) && path.is_file()
+}
+
+fn has_archive_ext(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        let ext = ext.to_string_lossy().to_lowercase();
+        ARCHIVE_EXTS.contains(&ext.as_str())
+    })
+}
+
+/// 仮想パスをアーカイブのパスとエントリ名（"/" 区切り）に分ける。普通のファイルなら None。
+/// 先祖にアーカイブの拡張子を持つものが無ければディスクには触れない
+pub fn split(path: &Path) -> Option<(&Path, String)> {
+    if !path.ancestors().skip(1).any(has_archive_ext) || path.exists() {
+        return None;
+    }
+    let archive = path.ancestors().skip(1).find(|a| is_archive_path(a))?;
+    let entry: Vec<String> = path
+        .strip_prefix(archive)
+        .ok()?
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    Some((archive, entry.join("/")))
+}
+
+/// タイトルバー用の表示（"archive.zip › page012.png"）
+pub fn display(path: &Path) -> String {
+    match split(path) {
+        Some((archive, entry)) => format!("{} › {}", archive.display(), entry),
+        None => path.display().to_string(),
+    }
+}
+
+/// アーカイブ内の画像の仮想パスを、エントリ名の自然順で返す
+pub fn list(archive: &Path) -> Result<Vec<PathBuf>, String> {
+    let stamp = stamp(archive)
+        .map_err(|e| format!("アーカイブを開けません: {} - {}", archive.display(), e))?;
+    let index = index(archive, stamp)?;
+    let mut names: Vec<&str> = index
+        .keys()
+        .map(String::as_str)
+        .filter(|name| {
+            Path::new(name).extension().is_some_and(|ext| {
+                let ext = ext.to_string_lossy().to_lowercase();
+                super::SUPPORTED_EXTS.contains(&ext.as_str())
+            })
+        })
+        .collect();
+    names.sort_by(|a, b| super::natural_cmp(a, b).then_with(|| a.cmp(b)));
+    Ok(names.into_iter().map(|name| archive.join(name)).collect())
+}
+
+/// エントリの格納位置
+#[derive(Clone, Copy, Debug)]
+enum Entry {
+    Zip {
+        /// ローカルファイルヘッダの位置
+        header: u64,
+        method: u16,
+        encrypted: bool,
+        compressed: u64,
+        size: u64,
+        crc: u32,
+    },
+    Tar {
+        data: u64,
+        size: u64,
+    },
+}
+
+type Index = Arc<HashMap<String, Entry>>;
+/// アーカイブの更新日時とサイズ。変わっていたら目次・読み出したエントリを使い直さない
+type Stamp = (Option<SystemTime>, u64);
+
+fn stamp(archive: &Path) -> io::Result<Stamp> {
+    let meta = fs::metadata(archive)?;
+    Ok((meta.modified().ok(), meta.len()))
+}
+
+/// アーカイブの目次（更新日時・サイズが同じ間はキャッシュを使う）
+fn index(archive: &Path, stamp: Stamp) -> Result<Index, String> {
+    type Cache = Mutex<Vec<(PathBuf, Stamp, Index)>>;
+    static CACHE: OnceLock<Cache> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| Mutex::new(Vec::new()));
+    if let Some((_, _, index)) = cache
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(p, s, _)| p == archive && *s == stamp)
+    {
+        return Ok(index.clone());
+    }
+    let mut file = BufReader::new(
+        File::open(archive)
+            .map_err(|e| format!("アーカイブを開けません: {} - {}", archive.display(), e))?,
+    );
+    let ext = archive
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let entries = if ext == "tar" || ext == "cbt" {
+        read_tar_index(&mut file)
+    } else {
+        read_zip_index(&mut file, stamp.1)
+    }
+    .map_err(|e| format!("アーカイブの解析に失敗: {} - {}", archive.display(), e))?;
+    debug!(
+        "アーカイブの目次を読みました: {} ({} 件)",
+        archive.display(),
+        entries.len()
+    );
+    let index = Arc::new(entries);
+    let mut cache = cache.lock().unwrap();
+    cache.retain(|(p, _, _)| p != archive);
+    if cache.len() >= INDEX_CACHE_LEN {
+        cache.remove(0);
+    }
+    cache.push((archive.to_path_buf(), stamp, index.clone()));
+    Ok(index)
+}
+
+/// 読み出したエントリ（アーカイブのパス・更新日時とサイズ・エントリ名・内容）
+type CachedEntry = (PathBuf, Stamp, String, Arc<[u8]>);
+
+thread_local! {
+    /// 直前に読み出したエントリ。1 枚のデコード中に形式判定・EXIF・本体と何度も開くので使い回す。
+    /// デコード結果の LRU の予算外なので、LAST_ENTRY_MAX を超えるエントリは持たない
+    static LAST_ENTRY: RefCell<Option<CachedEntry>> = const { RefCell::new(None) };
+}
+
+fn read_entry(archive: &Path, name: &str) -> io::Result<Arc<[u8]>> {
+    let stamp = stamp(archive)?;
+    let cached = LAST_ENTRY.with(|last| {
+        last.borrow()
+            .as_ref()
+            .filter(|(a, s, n, _)| a == archive && *s == stamp && n == name)
+            .map(|(_, _, _, bytes)| bytes.clone())
+    });
+    if let Some(bytes) = cached {
+        return Ok(bytes);
+    }
+    let index = index(archive, stamp).map_err(io::Error::other)?;
+    let entry = *index.get(name).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("アーカイブ内にありません: {name}"),
+        )
+    })?;
+    let mut file = BufReader::new(File::open(archive)?);
+    let bytes: Arc<[u8]> = extract(&mut file, entry)?.into();
+    LAST_ENTRY.with(|last| {
+        *last.borrow_mut() = (bytes.len() <= LAST_ENTRY_MAX)
+            .then(|| (archive.to_path_buf(), stamp, name.to_string(), bytes.clone()))
+    });
+    Ok(bytes)
+}
+
+fn extract<R: Read + Seek>(file: &mut R, entry: Entry) -> io::Result<Vec<u8>> {
+    match entry {
+        Entry::Tar { data, size } => {
+            file.seek(SeekFrom::Start(data))?;
+            read_exact_vec(file, size)
+        }
+        Entry::Zip {
+            header,
+            method,
+            encrypted,
+            compressed,
+            size,
+            crc,
+        } => {
+            if encrypted {
+                return Err(io::Error::other("暗号化されたエントリには対応していません"));
+            }
+            file.seek(SeekFrom::Start(header))?;
+            let mut local = [0u8; 30];
+            file.read_exact(&mut local)?;
+            if u32_at(&local, 0) != 0x0403_4b50 {
+                return Err(io::Error::other("ローカルファイルヘッダが壊れています"));
+            }
+            let skip = u16_at(&local, 26) as i64 + u16_at(&local, 28) as i64;
+            file.seek(SeekFrom::Current(skip))?;
+            if size > MAX_ENTRY_SIZE {
+                return Err(io::Error::other(format!("エントリが大きすぎます: {size} バイト")));
+            }
+            let out = match method {
+                0 if compressed == size => read_exact_vec(file, size)?,
+                0 => return Err(io::Error::other("エントリのサイズが一致しません")),
+                8 => {
+                    // 展開後のサイズはヘッダの値までに抑える（zip bomb 対策）。超えたら壊れている
+                    let mut out = Vec::with_capacity(size.min(PREALLOC_LIMIT) as usize);
+                    DeflateDecoder::new(file.take(compressed))
+                        .take(size + 1)
+                        .read_to_end(&mut out)?;
+                    if out.len() as u64 != size {
+                        return Err(io::Error::other("エントリのサイズが一致しません"));
+                    }
+                    out
+                }
+                m => return Err(io::Error::other(format!("未対応の圧縮方式です: {m}"))),
+            };
+            let mut check = flate2::Crc::new();
+            check.update(&out);
+            if check.sum() != crc {
+                return Err(io::Error::other("エントリの CRC-32 が一致しません"));
+            }
+            Ok(out)
+        }
+    }
+}
+
+fn read_exact_vec<R: Read>(file: &mut R, size: u64) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(size.min(PREALLOC_LIMIT) as usize);
+    file.take(size).read_to_end(&mut out)?;
+    if (out.len() as u64) < size {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(out)
+}
+
+fn u16_at(b: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([b[i], b[i + 1]])
+}
+
+fn u32_at(b: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes(b[i..i + 4].try_into().unwrap())
+}
+
+fn u64_at(b: &[u8], i: usize) -> u64 {
+    u64::from_le_bytes(b[i..i + 8].try_into().unwrap())
+}
+
+/// ZIP の中央ディレクトリを読む（ZIP64 を含む。ディレクトリのエントリは除く）
+fn read_zip_index<R: Read + Seek>(
+    file: &mut R,
+    len: u64,
+) -> Result<HashMap<String, Entry>, String> {
+    // 終端レコード（22 バイト＋最大 64KiB のコメント）を末尾から探す
+    let tail_len = len.min(22 + 0xFFFF);
+    file.seek(SeekFrom::Start(len - tail_len))
+        .map_err(|e| e.to_string())?;
+    let tail = read_exact_vec(file, tail_len).map_err(|e| e.to_string())?;
+    let eocd = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| u32_at(&tail, i) == 0x0605_4b50)
+        .ok_or("ZIP の終端レコードが見つかりません")?;
+    let mut count = u16_at(&tail, eocd + 10) as u64;
+    let mut cd_size = u32_at(&tail, eocd + 12) as u64;
+    let mut cd_offset = u32_at(&tail, eocd + 16) as u64;
+    // ZIP64: 終端レコードの直前のロケータから ZIP64 終端レコードを読む
+    if eocd >= 20 && u32_at(&tail, eocd - 20) == 0x0706_4b50 {
+        let zip64_eocd = u64_at(&tail, eocd - 20 + 8);
+        file.seek(SeekFrom::Start(zip64_eocd))
+            .map_err(|e| e.to_string())?;
+        let record = read_exact_vec(file, 56).map_err(|e| e.to_string())?;
+        if u32_at(&record, 0) != 0x0606_4b50 {
+            return Err("ZIP64 の終端レコードが壊れています".into());
+        }
+        count = u64_at(&record, 32);
+        cd_size = u64_at(&record, 40);
+        cd_offset = u64_at(&record, 48);
+    }
+    if cd_offset.saturating_add(cd_size) > len {
+        return Err("中央ディレクトリの位置が不正です".into());
+    }
+    file.seek(SeekFrom::Start(cd_offset))
+        .map_err(|e| e.to_string())?;
+    let cd = read_exact_vec(file, cd_size).map_err(|e| e.to_string())?;
+
+    let mut entries = HashMap::new();
+    let mut pos = 0;
+    for _ in 0..count {
+        if pos + 46 > cd.len() || u32_at(&cd, pos) != 0x0201_4b50 {
+            return Err("中央ディレクトリが壊れています".into());
+        }
+        let flags = u16_at(&cd, pos + 8);
+        let method = u16_at(&cd, pos + 10);
+        let crc = u32_at(&cd, pos + 16);
+        let mut compressed = u32_at(&cd, pos + 20) as u64;
+        let mut size = u32_at(&cd, pos + 24) as u64;
+        let name_len = u16_at(&cd, pos + 28) as usize;
+        let extra_len = u16_at(&cd, pos + 30) as usize;
+        let comment_len = u16_at(&cd, pos + 32) as usize;
+        let mut header = u32_at(&cd, pos + 42) as u64;
+        let name_start = pos + 46;
+        let extra_start = name_start + name_len;
+        let next = extra_start + extra_len + comment_len;
+        if next > cd.len() {
+            return Err("中央ディレクトリが壊れています".into());
+        }
+        // ZIP64 拡張フィールド（0x0001）には 0xFFFFFFFF になっている値だけが順に入る
+        let mut extra = &cd[extra_start..extra_start + extra_len];
+        while extra.len() >= 4 {
+            let (id, data_len) = (u16_at(extra, 0), u16_at(extra, 2) as usize);
+            let data = &extra[4..(4 + data_len).min(extra.len())];
+            if id == 0x0001 {
+                let mut values = data.chunks_exact(8).map(|c| u64_at(c, 0));
+                for field in [&mut size, &mut compressed, &mut header] {
+                    if *field == 0xFFFF_FFFF {
+                        *field = values.next().unwrap_or(*field);
+                    }
+                }
+            }
+            extra = &extra[(4 + data_len).min(extra.len())..];
+        }
+        // 名前は UTF-8 フラグが無くても UTF-8 として読めればそれを使う
+        let name = String::from_utf8_lossy(&cd[name_start..extra_start]).to_string();
+        if let Some(name) = normalize_name(&name) {
+            entries.insert(
+                name,
+                Entry::Zip {
+                    header,
+                    method,
+                    encrypted: flags & 1 != 0,
+                    compressed,
+                    size,
+                    crc,
+                },
+            );
+        }
+        pos = next;
+    }
+    Ok(entries)
+}
+
+/// tar のヘッダを順に読む（ustar の prefix、GNU の長い名前、pax の path に対応）
+fn read_tar_index<R: Read + Seek>(file: &mut R) -> Result<HashMap<String, Entry>, String> {
+    let mut entries = HashMap::new();
+    let mut offset = 0u64;
+    let mut long_name: Option<String> = None;
+    loop {
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| e.to_string())?;
+        let mut header = [0u8; 512];
+        match file.read_exact(&mut header) {
+            Ok(()) => {}
+            // 終端ブロックが省略されていても、そこまでのエントリは使う
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.to_string()),
+        }
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let size = tar_number(&header[124..136]).ok_or("tar のヘッダが壊れています")?;
+        let data = offset.checked_add(512).ok_or("tar のヘッダが壊れています")?;
+        let kind = header[156];
+        match kind {
+            b'L' | b'x' => {
+                let bytes = read_exact_vec(file, size).map_err(|e| e.to_string())?;
+                long_name = if kind == b'L' {
+                    Some(tar_string(&bytes))
+                } else {
+                    pax_path(&bytes).or(long_name)
+                };
+            }
+            b'0' | 0 | b'7' => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    let name = tar_string(&header[0..100]);
+                    let prefix = tar_string(&header[345..500]);
+                    if &header[257..262] == b"ustar" && !prefix.is_empty() {
+                        format!("{prefix}/{name}")
+                    } else {
+                        name
+                    }
+                });
+                if let Some(name) = normalize_name(&name) {
+                    entries.insert(name, Entry::Tar { data, size });
+                }
+            }
+            _ => long_name = None,
+        }
+        offset = size
+            .div_ceil(512)
+            .checked_mul(512)
+            .and_then(|padded| data.checked_add(padded))
+            .ok_or("tar のヘッダが壊れています")?;
+    }
+    Ok(entries)
+}
+
+/// エントリ名を "/" 区切りの相対パスにそろえる。ディレクトリのエントリや、
+/// アーカイブの外を指す名前（".." やドライブ指定を含むもの）は None
+fn normalize_name(name: &str) -> Option<String> {
+    if name.ends_with('/') || name.ends_with('\\') {
+        return None;
+    }
+    let parts: Vec<&str> = name
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+    if parts.is_empty() || parts.iter().any(|part| *part == ".." || part.contains(':')) {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+fn tar_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+/// サイズ欄（8 進の文字列、または先頭ビットが立った 256 進）
+fn tar_number(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 != 0 {
+        return Some(field[1..].iter().fold(0u64, |n, &b| (n << 8) | b as u64));
+    }
+    let text = tar_string(field);
+    let text = text.trim_matches(|c: char| c == ' ' || c == '\0');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+/// pax 拡張ヘッダ（"長さ key=value\n" の並び）の path
+fn pax_path(bytes: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes);
+    text.lines().find_map(|line| {
+        let (_, record) = line.split_once(' ')?;
+        record.strip_prefix("path=").map(str::to_string)
+    })
+}
+
+/// テスト用の最小の tar を作る
+#[cfg(test)]
+pub(crate) fn tar_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (name, data) in files {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        out.extend_from_slice(&header);
+        out.extend_from_slice(data);
+        out.resize(out.len().div_ceil(512) * 512, 0);
+    }
+    out.extend_from_slice(&[0; 1024]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::DeflateEncoder;
+    use std::io::Write;
+
+    /// テスト用の最小の ZIP（stored と deflate）を作る
+    fn zip_bytes(files: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for (name, data, deflate) in files {
+            let body = if *deflate {
+                let mut enc = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                enc.write_all(data).unwrap();
+                enc.finish().unwrap()
+            } else {
+                data.to_vec()
+            };
+            let method: u16 = if *deflate { 8 } else { 0 };
+            let mut crc = flate2::Crc::new();
+            crc.update(data);
+            let offset = out.len() as u32;
+            out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+            out.extend_from_slice(&[20, 0, 0, 0]);
+            out.extend_from_slice(&method.to_le_bytes());
+            out.extend_from_slice(&[0; 4]); // 時刻（読み込みでは使わない）
+            out.extend_from_slice(&crc.sum().to_le_bytes());
+            out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&body);
+
+            central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            central.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+            central.extend_from_slice(&method.to_le_bytes());
+            central.extend_from_slice(&[0; 4]);
+            central.extend_from_slice(&crc.sum().to_le_bytes());
+            central.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 12]);
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let cd_offset = out.len() as u32;
+        out.extend_from_slice(&central);
+        out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        out.extend_from_slice(&cd_offset.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn lists_and_reads_zip_and_tar_entries() {
+        let dir = std::env::temp_dir().join(format!("msbt-archive-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let zip = dir.join("book.cbz");
+        fs::write(
+            &zip,
+            zip_bytes(&[
+                ("page10.png", b"ten", false),
+                ("page2.png", b"two two two two", true),
+                ("notes.txt", b"x", false),
+                ("extra/", b"", false),
+            ]),
+        )
+        .unwrap();
+        let tar = dir.join("assets.tar");
+        fs::write(
+            &tar,
+            tar_bytes(&[("b/1.jpg", b"one"), ("a.svg", b"<svg/>")]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            list(&zip).unwrap(),
+            [zip.join("page2.png"), zip.join("page10.png")]
+        );
+        assert_eq!(read(&zip.join("page2.png")).unwrap(), b"two two two two");
+        assert_eq!(read(&zip.join("page10.png")).unwrap(), b"ten");
+        assert_eq!(
+            display(&zip.join("page10.png")),
+            format!("{} › page10.png", zip.display())
+        );
+        assert!(read(&zip.join("missing.png")).is_err());
+
+        assert_eq!(
+            list(&tar).unwrap(),
+            [tar.join("a.svg"), tar.join("b/1.jpg")]
+        );
+        let entry_path = tar.join("b").join("1.jpg");
+        let (archive, entry) = split(&entry_path).unwrap();
+        assert_eq!((archive, entry.as_str()), (tar.as_path(), "b/1.jpg"));
+        let mut source = open(&tar.join("b/1.jpg")).unwrap();
+        let mut bytes = Vec::new();
+        source.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, b"one");
+        assert!(split(&zip).is_none(), "アーカイブ自体は普通のファイル");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_entries_that_do_not_match_their_header() {
+        let dir = std::env::temp_dir().join(format!("msbt-archive-bad-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let zip = dir.join("bad.zip");
+        let data = vec![b'a'; 4096];
+        let mut bytes = zip_bytes(&[("bomb.png", &data, true), ("crc.png", b"abc", false)]);
+        // 1 件目の中央ディレクトリの展開後サイズを 16 に書き換える（実際は 4096 に展開される）
+        let cd = bytes.len() - 22 - (46 * 2 + "bomb.png".len() + "crc.png".len());
+        bytes[cd + 24..cd + 28].copy_from_slice(&16u32.to_le_bytes());
+        // 2 件目（stored）の中身を 1 バイト書き換えて CRC を合わなくする
+        let body = bytes.windows(3).position(|w| w == b"abc").unwrap();
+        bytes[body] = b'x';
+        fs::write(&zip, bytes).unwrap();
+
+        let err = read(&zip.join("bomb.png")).unwrap_err();
+        assert!(err.to_string().contains("サイズ"), "{err}");
+        let err = read(&zip.join("crc.png")).unwrap_err();
+        assert!(err.to_string().contains("CRC"), "{err}");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rereads_an_entry_after_the_archive_is_rewritten() {
+        let dir = std::env::temp_dir().join(format!("msbt-archive-stale-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let tar = dir.join("pages.tar");
+        fs::write(&tar, tar_bytes(&[("a.png", b"old")])).unwrap();
+        assert_eq!(read(&tar.join("a.png")).unwrap(), b"old");
+        // 同じ秒に書き換えても長さが変われば読み直す
+        fs::write(&tar, tar_bytes(&[("a.png", b"newer")])).unwrap();
+        assert_eq!(read(&tar.join("a.png")).unwrap(), b"newer");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn entry_names_stay_inside_the_archive() {
+        assert_eq!(normalize_name("./a/b.png").as_deref(), Some("a/b.png"));
+        assert_eq!(normalize_name("/abs/c.png").as_deref(), Some("abs/c.png"));
+        assert_eq!(normalize_name("dir\\d.png").as_deref(), Some("dir/d.png"));
+        assert_eq!(normalize_name("../escape.png"), None);
+        assert_eq!(normalize_name("a/../../e.png"), None);
+        assert_eq!(normalize_name("C:/win.png"), None);
+        assert_eq!(normalize_name("folder/"), None);
+    }
+
+    #[test]
+    fn huge_tar_size_is_a_corrupt_archive() {
+        let mut header = [0u8; 512];
+        header[..5].copy_from_slice(b"a.png");
+        header[124] = 0x80;
+        header[125..136].fill(0xFF);
+        header[156] = b'0';
+        let mut bytes = header.to_vec();
+        bytes.extend_from_slice(&[0; 1024]);
+        assert!(read_tar_index(&mut Cursor::new(bytes)).is_err());
+    }
+}
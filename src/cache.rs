@@ -0,0 +1,172 @@
+//! パス・ページ・更新日時をキーにした、メモリ上限付きの LRU キャッシュ。
+//!
+//! デコード済みのラスタ画像と解析済みの SVG ツリーを保持し、同じ画像へ戻ったときに
+//! 読み込み・デコードを省く。ファイルが書き換えられると更新日時が変わるので古い内容は使われない。
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// キャッシュのキー（更新日時が取れないファイルは None のまま比較する）
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct CacheKey {
+    pub path: PathBuf,
+    pub page: usize,
+    pub mtime: Option<SystemTime>,
+}
+
+impl CacheKey {
+    /// 現在のファイルの更新日時でキーを作る
+    pub fn new(path: &Path, page: usize) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            page,
+            mtime: crate::archive::metadata(path).and_then(|m| m.modified()).ok(),
+        }
+    }
+
+    fn same_entry(&self, other: &CacheKey) -> bool {
+        self.path == other.path && self.page == other.page
+    }
+}
+
+pub struct LruCache<V> {
+    /// 最近使ったものほど後ろ。上限を超えたら先頭から捨てる
+    entries: VecDeque<(CacheKey, V, usize)>,
+    bytes: usize,
+    budget: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl<V: Clone> LruCache<V> {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            bytes: 0,
+            budget,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// キーに一致するものを返し、最近使ったものとして後ろへ移す。
+    /// 同じファイルでも更新日時が違うものは古い内容なので捨てる
+    pub fn get(&mut self, key: &CacheKey) -> Option<V> {
+        let found = self.entries.iter().position(|(k, _, _)| k.same_entry(key));
+        let entry = found.and_then(|pos| self.entries.remove(pos));
+        match entry {
+            Some(entry) if entry.0 == *key => {
+                self.hits += 1;
+                let value = entry.1.clone();
+                self.entries.push_back(entry);
+                Some(value)
+            }
+            stale => {
+                if let Some((_, _, bytes)) = stale {
+                    self.bytes -= bytes;
+                }
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// 統計を変えずに有無だけを調べる（先読みの要否判定用）
+    pub fn contains(&self, key: &CacheKey) -> bool {
+        self.entries.iter().any(|(k, _, _)| k == key)
+    }
+
+    /// 統計・順序を変えずに、パスとページが一致するものを返す（更新日時は比べない。
+    /// ファイルを調べずに済むので、毎フレーム呼ぶ見開き表示のページ割りに使う）
+    pub fn peek(&self, path: &Path, page: usize) -> Option<&V> {
+        self.entries
+            .iter()
+            .find(|(k, _, _)| k.path == path && k.page == page)
+            .map(|(_, v, _)| v)
+    }
+
+    /// bytes は値の概算サイズ。上限を超える単体の値は保持しない
+    pub fn insert(&mut self, key: CacheKey, value: V, bytes: usize) {
+        if let Some(pos) = self.entries.iter().position(|(k, _, _)| k.same_entry(&key)) {
+            if let Some((_, _, old)) = self.entries.remove(pos) {
+                self.bytes -= old;
+            }
+        }
+        if bytes > self.budget {
+            return;
+        }
+        self.bytes += bytes;
+        self.entries.push_back((key, value, bytes));
+        self.evict();
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    /// ログ用の状態（ヒット・ミス回数、使用量、件数）
+    pub fn summary(&self) -> String {
+        format!(
+            "ヒット {} / ミス {}（{:.1}MB / {}MB, {} 件）",
+            self.hits,
+            self.misses,
+            self.bytes as f64 / (1024.0 * 1024.0),
+            self.budget / (1024 * 1024),
+            self.entries.len()
+        )
+    }
+
+    fn evict(&mut self) {
+        while self.bytes > self.budget {
+            match self.entries.pop_front() {
+                Some((_, _, bytes)) => self.bytes -= bytes,
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn key(name: &str, mtime: u64) -> CacheKey {
+        CacheKey {
+            path: PathBuf::from(name),
+            page: 0,
+            mtime: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime)),
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used_within_budget() {
+        let mut cache = LruCache::new(100);
+        cache.insert(key("a", 1), 'a', 40);
+        cache.insert(key("b", 1), 'b', 40);
+        assert_eq!(cache.get(&key("a", 1)), Some('a')); // a を最近使ったことにする
+        cache.insert(key("c", 1), 'c', 40);
+        assert!(cache.contains(&key("a", 1)));
+        assert!(!cache.contains(&key("b", 1)), "最も古い b が捨てられる");
+        cache.insert(key("huge", 1), 'h', 101);
+        assert!(!cache.contains(&key("huge", 1)), "上限より大きいものは保持しない");
+        cache.set_budget(50);
+        assert!(cache.contains(&key("c", 1)) && !cache.contains(&key("a", 1)));
+    }
+
+    #[test]
+    fn modified_file_misses_and_drops_stale_entry() {
+        let mut cache = LruCache::new(100);
+        cache.insert(key("a", 1), 'a', 10);
+        assert_eq!(cache.get(&key("a", 2)), None);
+        assert_eq!(cache.get(&key("a", 1)), None, "古い内容は捨てられている");
+        assert_eq!((cache.hits, cache.misses, cache.bytes), (0, 2, 0));
+    }
+}
@@ -0,0 +1,360 @@
+//! 埋め込み ICC プロファイルによるカラーマネジメント。
+//!
+//! image クレートのデコーダからプロファイルを取り出し、moxcms で出力プロファイル
+//! （既定は sRGB、設定 `output_icc_profile` で変更可）へ変換してからテクスチャ化する。
+//! Display P3 / Adobe RGB の写真や CMYK JPEG が、未変換のまま表示されて色がずれるのを防ぐ。
+//!
+//! CMYK JPEG は image が素朴な式で RGB にしてしまうため、zune-jpeg で CMYK のまま読み直して
+//! プロファイルで変換する。WIC 経由（HEIC 等）・2 ページ目以降・アニメーションは対象外。
+
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use moxcms::{ColorProfile, DataColorSpace, Layout, ProfileText, TransformOptions};
+use std::path::Path;
+use zune_jpeg::zune_core::colorspace::ColorSpace;
+use zune_jpeg::zune_core::options::DecoderOptions;
+
+/// 表示中の画像に対するカラーマネジメントの状態（タイトルバーの表示に使う）
+#[derive(Debug, Clone, PartialEq)]
+pub enum IccState {
+    /// 埋め込みプロファイルなし（sRGB とみなしてそのまま表示）
+    None,
+    /// プロファイル（名前）に従って出力プロファイルへ変換した
+    Applied(String),
+    /// プロファイルはあるが、カラーマネジメント無効のため変換していない
+    Unmanaged(String),
+    /// プロファイルの解析・変換に失敗したため変換していない
+    Failed(String),
+}
+
+/// image クレートでデコードし、埋め込み ICC プロファイルがあれば一緒に返す
+/// （`image::open` と同じく拡張子から形式を決める）
+pub fn decode_with_icc(path: &Path) -> Result<(DynamicImage, Option<Vec<u8>>), String> {
+    let mut reader = ImageReader::new(crate::archive::open(path).map_err(|e| e.to_string())?);
+    if let Ok(format) = ImageFormat::from_path(path) {
+        reader.set_format(format);
+    }
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|e| e.to_string())?;
+    // プロファイルが壊れていても画像自体は表示したいので、取得失敗は「なし」扱い
+    let icc = decoder.icc_profile().ok().flatten().filter(|p| !p.is_empty());
+    let image = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    Ok((image, icc))
+}
+
+/// 出力先プロファイルを用意する。path が空なら sRGB
+pub fn output_profile(path: &str) -> Result<ColorProfile, String> {
+    if path.trim().is_empty() {
+        return Ok(ColorProfile::new_srgb());
+    }
+    let bytes = std::fs::read(path).map_err(|e| format!("出力プロファイルを開けません: {path} ({e})"))?;
+    let profile = ColorProfile::new_from_slice(&bytes)
+        .map_err(|e| format!("出力プロファイルの解析に失敗: {path} ({e:?})"))?;
+    if profile.color_space != DataColorSpace::Rgb {
+        return Err(format!("出力プロファイルは RGB である必要があります: {path}"));
+    }
+    Ok(profile)
+}
+
+/// プロファイルの表示名（desc タグ）。無ければ "ICC"
+pub fn profile_name(profile: &ColorProfile) -> String {
+    let name = match &profile.description {
+        Some(ProfileText::PlainString(s)) => s.clone(),
+        Some(ProfileText::Localizable(list)) => {
+            list.first().map(|l| l.value.clone()).unwrap_or_default()
+        }
+        Some(ProfileText::Description(d)) => d.ascii_string.clone(),
+        None => String::new(),
+    };
+    let name = name.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    if name.is_empty() {
+        "ICC".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// 埋め込みプロファイル icc に従って image を output へ変換する。
+/// 成功時は変換後の画像とプロファイル名を返す
+pub fn convert(
+    image: &DynamicImage,
+    icc: &[u8],
+    path: &Path,
+    output: &ColorProfile,
+) -> Result<(DynamicImage, String), String> {
+    let source =
+        ColorProfile::new_from_slice(icc).map_err(|e| format!("ICC プロファイルの解析に失敗: {e:?}"))?;
+    let name = profile_name(&source);
+    let converted = match source.color_space {
+        DataColorSpace::Rgb => convert_rgb(image, &source, output)?,
+        DataColorSpace::Gray => convert_gray(image, &source, output)?,
+        DataColorSpace::Cmyk => convert_cmyk_jpeg(path, &source, output)?,
+        other => return Err(format!("未対応のプロファイル色空間です: {other:?}")),
+    };
+    Ok((converted, name))
+}
+
+/// RGB プロファイル。浮動小数（HDR/EXR）は 1.0 を超える値を保ったまま f32 で、
+/// 16bit は 16bit のまま変換する（8bit に丸めてからだと HDR の階調が失われる）
+fn convert_rgb(
+    image: &DynamicImage,
+    source: &ColorProfile,
+    output: &ColorProfile,
+) -> Result<DynamicImage, String> {
+    if is_float(image) {
+        let src = image.to_rgba32f();
+        // ガンマ曲線を式で評価させ、0..1 の外の値も切り詰めない
+        let extended = TransformOptions {
+            allow_extended_range_rgb_xyz: true,
+            ..TransformOptions::default()
+        };
+        let transform = source
+            .create_transform_f32(Layout::Rgba, output, Layout::Rgba, extended)
+            .map_err(|e| format!("色変換を作成できません: {e:?}"))?;
+        let mut dst = vec![0f32; src.as_raw().len()];
+        transform
+            .transform(src.as_raw(), &mut dst)
+            .map_err(|e| format!("色変換に失敗: {e:?}"))?;
+        return image::Rgba32FImage::from_raw(src.width(), src.height(), dst)
+            .map(DynamicImage::ImageRgba32F)
+            .ok_or_else(|| "色変換後のバッファサイズが不一致".to_string());
+    }
+    if is_16bit(image) {
+        let src = image.to_rgba16();
+        let transform = source
+            .create_transform_16bit(Layout::Rgba, output, Layout::Rgba, TransformOptions::default())
+            .map_err(|e| format!("色変換を作成できません: {e:?}"))?;
+        let mut dst = vec![0u16; src.as_raw().len()];
+        transform
+            .transform(src.as_raw(), &mut dst)
+            .map_err(|e| format!("色変換に失敗: {e:?}"))?;
+        return image::ImageBuffer::from_raw(src.width(), src.height(), dst)
+            .map(DynamicImage::ImageRgba16)
+            .ok_or_else(|| "色変換後のバッファサイズが不一致".to_string());
+    }
+    let src = image.to_rgba8();
+    let transform = source
+        .create_transform_8bit(Layout::Rgba, output, Layout::Rgba, TransformOptions::default())
+        .map_err(|e| format!("色変換を作成できません: {e:?}"))?;
+    let mut dst = vec![0u8; src.as_raw().len()];
+    transform
+        .transform(src.as_raw(), &mut dst)
+        .map_err(|e| format!("色変換に失敗: {e:?}"))?;
+    image::RgbaImage::from_raw(src.width(), src.height(), dst)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| "色変換後のバッファサイズが不一致".to_string())
+}
+
+/// グレースケールプロファイル。輝度だけを変換し、アルファはそのまま残す（16bit は 16bit のまま）
+fn convert_gray(
+    image: &DynamicImage,
+    source: &ColorProfile,
+    output: &ColorProfile,
+) -> Result<DynamicImage, String> {
+    if is_16bit(image) {
+        let src = image.to_luma_alpha16();
+        let gray: Vec<u16> = src.pixels().map(|p| p.0[0]).collect();
+        let transform = source
+            .create_transform_16bit(Layout::Gray, output, Layout::Rgb, TransformOptions::default())
+            .map_err(|e| format!("色変換を作成できません: {e:?}"))?;
+        let mut rgb = vec![0u16; gray.len() * 3];
+        transform
+            .transform(&gray, &mut rgb)
+            .map_err(|e| format!("色変換に失敗: {e:?}"))?;
+        let rgba: Vec<u16> = rgb
+            .chunks_exact(3)
+            .zip(src.pixels())
+            .flat_map(|(c, p)| [c[0], c[1], c[2], p.0[1]])
+            .collect();
+        return image::ImageBuffer::from_raw(src.width(), src.height(), rgba)
+            .map(DynamicImage::ImageRgba16)
+            .ok_or_else(|| "色変換後のバッファサイズが不一致".to_string());
+    }
+    let src = image.to_luma_alpha8();
+    let gray: Vec<u8> = src.pixels().map(|p| p.0[0]).collect();
+    let transform = source
+        .create_transform_8bit(Layout::Gray, output, Layout::Rgb, TransformOptions::default())
+        .map_err(|e| format!("色変換を作成できません: {e:?}"))?;
+    let mut rgb = vec![0u8; gray.len() * 3];
+    transform
+        .transform(&gray, &mut rgb)
+        .map_err(|e| format!("色変換に失敗: {e:?}"))?;
+    let rgba: Vec<u8> = rgb
+        .chunks_exact(3)
+        .zip(src.pixels())
+        .flat_map(|(c, p)| [c[0], c[1], c[2], p.0[1]])
+        .collect();
+    image::RgbaImage::from_raw(src.width(), src.height(), rgba)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| "色変換後のバッファサイズが不一致".to_string())
+}
+
+fn is_float(image: &DynamicImage) -> bool {
+    matches!(image.color(), image::ColorType::Rgb32F | image::ColorType::Rgba32F)
+}
+
+fn is_16bit(image: &DynamicImage) -> bool {
+    matches!(
+        image.color(),
+        image::ColorType::Rgb16 | image::ColorType::Rgba16 | image::ColorType::L16 | image::ColorType::La16
+    )
+}
+
+/// CMYK JPEG を CMYK のまま読み直し、プロファイルで RGB へ変換する
+fn convert_cmyk_jpeg(
+    path: &Path,
+    source: &ColorProfile,
+    output: &ColorProfile,
+) -> Result<DynamicImage, String> {
+    let bytes = crate::archive::read(path).map_err(|e| format!("ファイルを開けません: {e}"))?;
+    let options = DecoderOptions::default().jpeg_set_out_colorspace(ColorSpace::CMYK);
+    let mut decoder = zune_jpeg::JpegDecoder::new_with_options(
+        zune_jpeg::zune_core::bytestream::ZCursor::new(&bytes),
+        options,
+    );
+    let mut cmyk = decoder
+        .decode()
+        .map_err(|e| format!("CMYK JPEG のデコードに失敗: {e:?}"))?;
+    if decoder.input_colorspace() != Some(ColorSpace::CMYK) {
+        // YCCK 等は CMYK のまま取り出せないので、image の素朴な変換に任せる
+        return Err("CMYK として取り出せない JPEG です".into());
+    }
+    let (w, h) = decoder
+        .dimensions()
+        .ok_or_else(|| "JPEG のサイズを取得できません".to_string())?;
+    // Adobe（Photoshop）の CMYK JPEG はインク量を反転して格納している
+    if has_adobe_marker(&bytes) {
+        cmyk.iter_mut().for_each(|v| *v = 255 - *v);
+    }
+    let transform = source
+        .create_transform_8bit(Layout::Rgba, output, Layout::Rgb, TransformOptions::default())
+        .map_err(|e| format!("色変換を作成できません: {e:?}"))?;
+    let mut rgb = vec![0u8; cmyk.len() / 4 * 3];
+    transform
+        .transform(&cmyk, &mut rgb)
+        .map_err(|e| format!("色変換に失敗: {e:?}"))?;
+    image::RgbImage::from_raw(w as u32, h as u32, rgb)
+        .map(DynamicImage::ImageRgb8)
+        .ok_or_else(|| "色変換後のバッファサイズが不一致".to_string())
+}
+
+/// JPEG のヘッダ部（SOS まで）に Adobe APP14 マーカーがあるか
+fn has_adobe_marker(jpeg: &[u8]) -> bool {
+    let mut i = 2; // SOI の後ろから
+    while i + 4 <= jpeg.len() && jpeg[i] == 0xFF {
+        let marker = jpeg[i + 1];
+        if marker == 0xDA {
+            break; // SOS 以降は圧縮データ
+        }
+        let len = u16::from_be_bytes([jpeg[i + 2], jpeg[i + 3]]) as usize;
+        if marker == 0xEE && jpeg[i + 4..].starts_with(b"Adobe") {
+            return true;
+        }
+        i += 2 + len;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adobe_marker_detection() {
+        // SOI, APP0(len 4), APP14 "Adobe", SOS
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00];
+        assert!(!has_adobe_marker(&jpeg));
+        jpeg.extend_from_slice(&[0xFF, 0xEE, 0x00, 0x07]);
+        jpeg.extend_from_slice(b"Adobe");
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02]);
+        assert!(has_adobe_marker(&jpeg));
+        // SOS より後ろにある "Adobe" は見ない
+        let late = [&[0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x02][..], b"\xFF\xEE\x00\x07Adobe"].concat();
+        assert!(!has_adobe_marker(&late));
+    }
+
+    #[test]
+    fn srgb_to_srgb_is_nearly_identity() {
+        let srgb = ColorProfile::new_srgb();
+        let icc = srgb.encode().unwrap();
+        let img = image::RgbaImage::from_raw(2, 1, vec![200, 100, 50, 128, 0, 255, 0, 255]).unwrap();
+        let (out, _) =
+            convert(&DynamicImage::ImageRgba8(img.clone()), &icc, Path::new("x.png"), &srgb).unwrap();
+        for (a, b) in out.to_rgba8().as_raw().iter().zip(img.as_raw()) {
+            assert!(a.abs_diff(*b) <= 2, "{a} vs {b}");
+        }
+    }
+
+    #[test]
+    fn wide_gamut_red_is_clipped_into_srgb() {
+        // Display P3 の純赤は sRGB の赤より鮮やかなので、変換しても (255,0,0) 付近に収まり、
+        // 逆に P3 の中間色は sRGB 値が変わる（未変換だと彩度が落ちて見える原因）
+        let p3 = ColorProfile::new_display_p3().encode().unwrap();
+        let srgb = ColorProfile::new_srgb();
+        let img = image::RgbaImage::from_raw(2, 1, vec![255, 0, 0, 255, 0, 160, 0, 255]).unwrap();
+        let (out, _) =
+            convert(&DynamicImage::ImageRgba8(img), &p3, Path::new("x.jpg"), &srgb).unwrap();
+        let out = out.to_rgba8();
+        assert!(out.get_pixel(0, 0).0[0] >= 250);
+        assert_ne!(out.get_pixel(1, 0).0, [0, 160, 0, 255]);
+        assert_eq!(out.get_pixel(1, 0).0[3], 255, "アルファは保たれる");
+    }
+
+    #[test]
+    fn float_images_stay_float_and_keep_values_above_one() {
+        let srgb = ColorProfile::new_srgb();
+        let icc = srgb.encode().unwrap();
+        let img = image::Rgba32FImage::from_raw(2, 1, vec![4.0, 2.0, 0.25, 1.0, 0.5, 0.5, 0.5, 0.5])
+            .unwrap();
+        let (out, _) =
+            convert(&DynamicImage::ImageRgba32F(img), &icc, Path::new("x.exr"), &srgb).unwrap();
+        let DynamicImage::ImageRgba32F(out) = out else {
+            panic!("浮動小数のまま返す");
+        };
+        assert!(out.get_pixel(0, 0).0[0] > 1.5, "{:?}", out.get_pixel(0, 0));
+        assert!((out.get_pixel(1, 0).0[1] - 0.5).abs() < 0.01);
+        assert_eq!(out.get_pixel(1, 0).0[3], 0.5, "アルファは保たれる");
+    }
+
+    #[test]
+    fn sixteen_bit_images_keep_their_depth() {
+        let srgb = ColorProfile::new_srgb();
+        let icc = srgb.encode().unwrap();
+        let img: image::ImageBuffer<image::Rgba<u16>, _> =
+            image::ImageBuffer::from_raw(1, 1, vec![20000u16, 30000, 40000, 65535]).unwrap();
+        let (out, _) =
+            convert(&DynamicImage::ImageRgba16(img), &icc, Path::new("x.png"), &srgb).unwrap();
+        let DynamicImage::ImageRgba16(out) = out else {
+            panic!("16bit のまま返す");
+        };
+        // 同じプロファイルどうしなので値はほぼ変わらない。8bit に丸めていれば
+        // 20000 → 78×257 = 20046、30000 → 117×257 = 30069、40000 → 156×257 = 40092 になる
+        let rgba = out.get_pixel(0, 0).0;
+        for (got, want) in rgba.into_iter().zip([20000u16, 30000, 40000, 65535]) {
+            assert!(got.abs_diff(want) <= 4, "{rgba:?}");
+        }
+    }
+
+    #[test]
+    fn gray_profile_keeps_alpha() {
+        let gray = ColorProfile::new_gray_with_gamma(2.2).encode().unwrap();
+        let img = image::GrayAlphaImage::from_raw(2, 1, vec![0, 10, 255, 200]).unwrap();
+        let (out, _) = convert(
+            &DynamicImage::ImageLumaA8(img),
+            &gray,
+            Path::new("x.png"),
+            &ColorProfile::new_srgb(),
+        )
+        .unwrap();
+        let out = out.to_rgba8();
+        assert_eq!(out.get_pixel(0, 0).0[3], 10);
+        assert_eq!(out.get_pixel(1, 0).0[3], 200);
+        assert!(out.get_pixel(1, 0).0[0] >= 250, "白は白のまま");
+    }
+
+    #[test]
+    fn empty_output_path_means_srgb() {
+        assert!(output_profile("").is_ok());
+        assert!(output_profile("no/such/profile.icc").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+//! 比較表示の差分（Difference）モード: 同じ大きさの 2 枚の画像のピクセルごとの差。
+//!
+//! 差は不透明度を掛けた色で比べる（完全に透明なピクセルどうしは、色の値が違っても一致とみなす）。
+//! 差の絶対値はピクセルごとに保持しておき、増幅率を変えたときは表示用の画像だけを作り直す。
+
+use eframe::egui;
+use image::RgbaImage;
+
+pub struct Difference {
+    size: [usize; 2],
+    /// ピクセルごとの差の絶対値（R, G, B, A）
+    deltas: Vec<[u8; 4]>,
+    /// いずれかのチャンネルが異なるピクセルの数
+    pub changed: usize,
+    /// チャンネルの差の最大値
+    pub max: u8,
+}
+
+impl Difference {
+    pub fn compute(a: &RgbaImage, b: &RgbaImage) -> Result<Self, String> {
+        if a.dimensions() != b.dimensions() {
+            return Err(format!(
+                "画像の大きさが違います（{}x{} と {}x{}）",
+                a.width(),
+                a.height(),
+                b.width(),
+                b.height()
+            ));
+        }
+        let mut changed = 0;
+        let mut max = 0;
+        let deltas: Vec<[u8; 4]> = a
+            .pixels()
+            .zip(b.pixels())
+            .map(|(pa, pb)| {
+                let (pa, pb) = (premultiply(pa.0), premultiply(pb.0));
+                let delta: [u8; 4] = std::array::from_fn(|i| pa[i].abs_diff(pb[i]));
+                let largest = delta.iter().copied().max().unwrap_or(0);
+                if largest > 0 {
+                    changed += 1;
+                    max = max.max(largest);
+                }
+                delta
+            })
+            .collect();
+        Ok(Self {
+            size: [a.width() as usize, a.height() as usize],
+            deltas,
+            changed,
+            max,
+        })
+    }
+
+    /// 比べたピクセルの総数
+    pub fn total(&self) -> usize {
+        self.deltas.len()
+    }
+
+    /// 差を gain 倍した明るさで表す不透明な画像（一致するピクセルは黒）。
+    /// 不透明度だけの差は灰色になるよう RGB の各チャンネルに含める
+    pub fn to_image(&self, gain: f32) -> egui::ColorImage {
+        let pixels = self
+            .deltas
+            .iter()
+            .map(|&[r, g, b, a]| {
+                let amplify = |v: u8| (v.max(a) as f32 * gain).min(255.0) as u8;
+                egui::Color32::from_rgb(amplify(r), amplify(g), amplify(b))
+            })
+            .collect();
+        egui::ColorImage {
+            size: self.size,
+            pixels,
+        }
+    }
+}
+
+fn premultiply([r, g, b, a]: [u8; 4]) -> [u8; 4] {
+    let mul = |c: u8| ((c as u16 * a as u16 + 127) / 255) as u8;
+    [mul(r), mul(g), mul(b), a]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn counts_changed_pixels_and_amplifies_difference() {
+        let a = RgbaImage::from_pixel(3, 2, Rgba([100, 100, 100, 255]));
+        let mut b = a.clone();
+        b.put_pixel(1, 0, Rgba([104, 100, 98, 255]));
+        // 完全に透明なピクセルは色が違っても一致とみなす
+        let mut a_clear = a.clone();
+        a_clear.put_pixel(2, 1, Rgba([255, 0, 0, 0]));
+        b.put_pixel(2, 1, Rgba([0, 0, 255, 0]));
+
+        let diff = Difference::compute(&a_clear, &b).unwrap();
+        assert_eq!((diff.changed, diff.total(), diff.max), (1, 6, 4));
+        let image = diff.to_image(10.0);
+        assert_eq!(image.pixels[1], egui::Color32::from_rgb(40, 0, 20));
+        assert_eq!(image.pixels[0], egui::Color32::BLACK);
+        assert_eq!(diff.to_image(100.0).pixels[1].r(), 255);
+
+        let small = RgbaImage::new(2, 2);
+        assert!(Difference::compute(&a, &small).is_err());
+    }
+}
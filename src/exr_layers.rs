@@ -0,0 +1,395 @@
+//! OpenEXR のレイヤー・チャンネル選択。
+//!
+//! image クレートは EXR を RGBA 1 枚に畳んでしまうため、マルチレイヤー（AOV）の EXR は
+//! exr クレートで全チャンネルを読み、レイヤー単位またはチャンネル単位で表示する。
+//! レイヤーは「パートのレイヤー名 ＋ チャンネル名の最後の `.` より前」でまとめる
+//! （1 パートに `diffuse.R` `specular.R` … を並べる形式と、パートごとに分ける形式の両方に対応）。
+//!
+//! 色レイヤーは HDR として露出・トーンマッピングの対象にし、深度・法線・単一チャンネルは
+//! 値域を自動で合わせた表示用画像（8bit）にする。
+
+use image::{Rgba32FImage, RgbaImage};
+use std::path::Path;
+
+/// 深度のように「背景が無限遠」の値を値域の計算から外すしきい値
+const FAR_VALUE: f32 = 1e10;
+
+/// 読み込んだ EXR の全レイヤー
+pub struct ExrFile {
+    pub layers: Vec<ExrLayer>,
+}
+
+/// 表示上の 1 レイヤー（同じ接頭辞を持つチャンネルの集まり）
+pub struct ExrLayer {
+    /// 表示名（接頭辞なしの既定レイヤーは空文字）
+    pub name: String,
+    width: usize,
+    height: usize,
+    pub channels: Vec<ExrChannel>,
+}
+
+pub struct ExrChannel {
+    /// 接頭辞を除いたチャンネル名（"R", "Z" など）
+    pub name: String,
+    samples: Vec<f32>,
+}
+
+/// 表示中のレイヤーとチャンネル（None ならレイヤー全体）
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Selection {
+    pub layer: usize,
+    pub channel: Option<usize>,
+}
+
+/// レイヤーの種類ごとの既定の見せ方
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum LayerKind {
+    /// RGB(A)。HDR としてトーンマッピングする
+    Color,
+    /// 深度。手前を明るく、値域を自動で合わせたグレースケール
+    Depth,
+    /// 法線（-1..1）。0.5 + 0.5n で RGB に割り当てる
+    Normal,
+    /// その他（任意チャンネル）。先頭チャンネルをグレースケールで表示
+    Data,
+}
+
+/// 選択の表示結果
+pub enum View {
+    /// シーンの明るさを持つ色（HDR として露出・トーンマッピングを掛ける）
+    Color(Rgba32FImage),
+    /// そのまま表示する 8bit 画像（深度・法線・単一チャンネル）
+    Display(RgbaImage),
+}
+
+/// EXR の全レイヤー・全チャンネルを読む（最大解像度のみ。ディープデータは非対応）
+pub fn read(path: &Path) -> Result<ExrFile, String> {
+    use exr::prelude::*;
+    let image = read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .all_channels()
+        .all_layers()
+        .all_attributes()
+        .from_buffered(crate::archive::open(path).map_err(|e| format!("ファイルを開けません: {e}"))?)
+        .map_err(|e| format!("EXR の読み込みに失敗: {e}"))?;
+
+    let mut layers: Vec<ExrLayer> = Vec::new();
+    for layer in &image.layer_data {
+        let part = layer
+            .attributes
+            .layer_name
+            .as_ref()
+            .map(|t| t.to_string())
+            .unwrap_or_default();
+        for channel in &layer.channel_data.list {
+            let full = channel.name.to_string();
+            let (prefix, short) = match full.rfind('.') {
+                Some(i) => (&full[..i], &full[i + 1..]),
+                None => ("", full.as_str()),
+            };
+            let name = match (part.is_empty(), prefix.is_empty()) {
+                (true, _) => prefix.to_string(),
+                (false, true) => part.clone(),
+                (false, false) => format!("{part}.{prefix}"),
+            };
+            let target = match layers.iter().position(|l| l.name == name) {
+                Some(i) => i,
+                None => {
+                    layers.push(ExrLayer {
+                        name,
+                        width: layer.size.width(),
+                        height: layer.size.height(),
+                        channels: Vec::new(),
+                    });
+                    layers.len() - 1
+                }
+            };
+            layers[target].channels.push(ExrChannel {
+                name: short.to_string(),
+                samples: channel.sample_data.values_as_f32().collect(),
+            });
+        }
+    }
+    if layers.is_empty() {
+        return Err("EXR にチャンネルがありません".into());
+    }
+    Ok(ExrFile { layers })
+}
+
+impl ExrFile {
+    /// 保持しているサンプルのバイト数
+    pub fn bytes(&self) -> usize {
+        self.layers
+            .iter()
+            .flat_map(|l| &l.channels)
+            .map(|c| c.samples.len() * 4)
+            .sum()
+    }
+
+    /// 最初に表示するレイヤー。既定（接頭辞なし）の色レイヤー、なければ最初の色レイヤー
+    pub fn default_selection(&self) -> Selection {
+        let layer = self
+            .layers
+            .iter()
+            .position(|l| l.name.is_empty() && l.kind() == LayerKind::Color)
+            .or_else(|| self.layers.iter().position(|l| l.kind() == LayerKind::Color))
+            .unwrap_or(0);
+        Selection {
+            layer,
+            channel: None,
+        }
+    }
+
+    /// メニュー・タイトル用の選択名（"diffuse", "diffuse.R" など）
+    pub fn label(&self, sel: Selection) -> String {
+        let Some(layer) = self.layers.get(sel.layer) else {
+            return String::new();
+        };
+        let base = if layer.name.is_empty() { "(default)" } else { &layer.name };
+        match sel.channel.and_then(|c| layer.channels.get(c)) {
+            Some(ch) => format!("{}.{}", base, ch.name),
+            None => base.to_string(),
+        }
+    }
+
+    /// 選択を表示用に描く。チャンネルの画素数がレイヤーと合わない（サブサンプリング・壊れた
+    /// ファイル）ときはエラー
+    pub fn render(&self, sel: Selection) -> Result<View, String> {
+        let layer = self
+            .layers
+            .get(sel.layer.min(self.layers.len().saturating_sub(1)))
+            .ok_or("EXR にレイヤーがありません")?;
+        if let Some(ch) = sel.channel.and_then(|c| layer.channels.get(c)) {
+            return Ok(View::Display(layer.gray(layer.checked(ch)?, false)?));
+        }
+        let first = layer.channels.first().ok_or("EXR のレイヤーにチャンネルがありません")?;
+        Ok(match layer.kind() {
+            LayerKind::Color => View::Color(layer.color()?),
+            LayerKind::Depth => View::Display(layer.gray(layer.checked(first)?, true)?),
+            LayerKind::Normal => View::Display(layer.normal()?),
+            LayerKind::Data => View::Display(layer.gray(layer.checked(first)?, false)?),
+        })
+    }
+}
+
+impl ExrLayer {
+    fn channel(&self, names: &[&str]) -> Option<&[f32]> {
+        self.channels
+            .iter()
+            .find(|c| names.iter().any(|n| c.name.eq_ignore_ascii_case(n)))
+            .map(|c| c.samples.as_slice())
+    }
+
+    /// チャンネルのサンプル。画素数がレイヤーと一致しなければエラー
+    fn checked<'a>(&self, channel: &'a ExrChannel) -> Result<&'a [f32], String> {
+        if channel.samples.len() == self.width * self.height {
+            Ok(&channel.samples)
+        } else {
+            Err(format!(
+                "EXR のチャンネル {} の画素数がレイヤーと一致しません（サブサンプリングには非対応）",
+                channel.name
+            ))
+        }
+    }
+
+    /// 名前で探したチャンネルのサンプル（画素数を確かめたもの）
+    fn channel_checked(&self, names: &[&str]) -> Result<Option<&[f32]>, String> {
+        self.channels
+            .iter()
+            .find(|c| names.iter().any(|n| c.name.eq_ignore_ascii_case(n)))
+            .map(|c| self.checked(c))
+            .transpose()
+    }
+
+    fn kind(&self) -> LayerKind {
+        let name = self.name.to_lowercase();
+        let has = |n: &[&str]| self.channel(n).is_some();
+        if name.contains("normal") || (name == "n" && self.channels.len() >= 3) {
+            LayerKind::Normal
+        } else if name.contains("depth") || (self.channels.len() == 1 && has(&["Z"])) {
+            LayerKind::Depth
+        } else if (has(&["R"]) && has(&["G"]) && has(&["B"]))
+            || (self.channels.len() == 1 && has(&["Y"]))
+        {
+            // 輝度のみの画像もシーンの明るさとして扱う
+            LayerKind::Color
+        } else {
+            LayerKind::Data
+        }
+    }
+
+    fn color(&self) -> Result<Rgba32FImage, String> {
+        let n = self.width * self.height;
+        let y = self.channel_checked(&["Y"])?;
+        let r = self.channel_checked(&["R"])?.or(y);
+        let g = self.channel_checked(&["G"])?.or(y);
+        let b = self.channel_checked(&["B"])?.or(y);
+        let a = self.channel_checked(&["A"])?;
+        let at = |c: Option<&[f32]>, i: usize, default: f32| c.map_or(default, |c| c[i]);
+        let data = (0..n)
+            .flat_map(|i| [at(r, i, 0.0), at(g, i, 0.0), at(b, i, 0.0), at(a, i, 1.0)])
+            .collect();
+        Rgba32FImage::from_raw(self.width as u32, self.height as u32, data)
+            .ok_or_else(|| "EXR のレイヤーの大きさが不正です".to_string())
+    }
+
+    /// 1 チャンネルを値域に合わせたグレースケールにする。near_bright なら小さい値を明るくする（深度）
+    fn gray(&self, samples: &[f32], near_bright: bool) -> Result<RgbaImage, String> {
+        let (lo, hi) = value_range(samples);
+        let span = (hi - lo).max(f32::EPSILON);
+        let data = samples
+            .iter()
+            .flat_map(|&v| {
+                let t = if v.is_finite() && v < FAR_VALUE {
+                    ((v - lo) / span).clamp(0.0, 1.0)
+                } else {
+                    1.0 // 無限遠・NaN は最も遠い扱い
+                };
+                let t = if near_bright { 1.0 - t } else { t };
+                let g = (t * 255.0 + 0.5) as u8;
+                [g, g, g, 255]
+            })
+            .collect();
+        RgbaImage::from_raw(self.width as u32, self.height as u32, data)
+            .ok_or_else(|| "EXR のレイヤーの大きさが不正です".to_string())
+    }
+
+    /// 法線 (-1..1) を 0.5 + 0.5n で RGB に割り当てる
+    fn normal(&self) -> Result<RgbaImage, String> {
+        let first = self.channels.first().ok_or("EXR のレイヤーにチャンネルがありません")?;
+        let x = match self.channel_checked(&["X", "R"])? {
+            Some(x) => x,
+            None => self.checked(first)?,
+        };
+        let y = self.channel_checked(&["Y", "G"])?.unwrap_or(x);
+        let z = self.channel_checked(&["Z", "B"])?.unwrap_or(x);
+        let enc = |v: f32| ((v * 0.5 + 0.5).clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+        let data = (0..self.width * self.height)
+            .flat_map(|i| [enc(x[i]), enc(y[i]), enc(z[i]), 255])
+            .collect();
+        RgbaImage::from_raw(self.width as u32, self.height as u32, data)
+            .ok_or_else(|| "EXR のレイヤーの大きさが不正です".to_string())
+    }
+}
+
+/// 有限かつ無限遠でない値の最小・最大。該当なしなら (0, 1)
+fn value_range(samples: &[f32]) -> (f32, f32) {
+    let (lo, hi) = samples
+        .iter()
+        .filter(|v| v.is_finite() && **v < FAR_VALUE)
+        .fold((f32::MAX, f32::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    if lo > hi {
+        (0.0, 1.0)
+    } else {
+        (lo, hi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_aov_exr(path: &Path) {
+        use exr::prelude::*;
+        let size = (2, 1);
+        let ch = |name: &str, v: [f32; 2]| AnyChannel::new(name, FlatSamples::F32(v.to_vec()));
+        let channels = AnyChannels::sort(SmallVec::from_vec(vec![
+            ch("R", [1.0, 0.5]),
+            ch("G", [0.0, 0.5]),
+            ch("B", [0.0, 0.5]),
+            ch("diffuse.R", [0.2, 0.2]),
+            ch("diffuse.G", [0.3, 0.3]),
+            ch("diffuse.B", [0.4, 0.4]),
+            ch("depth.Z", [1.0, 5.0]),
+            ch("N.X", [1.0, -1.0]),
+            ch("N.Y", [0.0, 0.0]),
+            ch("N.Z", [0.0, 0.0]),
+            ch("coverage.V", [4.0, 0.25]),
+        ]));
+        let layer = Layer::new(size, LayerAttributes::default(), Encoding::default(), channels);
+        Image::from_layer(layer).write().to_file(path).unwrap();
+    }
+
+    #[test]
+    fn aov_layers_are_grouped_and_visualized() {
+        let dir = std::env::temp_dir().join(format!("msbt-exr-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("aov.exr");
+        write_aov_exr(&path);
+        let file = read(&path).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut names: Vec<_> = file.layers.iter().map(|l| l.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["", "N", "coverage", "depth", "diffuse"]);
+        let sel = file.default_selection();
+        assert_eq!(file.layers[sel.layer].name, "", "既定の RGB レイヤーから表示する");
+
+        let find = |n: &str| file.layers.iter().position(|l| l.name == n).unwrap();
+        // 深度: 手前（1.0）が白、奥（5.0）が黒
+        match file.render(Selection { layer: find("depth"), channel: None }).unwrap() {
+            View::Display(img) => {
+                assert_eq!(img.get_pixel(0, 0).0, [255, 255, 255, 255]);
+                assert_eq!(img.get_pixel(1, 0).0, [0, 0, 0, 255]);
+            }
+            View::Color(_) => panic!("深度は表示用画像になる"),
+        }
+        // 法線: +X は (255,128,128)
+        match file.render(Selection { layer: find("N"), channel: None }).unwrap() {
+            View::Display(img) => assert_eq!(img.get_pixel(0, 0).0, [255, 128, 128, 255]),
+            View::Color(_) => panic!("法線は表示用画像になる"),
+        }
+        // 色レイヤーは浮動小数のまま
+        match file.render(Selection { layer: find("diffuse"), channel: None }).unwrap() {
+            View::Color(img) => assert_eq!(img.get_pixel(0, 0).0, [0.2, 0.3, 0.4, 1.0]),
+            View::Display(_) => panic!("色レイヤーは HDR として扱う"),
+        }
+        // 単一チャンネルは値域に合わせたグレースケール: 最小が 0、最大が 255、R == G == B
+        let gray = |sel: Selection| match file.render(sel).unwrap() {
+            View::Display(img) => [img.get_pixel(0, 0).0, img.get_pixel(1, 0).0],
+            View::Color(_) => panic!("単一チャンネルは表示用画像になる"),
+        };
+        assert_eq!(
+            gray(Selection { layer: find("coverage"), channel: None }),
+            [[255, 255, 255, 255], [0, 0, 0, 255]]
+        );
+        let base = find("");
+        let r = file.layers[base].channels.iter().position(|c| c.name == "R").unwrap();
+        assert_eq!(
+            gray(Selection { layer: base, channel: Some(r) }),
+            [[255, 255, 255, 255], [0, 0, 0, 255]]
+        );
+        let diffuse = find("diffuse");
+        let label = file.label(Selection { layer: diffuse, channel: Some(0) });
+        assert!(label.starts_with("diffuse."), "{label}");
+    }
+
+    #[test]
+    fn mismatched_channel_lengths_are_an_error() {
+        let layer = |channels: Vec<ExrChannel>| ExrFile {
+            layers: vec![ExrLayer {
+                name: String::new(),
+                width: 2,
+                height: 2,
+                channels,
+            }],
+        };
+        let ch = |name: &str, n: usize| ExrChannel {
+            name: name.into(),
+            samples: vec![0.5; n],
+        };
+        let sel = Selection::default();
+        // 色差チャンネルがサブサンプリングされている等で、画素数が足りない
+        assert!(layer(vec![ch("R", 4), ch("G", 1), ch("B", 4)]).render(sel).is_err());
+        assert!(layer(vec![ch("Z", 3)]).render(sel).is_err());
+        assert!(layer(vec![ch("foo", 4)]).render(Selection { layer: 0, channel: Some(0) }).is_ok());
+        assert!(layer(vec![ch("R", 4), ch("G", 4), ch("B", 4)]).render(sel).is_ok());
+    }
+
+    #[test]
+    fn range_ignores_infinite_background() {
+        assert_eq!(value_range(&[2.0, f32::INFINITY, 4.0, 1e20, f32::NAN]), (2.0, 4.0));
+        assert_eq!(value_range(&[]), (0.0, 1.0));
+    }
+}
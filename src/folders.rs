@@ -0,0 +1,339 @@
+//! フォルダ送りの対象となる画像一覧の作成と、フォルダ単位の移動。
+//!
+//! サブフォルダも辿る設定なら、フォルダ内の画像の後にサブフォルダを名前の自然順で深さ優先に
+//! 並べる（ep01/ の最後の次が ep02/ の先頭になる）。並び順の設定はフォルダごとに適用する。
+//! シンボリックリンクの循環は実体のパスで検出して辿らず、読めないフォルダはログに残して飛ばす。
+
+use crate::archive;
+use crate::sort::{self, SortKey};
+use log::{debug, error};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// サブフォルダを辿るときに集める画像数の上限（ホームフォルダ等を開いたときに固まらないように）
+const MAX_RECURSIVE_IMAGES: usize = 100_000;
+
+/// 一覧の作り方（設定から作る）
+#[derive(Clone, Copy)]
+pub struct ListOptions {
+    pub recursive: bool,
+    pub sort_order: SortKey,
+    pub descending: bool,
+}
+
+/// root 内の画像の一覧。recursive ならサブフォルダの画像も続けて並べる。
+/// root がアーカイブなら、その中の画像をエントリ名の自然順で並べる
+pub fn collect_images(root: &Path, options: ListOptions) -> Vec<PathBuf> {
+    if archive::is_archive_path(root) {
+        let mut images = archive::list(root).unwrap_or_else(|e| {
+            error!("{}", e);
+            Vec::new()
+        });
+        if options.sort_order != SortKey::Natural || options.descending {
+            resort(&mut images, options);
+        }
+        return images;
+    }
+    let mut images = Vec::new();
+    let mut visited = HashSet::new();
+    walk(root, options, &mut visited, &mut images);
+    images
+}
+
+fn walk(dir: &Path, options: ListOptions, visited: &mut HashSet<PathBuf>, images: &mut Vec<PathBuf>) {
+    // 同じ実体のフォルダは一度だけ（シンボリックリンクの循環対策）
+    match fs::canonicalize(dir) {
+        Ok(real) => {
+            if !visited.insert(real) {
+                debug!("辿り済みのフォルダなので飛ばします: {:?}", dir);
+                return;
+            }
+        }
+        Err(e) => {
+            error!("フォルダの実体を解決できません: {:?} - エラー: {}", dir, e);
+            return;
+        }
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("ディレクトリの読み込みに失敗しました: {:?} - エラー: {}", dir, e);
+            return;
+        }
+    };
+    let mut files = Vec::new();
+    let mut subdirs = Vec::new();
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                error!("ディレクトリエントリの読み込みに失敗: {}", e);
+                continue;
+            }
+        };
+        if is_image_path(&path) {
+            files.push(path);
+        } else if options.recursive && is_visible_dir(&path) {
+            subdirs.push(path);
+        }
+    }
+    sort::sort_paths(&mut files, options.sort_order, options.descending);
+    images.extend(files);
+    if images.len() >= MAX_RECURSIVE_IMAGES {
+        error!(
+            "画像が多すぎるため {} 件で一覧を打ち切りました: {:?}",
+            MAX_RECURSIVE_IMAGES, dir
+        );
+        images.truncate(MAX_RECURSIVE_IMAGES);
+        return;
+    }
+    sort_dirs(&mut subdirs);
+    for subdir in subdirs {
+        walk(&subdir, options, visited, images);
+        if images.len() >= MAX_RECURSIVE_IMAGES {
+            return;
+        }
+    }
+}
+
+/// 並び順の設定を変えたとき、一覧をフォルダの並びは保ったままフォルダごとに並べ替える
+pub fn resort(paths: &mut [PathBuf], options: ListOptions) {
+    for group in paths.chunk_by_mut(|a, b| a.parent() == b.parent()) {
+        sort::sort_paths(group, options.sort_order, options.descending);
+    }
+}
+
+/// 変更監視で見つかった追加・削除を一覧に反映する。追加された画像は同じフォルダの画像の中でだけ
+/// 並べ直し、他のフォルダは並べ替えない。同じフォルダの画像が一覧に無い（新しいフォルダ・
+/// 空だったフォルダ）ときは位置を決められないので false を返す（呼び出し側で作り直す）
+pub fn apply_changes(
+    paths: &mut Vec<PathBuf>,
+    added: &[PathBuf],
+    removed: &[PathBuf],
+    options: ListOptions,
+) -> bool {
+    if !removed.is_empty() {
+        let removed: HashSet<&PathBuf> = removed.iter().collect();
+        paths.retain(|p| !removed.contains(p));
+    }
+    let mut touched = Vec::new();
+    for path in added {
+        let parent = path.parent();
+        let Some(last) = paths.iter().rposition(|p| p.parent() == parent) else {
+            return false;
+        };
+        paths.insert(last + 1, path.clone());
+        if !touched.contains(&parent) {
+            touched.push(parent);
+        }
+    }
+    for parent in touched {
+        let start = paths.iter().position(|p| p.parent() == parent).unwrap_or(0);
+        let len = paths[start..].iter().take_while(|p| p.parent() == parent).count();
+        sort::sort_paths(&mut paths[start..start + len], options.sort_order, options.descending);
+    }
+    true
+}
+
+/// 一覧内で次（前）のフォルダの先頭の画像の位置。一覧の端を越える場合は None
+pub fn adjacent_folder_start(paths: &[PathBuf], current: usize, next: bool) -> Option<usize> {
+    let parent = paths.get(current)?.parent();
+    if next {
+        (current + 1..paths.len()).find(|&i| paths[i].parent() != parent)
+    } else {
+        let start = group_start(paths, current);
+        let prev = start.checked_sub(1)?;
+        Some(group_start(paths, prev))
+    }
+}
+
+/// 一覧内の最後のフォルダの先頭の画像の位置
+pub fn last_folder_start(paths: &[PathBuf]) -> usize {
+    paths.len().checked_sub(1).map_or(0, |last| group_start(paths, last))
+}
+
+fn group_start(paths: &[PathBuf], index: usize) -> usize {
+    let parent = paths[index].parent();
+    (0..index)
+        .rev()
+        .take_while(|&i| paths[i].parent() == parent)
+        .last()
+        .unwrap_or(index)
+}
+
+/// root と同じ階層にある次（前）のフォルダ（またはアーカイブ）のうち、画像を含む最初のもの
+/// （端では反対側へ回る）。該当するものが root 以外に無ければ None
+pub fn sibling_folder(root: &Path, next: bool, options: ListOptions) -> Option<PathBuf> {
+    let parent = root.parent()?;
+    let entries = match fs::read_dir(parent) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("ディレクトリの読み込みに失敗しました: {:?} - エラー: {}", parent, e);
+            return None;
+        }
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| is_visible_dir(path) || archive::is_archive_path(path))
+        .collect();
+    sort_dirs(&mut dirs);
+    let pos = dirs.iter().position(|d| d == root)?;
+    let n = dirs.len();
+    (1..n)
+        .map(|step| {
+            if next {
+                &dirs[(pos + step) % n]
+            } else {
+                &dirs[(pos + n - step) % n]
+            }
+        })
+        .find(|dir| has_images(dir, options, &mut HashSet::new()))
+        .cloned()
+}
+
+/// フォルダ（サブフォルダを辿る設定ならその下も含む）に画像があるか。最初の 1 件で止める
+fn has_images(dir: &Path, options: ListOptions, visited: &mut HashSet<PathBuf>) -> bool {
+    if archive::is_archive_path(dir) {
+        return archive::list(dir).is_ok_and(|images| !images.is_empty());
+    }
+    let Ok(real) = fs::canonicalize(dir) else {
+        return false;
+    };
+    if !visited.insert(real) {
+        return false;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        debug!("フォルダを読めないので飛ばします: {:?}", dir);
+        return false;
+    };
+    let mut subdirs = Vec::new();
+    for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+        if is_image_path(&path) {
+            return true;
+        }
+        if options.recursive && is_visible_dir(&path) {
+            subdirs.push(path);
+        }
+    }
+    subdirs.iter().any(|subdir| has_images(subdir, options, visited))
+}
+
+/// 対応する拡張子のファイルか（フォルダ送りの対象）
+pub fn is_image_path(path: &Path) -> bool {
+    has_image_ext(path) && !path.is_dir()
+}
+
+/// 対応する拡張子か（ディスクは見ない）
+pub fn has_image_ext(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        let ext = ext.to_string_lossy().to_lowercase();
+        super::SUPPORTED_EXTS.contains(&ext.as_str())
+    })
+}
+
+/// 辿る対象のフォルダか（"." で始まる隠しフォルダは除く。リンク先がフォルダのものは含む）
+pub fn is_visible_dir(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+    !hidden && path.is_dir()
+}
+
+fn sort_dirs(dirs: &mut [PathBuf]) {
+    dirs.sort_by(|a, b| {
+        let an = a.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let bn = b.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        super::natural_cmp(&an, &bn).then_with(|| a.cmp(b))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: ListOptions = ListOptions {
+        recursive: true,
+        sort_order: SortKey::Natural,
+        descending: false,
+    };
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"").unwrap();
+    }
+
+    #[test]
+    fn walks_subfolders_in_natural_order_and_jumps_between_folders() {
+        let base = std::env::temp_dir().join(format!("msbt-folders-{}", std::process::id()));
+        let root = base.join("series");
+        touch(&root.join("cover.png"));
+        touch(&root.join("ep2").join("b.png"));
+        touch(&root.join("ep2").join("a.jpg"));
+        touch(&root.join("ep10").join("c.png"));
+        touch(&root.join("ep10").join("notes.txt"));
+        fs::create_dir_all(root.join("empty")).unwrap();
+        touch(&root.join(".hidden").join("x.png"));
+        touch(&base.join("other").join("d.png"));
+        fs::create_dir_all(base.join("nothing")).unwrap();
+        // 親フォルダへのリンクで循環させても無限に辿らない
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&root, root.join("ep10").join("loop")).unwrap();
+
+        let images = collect_images(&root, OPTIONS);
+        let rel: Vec<_> = images.iter().map(|p| p.strip_prefix(&root).unwrap()).collect();
+        assert_eq!(
+            rel,
+            [
+                Path::new("cover.png"),
+                Path::new("ep2/a.jpg"),
+                Path::new("ep2/b.png"),
+                Path::new("ep10/c.png"),
+            ]
+        );
+        let flat = ListOptions { recursive: false, ..OPTIONS };
+        assert_eq!(collect_images(&root, flat).len(), 1);
+
+        assert_eq!(adjacent_folder_start(&images, 0, true), Some(1));
+        assert_eq!(adjacent_folder_start(&images, 2, true), Some(3));
+        assert_eq!(adjacent_folder_start(&images, 3, true), None);
+        assert_eq!(adjacent_folder_start(&images, 3, false), Some(1));
+        assert_eq!(adjacent_folder_start(&images, 2, false), Some(0));
+        assert_eq!(adjacent_folder_start(&images, 0, false), None);
+        assert_eq!(last_folder_start(&images), 3);
+
+        // 画像の無い "nothing" は飛ばし、端では反対側へ回る
+        assert_eq!(sibling_folder(&root, true, OPTIONS), Some(base.join("other")));
+        assert_eq!(sibling_folder(&root, false, OPTIONS), Some(base.join("other")));
+        assert_eq!(sibling_folder(&base.join("other"), true, OPTIONS), Some(root.clone()));
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn changes_are_applied_within_their_folder() {
+        let root = Path::new("/pics");
+        let mut paths: Vec<PathBuf> = ["cover.png", "ep2/a.png", "ep2/c.png", "ep10/x.png"]
+            .iter()
+            .map(|p| root.join(p))
+            .collect();
+        assert!(apply_changes(
+            &mut paths,
+            &[root.join("ep2/b.png"), root.join("ep10/w.png")],
+            &[root.join("ep2/a.png")],
+            OPTIONS,
+        ));
+        let rel: Vec<_> = paths.iter().map(|p| p.strip_prefix(root).unwrap()).collect();
+        assert_eq!(
+            rel,
+            [
+                Path::new("cover.png"),
+                Path::new("ep2/b.png"),
+                Path::new("ep2/c.png"),
+                Path::new("ep10/w.png"),
+                Path::new("ep10/x.png"),
+            ]
+        );
+        // 一覧に無いフォルダへの追加は作り直しが要る
+        assert!(!apply_changes(&mut paths, &[root.join("ep3/a.png")], &[], OPTIONS));
+    }
+}
@@ -0,0 +1,223 @@
+//! HDR（OpenEXR / Radiance HDR / 浮動小数 TIFF 等）の露出・トーンマッピング。
+//!
+//! 浮動小数のまま画素を保持し、露出（EV）・ガンマ・トーンマッピング方式から 8bit の表示用画像を
+//! 作り直す。設定を変えてもファイルは読み直さない（保持している画素からテクスチャを更新するだけ）。
+
+use eframe::egui;
+use image::Rgba32FImage;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// トーンマッピング方式
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ToneMap {
+    /// 0..1 で切り捨て（露出だけを掛ける）
+    Clamp,
+    /// x / (1 + x)
+    Reinhard,
+    /// ACES フィルミックカーブの近似（Narkowicz）
+    #[default]
+    Aces,
+}
+
+impl ToneMap {
+    pub const ALL: [ToneMap; 3] = [ToneMap::Clamp, ToneMap::Reinhard, ToneMap::Aces];
+
+    /// 設定ファイル・タイトルバーでの名前
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMap::Clamp => "clamp",
+            ToneMap::Reinhard => "reinhard",
+            ToneMap::Aces => "aces",
+        }
+    }
+
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            ToneMap::Clamp => x,
+            ToneMap::Reinhard => x / (1.0 + x),
+            ToneMap::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        }
+    }
+}
+
+/// 表示用画像を作るためのパラメータ（変化したときだけ作り直す）
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ToneParams {
+    /// 露出補正（EV。+1 で 2 倍の明るさ）
+    pub exposure: f32,
+    pub gamma: f32,
+    pub tone_map: ToneMap,
+}
+
+impl ToneParams {
+    /// 1 画素の表示用の値（不透明度を掛ける前の 8bit RGBA。render と同じ値になる）
+    pub fn encode(self, pixel: [f32; 4]) -> [u8; 4] {
+        Encoder::new(self).encode(pixel)
+    }
+}
+
+/// 露出・トーンマッピング・ガンマの適用（画素ごとに使う係数を先に求めておく）
+struct Encoder {
+    gain: f32,
+    inv_gamma: f32,
+    tone_map: ToneMap,
+}
+
+impl Encoder {
+    fn new(params: ToneParams) -> Self {
+        Self {
+            gain: 2f32.powf(params.exposure),
+            inv_gamma: 1.0 / params.gamma.max(0.01),
+            tone_map: params.tone_map,
+        }
+    }
+
+    fn encode(&self, [r, g, b, a]: [f32; 4]) -> [u8; 4] {
+        let channel = |v: f32| {
+            let v = self.tone_map.apply((v * self.gain).max(0.0)).clamp(0.0, 1.0);
+            (v.powf(self.inv_gamma) * 255.0 + 0.5) as u8
+        };
+        [
+            channel(r),
+            channel(g),
+            channel(b),
+            (a.clamp(0.0, 1.0) * 255.0 + 0.5) as u8,
+        ]
+    }
+}
+
+/// 浮動小数の画素と、その画像の自動露出値（画素は共有されるので複製は軽い）
+#[derive(Clone)]
+pub struct HdrImage {
+    pixels: Arc<Rgba32FImage>,
+    /// 輝度ヒストグラムから求めた露出（auto_exposure が有効なときの初期値）
+    pub auto_exposure: f32,
+}
+
+impl HdrImage {
+    pub fn new(pixels: Rgba32FImage) -> Self {
+        let auto_exposure = auto_exposure(&pixels);
+        Self {
+            pixels: Arc::new(pixels),
+            auto_exposure,
+        }
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        let (w, h) = self.pixels.dimensions();
+        [w, h]
+    }
+
+    /// トーンマッピング前の画素（ヒストグラムの計算用）
+    pub fn pixels(&self) -> &Rgba32FImage {
+        &self.pixels
+    }
+
+    /// (x, y) の画素（トーンマッピング前の値）
+    pub fn pixel(&self, x: u32, y: u32) -> [f32; 4] {
+        self.pixels.get_pixel(x, y).0
+    }
+
+    /// 露出・トーンマッピング・ガンマを適用した 8bit 画像
+    pub fn render(&self, params: ToneParams) -> egui::ColorImage {
+        let encoder = Encoder::new(params);
+        let pixels = self
+            .pixels
+            .pixels()
+            .map(|p| {
+                let [r, g, b, a] = encoder.encode(p.0);
+                egui::Color32::from_rgba_unmultiplied(r, g, b, a)
+            })
+            .collect();
+        let [w, h] = self.size();
+        egui::ColorImage {
+            size: [w as usize, h as usize],
+            pixels,
+        }
+    }
+}
+
+/// 輝度（Rec.709）の対数ヒストグラムの中央値が中間グレー（0.18）になる露出を求める。
+/// 真っ黒な画素は除外し、全画素が黒なら 0
+fn auto_exposure(pixels: &Rgba32FImage) -> f32 {
+    const MIN_LOG: f32 = -20.0;
+    const MAX_LOG: f32 = 20.0;
+    const BINS: usize = 400;
+    let mut hist = [0u32; BINS];
+    let mut total = 0u32;
+    for p in pixels.pixels() {
+        let [r, g, b, _] = p.0;
+        let lum = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        if lum <= 0.0 || !lum.is_finite() {
+            continue;
+        }
+        let t = (lum.log2() - MIN_LOG) / (MAX_LOG - MIN_LOG);
+        let bin = ((t * BINS as f32) as usize).min(BINS - 1);
+        hist[bin] += 1;
+        total += 1;
+    }
+    if total == 0 {
+        return 0.0;
+    }
+    let mut seen = 0;
+    let median_bin = hist
+        .iter()
+        .position(|&n| {
+            seen += n;
+            seen * 2 >= total
+        })
+        .unwrap_or(BINS / 2);
+    let median_log = MIN_LOG + (median_bin as f32 + 0.5) / BINS as f32 * (MAX_LOG - MIN_LOG);
+    (0.18f32.log2() - median_log).clamp(-10.0, 10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat(value: f32) -> Rgba32FImage {
+        Rgba32FImage::from_pixel(4, 4, image::Rgba([value, value, value, 1.0]))
+    }
+
+    #[test]
+    fn auto_exposure_brings_median_to_middle_gray() {
+        // 0.72 = 0.18 の 4 倍 → -2EV
+        let ev = auto_exposure(&flat(0.72));
+        assert!((ev + 2.0).abs() < 0.1, "{ev}");
+        let ev = auto_exposure(&flat(0.045));
+        assert!((ev - 2.0).abs() < 0.1, "{ev}");
+        assert_eq!(auto_exposure(&flat(0.0)), 0.0);
+    }
+
+    #[test]
+    fn operators_are_monotonic_and_bounded() {
+        for op in ToneMap::ALL {
+            let mut prev = -1.0;
+            for i in 0..100 {
+                let v = op.apply(i as f32 * 0.2).clamp(0.0, 1.0);
+                assert!(v >= prev, "{op:?} が単調でない");
+                prev = v;
+            }
+        }
+        assert!(ToneMap::Reinhard.apply(1000.0) < 1.0, "Reinhard は白飛びしない");
+        assert_eq!(ToneMap::Clamp.apply(3.0).clamp(0.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn exposure_and_gamma_are_applied() {
+        let img = HdrImage::new(flat(0.25));
+        let params = ToneParams {
+            exposure: 1.0,
+            gamma: 1.0,
+            tone_map: ToneMap::Clamp,
+        };
+        // 0.25 × 2 = 0.5 → 128
+        assert_eq!(img.render(params).pixels[0].r(), 128);
+        let params = ToneParams { gamma: 2.0, ..params };
+        // 0.5^(1/2) ≒ 0.707 → 180
+        assert_eq!(img.render(params).pixels[0].r(), 180);
+        assert_eq!(params.encode(img.pixel(0, 0)), [180, 180, 180, 255]);
+    }
+}
@@ -0,0 +1,214 @@
+//! 表示中の画像のヒストグラムとチャンネルごとの統計（ヒストグラムパネル用）。
+//!
+//! 8bit の画像は 0〜255 をそのまま 256 段に、浮動小数の画像（EXR / HDR）は 0〜最大値（1 未満なら 1）を
+//! 256 段に分けて数える。輝度は RGB から Rec.709 の係数で求める。画素数が多いので UI スレッドの外で計算する。
+
+use image::Rgba32FImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+pub const BINS: usize = 256;
+/// 色数を数える画素数の上限（これより大きい画像は数えない）
+const UNIQUE_COLOR_LIMIT: usize = 16 * 1024 * 1024;
+
+/// チャンネルの名前（Stats::channels の並び）
+pub const CHANNEL_NAMES: [&str; 5] = ["R", "G", "B", "Luma", "Alpha"];
+pub const LUMA: usize = 3;
+pub const ALPHA: usize = 4;
+
+/// パネルを置く位置
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Dock {
+    Left,
+    #[default]
+    Right,
+    Bottom,
+}
+
+impl Dock {
+    pub const ALL: [Dock; 3] = [Dock::Left, Dock::Right, Dock::Bottom];
+
+    /// 設定ファイルでの名前
+    pub fn name(self) -> &'static str {
+        match self {
+            Dock::Left => "left",
+            Dock::Right => "right",
+            Dock::Bottom => "bottom",
+        }
+    }
+
+    /// メニューでの表示名
+    pub fn label(self) -> &'static str {
+        match self {
+            Dock::Left => "Left",
+            Dock::Right => "Right",
+            Dock::Bottom => "Bottom",
+        }
+    }
+}
+
+/// 1 チャンネルの統計
+pub struct Channel {
+    pub bins: Vec<u32>,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    /// 黒つぶれ（0 以下）の画素の割合（%）
+    pub clipped_low: f32,
+    /// 白飛び（8bit は 255、浮動小数は 1 以上）の画素の割合（%）
+    pub clipped_high: f32,
+}
+
+pub struct Stats {
+    /// R, G, B, 輝度, 不透明度
+    pub channels: [Channel; 5],
+    /// 浮動小数の画像か（値は 0〜1 が表示の黒〜白に当たる。8bit は 0〜255）
+    pub float: bool,
+    /// RGB・輝度のヒストグラムの右端の値（不透明度は常に 1 または 255）
+    pub range: f32,
+    pub pixels: usize,
+    /// 色数（画素数が多い画像と浮動小数の画像は None）
+    pub unique_colors: Option<usize>,
+}
+
+/// 8bit の画像（不透明度を掛ける前の RGBA）の統計
+pub fn from_rgba8(pixels: impl ExactSizeIterator<Item = [u8; 4]>) -> Stats {
+    let count = pixels.len();
+    let mut colors = (count <= UNIQUE_COLOR_LIMIT).then(HashSet::new);
+    let mut channels: [Accumulator; 5] = std::array::from_fn(|_| Accumulator::new());
+    for [r, g, b, a] in pixels {
+        if let Some(colors) = &mut colors {
+            colors.insert(u32::from_le_bytes([r, g, b, a]));
+        }
+        let luma = luminance(r as f32, g as f32, b as f32);
+        for (channel, value) in channels
+            .iter_mut()
+            .zip([r as f32, g as f32, b as f32, luma, a as f32])
+        {
+            channel.add(value, value.round() as usize, value <= 0.0, value >= 255.0);
+        }
+    }
+    Stats {
+        channels: channels.map(|c| c.finish(count)),
+        float: false,
+        range: 255.0,
+        pixels: count,
+        unique_colors: colors.map(|c| c.len()),
+    }
+}
+
+/// 浮動小数の画像（トーンマッピング前）の統計。NaN・無限大は 0 として数える
+pub fn from_rgba32f(image: &Rgba32FImage) -> Stats {
+    let finite = |v: f32| if v.is_finite() { v } else { 0.0 };
+    let range = image
+        .pixels()
+        .flat_map(|p| [p[0], p[1], p[2]])
+        .map(finite)
+        .fold(1.0f32, f32::max);
+    let bin =
+        |v: f32, range: f32| ((v / range).clamp(0.0, 1.0) * (BINS - 1) as f32).round() as usize;
+    let mut channels: [Accumulator; 5] = std::array::from_fn(|_| Accumulator::new());
+    for p in image.pixels() {
+        let [r, g, b, a] = p.0.map(finite);
+        let luma = luminance(r, g, b);
+        for (i, (channel, value)) in channels.iter_mut().zip([r, g, b, luma, a]).enumerate() {
+            let channel_range = if i == ALPHA { 1.0 } else { range };
+            channel.add(value, bin(value, channel_range), value <= 0.0, value >= 1.0);
+        }
+    }
+    let count = image.pixels().len();
+    Stats {
+        channels: channels.map(|c| c.finish(count)),
+        float: true,
+        range,
+        pixels: count,
+        unique_colors: None,
+    }
+}
+
+fn luminance(r: f32, g: f32, b: f32) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+struct Accumulator {
+    bins: Vec<u32>,
+    min: f32,
+    max: f32,
+    sum: f64,
+    low: usize,
+    high: usize,
+}
+
+impl Accumulator {
+    fn new() -> Self {
+        Self {
+            bins: vec![0; BINS],
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            sum: 0.0,
+            low: 0,
+            high: 0,
+        }
+    }
+
+    fn add(&mut self, value: f32, bin: usize, low: bool, high: bool) {
+        self.bins[bin.min(BINS - 1)] += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value as f64;
+        self.low += low as usize;
+        self.high += high as usize;
+    }
+
+    fn finish(self, count: usize) -> Channel {
+        let percent = |n: usize| (n as f64 * 100.0 / count.max(1) as f64) as f32;
+        Channel {
+            bins: self.bins,
+            min: if count > 0 { self.min } else { 0.0 },
+            max: if count > 0 { self.max } else { 0.0 },
+            mean: (self.sum / count.max(1) as f64) as f32,
+            clipped_low: percent(self.low),
+            clipped_high: percent(self.high),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn counts_channels_clipping_and_colors() {
+        let pixels = [
+            [0, 0, 0, 255],
+            [255, 255, 255, 255],
+            [255, 0, 0, 128],
+            [0, 0, 0, 255],
+        ];
+        let stats = from_rgba8(pixels.into_iter());
+        let red = &stats.channels[0];
+        assert_eq!((red.min, red.max, red.mean), (0.0, 255.0, 127.5));
+        assert_eq!((red.bins[0], red.bins[255]), (2, 2));
+        assert_eq!((red.clipped_low, red.clipped_high), (50.0, 50.0));
+        assert_eq!(stats.channels[ALPHA].bins[128], 1);
+        assert_eq!(stats.channels[LUMA].bins[54], 1, "純色の赤の輝度は 0.2126");
+        assert_eq!(stats.unique_colors, Some(3));
+    }
+
+    #[test]
+    fn float_histogram_covers_values_above_one() {
+        let mut image = Rgba32FImage::from_pixel(2, 1, Rgba([0.5, 0.5, 0.5, 1.0]));
+        image.put_pixel(1, 0, Rgba([4.0, f32::NAN, 0.0, 1.0]));
+        let stats = from_rgba32f(&image);
+        assert_eq!(stats.range, 4.0);
+        let red = &stats.channels[0];
+        assert_eq!((red.min, red.max, red.mean), (0.5, 4.0, 2.25));
+        assert_eq!((red.bins[32], red.bins[BINS - 1]), (1, 1));
+        assert_eq!(red.clipped_high, 50.0);
+        assert_eq!(stats.channels[1].clipped_low, 50.0, "NaN は 0 として数える");
+        assert_eq!(stats.channels[ALPHA].bins[BINS - 1], 2);
+        assert_eq!(stats.unique_colors, None);
+    }
+}
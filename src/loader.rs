@@ -0,0 +1,779 @@
+//! ラスタ画像のバックグラウンドデコードと、前後の画像の先読み。
+//!
+//! デコード（ファイル読み込み・色変換・向き補正・ピラミッド作成・ColorImage 化まで）は
+//! ワーカースレッドで行い、UI スレッドは結果をテクスチャへアップロードするだけにする。
+//! 新しい画像を要求すると未着手の要求はすべて取り消されるので、→ キーを押し続けても
+//! 途中の画像のデコード待ちが積み上がらない（着手済みの結果は先読みキャッシュに入れる）。
+//!
+//! 比較表示のペインの画像も同じワーカー・キャッシュで読む（表示要求とは別に待ち、
+//! 表示要求による取り消しの対象にしない）。
+//!
+//! 先読みした結果はパス・更新日時をキーにした LRU キャッシュ（cache::LruCache）に置き、
+//! 表示要求がキャッシュに当たればデコードを待たずに表示する。解析済みの SVG ツリーも同じ
+//! キャッシュに入れる（SVG の解析は UI スレッド、ラスタライズは spawn_svg_render_worker で行う）。
+
+use crate::cache::{CacheKey, LruCache};
+use crate::{animation, color, exr_layers, hdr, metadata, pages, tiles};
+use eframe::egui;
+use log::{debug, info};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// デコードを並行して行うワーカー数（表示中の画像と先読みを同時に進められるように 2）
+const DECODE_WORKERS: usize = 2;
+
+/// 要求のキー（パス, ページ）
+pub type Key = (PathBuf, usize);
+
+/// デコード結果に影響する設定。変わったらキャッシュを捨てる
+/// （HDR のガンマ・トーンマッピングは表示時に作り直せるので比較しない）
+#[derive(Clone)]
+pub struct DecodeSettings {
+    pub auto_orient: bool,
+    pub color_management: bool,
+    pub output_profile: Arc<moxcms::ColorProfile>,
+    pub max_texture_side: u32,
+    pub texture_options: egui::TextureOptions,
+    pub hdr_gamma: f32,
+    pub tone_mapping: hdr::ToneMap,
+    pub auto_exposure: bool,
+}
+
+impl PartialEq for DecodeSettings {
+    fn eq(&self, other: &Self) -> bool {
+        self.auto_orient == other.auto_orient
+            && self.color_management == other.color_management
+            && Arc::ptr_eq(&self.output_profile, &other.output_profile)
+            && self.max_texture_side == other.max_texture_side
+            && self.texture_options == other.texture_options
+            && self.auto_exposure == other.auto_exposure
+    }
+}
+
+/// テクスチャ化の直前まで準備した画像（複製は Arc の共有だけで済む）
+#[derive(Clone)]
+pub enum Prepared {
+    Raster(Arc<egui::ColorImage>),
+    Animated(Vec<(Arc<egui::ColorImage>, Duration)>),
+    Hdr {
+        image: hdr::HdrImage,
+        exposure: f32,
+        params: hdr::ToneParams,
+        first: Arc<egui::ColorImage>,
+    },
+    Tiled(tiles::TiledImage),
+    Exr {
+        file: Arc<exr_layers::ExrFile>,
+        selection: exr_layers::Selection,
+        view: Box<Prepared>,
+    },
+    /// 解析済みの SVG（source_len は展開後のソースのバイト数）
+    Svg {
+        tree: Arc<usvg::Tree>,
+        source_len: usize,
+    },
+}
+
+impl Prepared {
+    /// 画像の大きさ（px）。SVG は None
+    pub fn size(&self) -> Option<[u32; 2]> {
+        match self {
+            Prepared::Raster(img) => Some([img.size[0] as u32, img.size[1] as u32]),
+            Prepared::Animated(frames) => frames
+                .first()
+                .map(|(f, _)| [f.size[0] as u32, f.size[1] as u32]),
+            Prepared::Hdr { image, .. } => Some(image.size()),
+            Prepared::Tiled(t) => Some(t.size()),
+            Prepared::Exr { view, .. } => view.size(),
+            Prepared::Svg { .. } => None,
+        }
+    }
+
+    /// 1 枚のテクスチャで表示できる静止画（アニメーションは先頭フレーム）。
+    /// タイル分割した画像と SVG は None
+    pub fn still(&self) -> Option<Arc<egui::ColorImage>> {
+        match self {
+            Prepared::Raster(img) => Some(img.clone()),
+            Prepared::Animated(frames) => frames.first().map(|(f, _)| f.clone()),
+            Prepared::Hdr { first, .. } => Some(first.clone()),
+            Prepared::Exr { view, .. } => view.still(),
+            Prepared::Tiled(_) | Prepared::Svg { .. } => None,
+        }
+    }
+
+    /// 画素データの概算バイト数（キャッシュの上限管理用）
+    fn bytes(&self) -> usize {
+        match self {
+            Prepared::Raster(img) => img.pixels.len() * 4,
+            Prepared::Animated(frames) => frames.iter().map(|(f, _)| f.pixels.len() * 4).sum(),
+            Prepared::Hdr { image, first, .. } => {
+                let [w, h] = image.size();
+                w as usize * h as usize * 16 + first.pixels.len() * 4
+            }
+            Prepared::Tiled(t) => {
+                let [w, h] = t.size();
+                w as usize * h as usize * 4 * 4 / 3
+            }
+            Prepared::Exr { file, view, .. } => file.bytes() + view.bytes(),
+            // ツリーの実サイズは測れないので、ソースの数倍と見積もる
+            Prepared::Svg { source_len, .. } => source_len * 4,
+        }
+    }
+}
+
+/// 1 ファイル（1 ページ）のデコード結果
+#[derive(Clone)]
+pub struct Decoded {
+    pub path: PathBuf,
+    pub page: usize,
+    /// デコード開始時のファイルの更新日時（キャッシュのキー）
+    pub mtime: Option<SystemTime>,
+    pub page_info: pages::PageInfo,
+    pub icc: color::IccState,
+    pub prepared: Prepared,
+}
+
+struct Job {
+    key: Key,
+    settings: DecodeSettings,
+    /// 設定の世代（古い設定でデコードした結果をキャッシュに入れないため）
+    settings_gen: u64,
+}
+
+struct JobResult {
+    key: Key,
+    settings_gen: u64,
+    result: Result<Decoded, String>,
+}
+
+struct Queue {
+    jobs: VecDeque<Job>,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+}
+
+/// デコードワーカーと先読みキャッシュ（UI スレッド側）
+pub struct Loader {
+    shared: Arc<Shared>,
+    result_rx: mpsc::Receiver<JobResult>,
+    cache: LruCache<Decoded>,
+    /// ワーカーが処理中・待ち行列にある要求
+    inflight: HashSet<Key>,
+    /// 表示待ちの要求（届いたら poll が返す）
+    wanted: Option<Key>,
+    /// 比較表示のペインが待っている要求（届いたら poll_panes が返す）
+    pane_wanted: HashSet<Key>,
+    /// 届いた表示待ちの結果（poll で返すまで）
+    wanted_result: Option<Result<Decoded, (PathBuf, String)>>,
+    /// 届いたペインの結果（poll_panes で返すまで）
+    pane_results: Vec<(Key, Result<Decoded, String>)>,
+    settings: Option<DecodeSettings>,
+    settings_gen: u64,
+}
+
+impl Loader {
+    /// cache_budget はデコード済み画像・SVG ツリーを保持するメモリ上限（バイト）
+    pub fn new(ctx: &egui::Context, cache_budget: usize) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                jobs: VecDeque::new(),
+                closed: false,
+            }),
+            ready: Condvar::new(),
+        });
+        let (result_tx, result_rx) = mpsc::channel();
+        for _ in 0..DECODE_WORKERS {
+            let shared = shared.clone();
+            let result_tx = result_tx.clone();
+            let ctx = ctx.clone();
+            std::thread::spawn(move || worker_loop(shared, result_tx, ctx));
+        }
+        Self {
+            shared,
+            result_rx,
+            cache: LruCache::new(cache_budget),
+            inflight: HashSet::new(),
+            wanted: None,
+            pane_wanted: HashSet::new(),
+            wanted_result: None,
+            pane_results: Vec::new(),
+            settings: None,
+            settings_gen: 0,
+        }
+    }
+
+    /// 表示する画像を要求する。未着手の要求（前の表示要求・先読み）はすべて取り消す。
+    /// キャッシュにあればその場で返し、なければワーカーに依頼して None を返す（結果は poll で受け取る）
+    pub fn request(&mut self, path: &Path, page: usize, settings: &DecodeSettings) -> Option<Decoded> {
+        self.sync_settings(settings);
+        self.cancel_queued();
+        if let Some(hit) = self.lookup(&CacheKey::new(path, page)) {
+            self.wanted = None;
+            return Some(hit);
+        }
+        let key = (path.to_path_buf(), page);
+        self.wanted = Some(key.clone());
+        // 先読みで処理中なら、その結果をそのまま表示に使う
+        if !self.inflight.contains(&key) {
+            self.push_job(key, settings, true);
+        }
+        None
+    }
+
+    /// 前後の画像などを先読みする（表示要求より後回し）
+    pub fn prefetch(&mut self, keys: &[Key], settings: &DecodeSettings) {
+        self.sync_settings(settings);
+        for key in keys {
+            let cached = self.cache.contains(&CacheKey::new(&key.0, key.1));
+            if !cached && !self.inflight.contains(key) {
+                self.push_job(key.clone(), settings, false);
+            }
+        }
+    }
+
+    /// 比較表示のペインに出す画像を要求する。キャッシュにあればその場で返し、なければ
+    /// ワーカーに依頼して None を返す（結果は poll_panes で受け取る）
+    pub fn request_pane(&mut self, path: &Path, page: usize, settings: &DecodeSettings) -> Option<Decoded> {
+        self.sync_settings(settings);
+        if let Some(hit) = self.lookup(&CacheKey::new(path, page)) {
+            return Some(hit);
+        }
+        let key = (path.to_path_buf(), page);
+        self.pane_wanted.insert(key.clone());
+        if !self.inflight.contains(&key) {
+            self.push_job(key, settings, false);
+        }
+        None
+    }
+
+    /// ペインの要求をすべて取り消す（比較表示を閉じたとき）
+    pub fn cancel_panes(&mut self) {
+        self.pane_wanted.clear();
+        self.cancel_queued();
+    }
+
+    /// 届いたペインの結果を取り出す
+    pub fn poll_panes(&mut self) -> Vec<(Key, Result<Decoded, String>)> {
+        self.receive();
+        std::mem::take(&mut self.pane_results)
+    }
+
+    /// キャッシュから取り出す（ヒット・ミスはデバッグログに出す）
+    pub fn lookup(&mut self, key: &CacheKey) -> Option<Decoded> {
+        let hit = self.cache.get(key);
+        debug!(
+            "デコードキャッシュ{}: {} — {}",
+            if hit.is_some() { "ヒット" } else { "ミス" },
+            key.path.display(),
+            self.cache.summary()
+        );
+        hit
+    }
+
+    /// キャッシュにあれば返す（統計・順序を変えず、ファイルの更新日時も調べない）
+    pub fn peek(&self, path: &Path, page: usize) -> Option<&Decoded> {
+        self.cache.peek(path, page)
+    }
+
+    /// UI スレッドで用意したもの（解析済みの SVG）をキャッシュに入れる
+    pub fn store(&mut self, decoded: Decoded) {
+        self.insert_cache(decoded);
+    }
+
+    pub fn set_cache_budget(&mut self, bytes: usize) {
+        self.cache.set_budget(bytes);
+    }
+
+    /// 表示待ちの要求を取り消す（SVG を開いたときなど）
+    pub fn cancel(&mut self) {
+        self.cancel_queued();
+        self.wanted = None;
+    }
+
+    /// 表示待ちの画像をデコード中かどうか
+    pub fn is_loading(&self) -> bool {
+        self.wanted.is_some()
+    }
+
+    /// 表示待ちの画像とページ
+    pub fn wanted(&self) -> Option<&Key> {
+        self.wanted.as_ref()
+    }
+
+    /// ワーカーの結果を受け取ってキャッシュに入れる。表示待ちの結果が届いていれば返す
+    /// （失敗は要求したパスと一緒に返す）
+    pub fn poll(&mut self) -> Option<Result<Decoded, (PathBuf, String)>> {
+        self.receive();
+        self.wanted_result.take()
+    }
+
+    /// ワーカーの結果をすべて受け取り、表示待ち・ペイン待ちの結果を取っておく
+    fn receive(&mut self) {
+        while let Ok(res) = self.result_rx.try_recv() {
+            if res.settings_gen != self.settings_gen {
+                continue; // 設定変更前の結果（要求し直し済み）
+            }
+            self.inflight.remove(&res.key);
+            let is_wanted = self.wanted.as_ref() == Some(&res.key);
+            if is_wanted {
+                self.wanted = None;
+            }
+            let for_pane = self.pane_wanted.remove(&res.key);
+            if let Ok(decoded) = &res.result {
+                self.insert_cache(decoded.clone());
+            }
+            if for_pane {
+                self.pane_results.push((res.key.clone(), res.result.clone()));
+            }
+            match res.result {
+                Ok(decoded) if is_wanted => self.wanted_result = Some(Ok(decoded)),
+                Err(e) if is_wanted => self.wanted_result = Some(Err((res.key.0, e))),
+                Err(e) if !for_pane => debug!("先読みに失敗: {} ({})", res.key.0.display(), e),
+                _ => {}
+            }
+        }
+    }
+
+    fn sync_settings(&mut self, settings: &DecodeSettings) {
+        if self.settings.as_ref() != Some(settings) {
+            if self.settings.is_some() {
+                info!("デコード設定が変わったため先読みキャッシュを破棄します");
+            }
+            self.settings = Some(settings.clone());
+            self.settings_gen += 1;
+            self.cache.clear();
+            // 処理中の要求は古い設定のものなので、結果を待たずに要求し直せるようにする
+            self.inflight.clear();
+            // 待ち行列の要求も古い設定のもの。ペインの要求だけは新しい設定で出し直す
+            self.shared.queue.lock().unwrap().jobs.clear();
+            for key in self.pane_wanted.clone() {
+                self.push_job(key, settings, false);
+            }
+        }
+    }
+
+    /// 未着手の要求を取り消す（ペインが待っているものは残す）
+    fn cancel_queued(&mut self) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let pane_wanted = &self.pane_wanted;
+        let inflight = &mut self.inflight;
+        queue.jobs.retain(|job| {
+            let keep = pane_wanted.contains(&job.key);
+            if !keep {
+                inflight.remove(&job.key);
+            }
+            keep
+        });
+    }
+
+    fn push_job(&mut self, key: Key, settings: &DecodeSettings, urgent: bool) {
+        self.inflight.insert(key.clone());
+        let job = Job {
+            key,
+            settings: settings.clone(),
+            settings_gen: self.settings_gen,
+        };
+        let mut queue = self.shared.queue.lock().unwrap();
+        if urgent {
+            queue.jobs.push_front(job);
+        } else {
+            queue.jobs.push_back(job);
+        }
+        drop(queue);
+        self.shared.ready.notify_one();
+    }
+
+    fn insert_cache(&mut self, decoded: Decoded) {
+        let key = CacheKey {
+            path: decoded.path.clone(),
+            page: decoded.page,
+            mtime: decoded.mtime,
+        };
+        // 上限を超える巨大画像は保持されない（表示中のものだけがメモリに残る）
+        let bytes = decoded.prepared.bytes();
+        self.cache.insert(key, decoded, bytes);
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.closed = true;
+        queue.jobs.clear();
+        drop(queue);
+        self.shared.ready.notify_all();
+    }
+}
+
+fn worker_loop(shared: Arc<Shared>, result_tx: mpsc::Sender<JobResult>, ctx: egui::Context) {
+    loop {
+        let job = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if queue.closed {
+                    return;
+                }
+                if let Some(job) = queue.jobs.pop_front() {
+                    break job;
+                }
+                queue = shared.ready.wait(queue).unwrap();
+            }
+        };
+        let started = std::time::Instant::now();
+        let result = decode(&job.key.0, job.key.1, &job.settings);
+        debug!(
+            "デコード完了: {} ({}ms)",
+            job.key.0.display(),
+            started.elapsed().as_millis()
+        );
+        let res = JobResult {
+            key: job.key,
+            settings_gen: job.settings_gen,
+            result,
+        };
+        if result_tx.send(res).is_err() {
+            return;
+        }
+        ctx.request_repaint();
+    }
+}
+
+/// 1 ファイル（1 ページ）をデコードし、テクスチャ化の直前まで準備する
+pub fn decode(path: &Path, page: usize, settings: &DecodeSettings) -> Result<Decoded, String> {
+    // デコード中に書き換えられても、古い内容が新しい日時でキャッシュされないよう先に調べる
+    let mtime = CacheKey::new(path, page).mtime;
+    let page_info = pages::probe(path);
+    let mut icc = color::IccState::None;
+    let prepared = decode_prepared(path, page, settings, &mut icc)?;
+    Ok(Decoded {
+        path: path.to_path_buf(),
+        page,
+        mtime,
+        page_info,
+        icc,
+        prepared,
+    })
+}
+
+fn decode_prepared(
+    path: &Path,
+    page: usize,
+    settings: &DecodeSettings,
+    icc_state: &mut color::IccState,
+) -> Result<Prepared, String> {
+    // ICO/CUR は解像度ごとのエントリをページとして扱うため、先頭ページも pages で読む
+    let paged = page > 0 || pages::PagedFormat::from_path(path) == pages::PagedFormat::Icon;
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    // EXR は exr クレートで全レイヤー・全チャンネルを読み、既定のレイヤーを表示する
+    // （image は RGBA 1 枚に畳んでしまう）。読めなければ従来どおり image で開く。
+    if ext == "exr" && !paged {
+        match exr_layers::read(path) {
+            Ok(file) => {
+                info!("EXR のレイヤー: {} 個", file.layers.len());
+                let selection = file.default_selection();
+                let view = prepare_exr_view(&file, selection, settings)?;
+                return Ok(Prepared::Exr {
+                    file: Arc::new(file),
+                    selection,
+                    view: Box::new(view),
+                });
+            }
+            Err(e) => info!("EXR のレイヤーを読めないため image で開きます: {}", e),
+        }
+    }
+    // アニメーション（2 フレーム以上の GIF/APNG/WebP）なら全フレームを読む。
+    // 静止画や解析に失敗したものは従来どおり 1 枚の画像として開く。
+    // 1 フレームだけ（または大きすぎる）GIF は、ここで取り出した先頭フレームをそのまま静止画として使う
+    let mut still = None;
+    if !paged {
+        match animation::decode_animation(path) {
+            Ok(Some(animation::Animation::Frames(frames))) => return Ok(prepare_animation(frames, settings)),
+            Ok(Some(animation::Animation::Still(first))) => still = Some(image::DynamicImage::ImageRgba8(first)),
+            Ok(None) => {}
+            Err(e) => info!("アニメーションとして読めないため静止画として開きます: {} ({})", path.display(), e),
+        }
+    }
+    // まず image クレートで読む（png/jpg/gif/webp/bmp/tiff/ico/tga/dds/exr/hdr/qoi/pnm 等を網羅）。
+    // image が非対応の形式（HEIC/HEIF/AVIF/JPEG XR/カメラRAW 等）は、Windows の WIC
+    // （OS が持つ画像コーデック＋ストアの拡張機能）にフォールバックして可能な限り開く。
+    let mut image = if let Some(image) = still {
+        image
+    } else if paged {
+        pages::decode_page(path, page)?
+    } else if ext == "heic" || ext == "heif" {
+        super::decode_via_wic(path)? // image は HEIC 非対応なので最初から WIC
+    } else {
+        match color::decode_with_icc(path) {
+            Ok((img, Some(icc))) => apply_icc(img, &icc, path, settings, icc_state),
+            Ok((img, None)) => img,
+            Err(e) => {
+                info!("image で読めず WIC にフォールバック: {} ({})", path.display(), e);
+                super::decode_via_wic(path).map_err(|werr| format!("{e} / WIC: {werr}"))?
+            }
+        }
+    };
+    // EXIF の向き（スマホ写真の縦横・左右反転）を画素に適用する。
+    // ここで補正しておけば rotation は常に補正後の画像からの相対回転になる。
+    // 2 ページ目以降・ICO はページ単位の EXIF を持たないので対象外。
+    if settings.auto_orient && !paged {
+        if let Some(orientation) = metadata::read_orientation(path) {
+            info!("EXIF の向きを適用: {:?}", orientation);
+            image.apply_orientation(orientation);
+        }
+    }
+    // 浮動小数の画像は 8bit に丸めず、露出・トーンマッピングを掛けて表示する
+    if matches!(
+        image.color(),
+        image::ColorType::Rgb32F | image::ColorType::Rgba32F
+    ) {
+        return Ok(prepare_hdr(image, settings));
+    }
+    // GPU の最大テクスチャ辺を超える画像は 1 枚のテクスチャにできない（load_texture が
+    // パニックする）ため、原寸のままタイルに分けて可視部分だけを表示する。
+    let max = settings.max_texture_side;
+    if image.width() > max || image.height() > max {
+        info!(
+            "画像がテクスチャ上限({0})を超過: {1}x{2} → タイル表示",
+            max,
+            image.width(),
+            image.height()
+        );
+        let tiled = tiles::TiledImage::new(
+            image.into_rgba8(),
+            path.to_string_lossy().to_string(),
+            settings.texture_options,
+        );
+        info!("ミップピラミッドを作成しました: {} 段", tiled.level_count());
+        return Ok(Prepared::Tiled(tiled));
+    }
+    Ok(Prepared::Raster(Arc::new(color_image(&image.to_rgba8()))))
+}
+
+fn color_image(image: &image::RgbaImage) -> egui::ColorImage {
+    let size = [image.width() as usize, image.height() as usize];
+    egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw())
+}
+
+/// テクスチャ上限を超える画像を、アスペクト比を保ったまま収まるよう縮小する
+fn fit_texture_limit(image: image::DynamicImage, max: u32) -> image::DynamicImage {
+    if image.width() > max || image.height() > max {
+        image.resize(max, max, image::imageops::FilterType::Triangle)
+    } else {
+        image
+    }
+}
+
+/// 埋め込みプロファイルに従って色を変換し、結果を icc_state に記録する。
+/// 無効設定時や変換に失敗した場合は元の画像をそのまま返す
+fn apply_icc(
+    image: image::DynamicImage,
+    icc: &[u8],
+    path: &Path,
+    settings: &DecodeSettings,
+    icc_state: &mut color::IccState,
+) -> image::DynamicImage {
+    if !settings.color_management {
+        let name = moxcms::ColorProfile::new_from_slice(icc)
+            .map(|p| color::profile_name(&p))
+            .unwrap_or_else(|_| "ICC".to_string());
+        *icc_state = color::IccState::Unmanaged(name);
+        return image;
+    }
+    match color::convert(&image, icc, path, &settings.output_profile) {
+        Ok((converted, name)) => {
+            info!("ICC プロファイルを適用: {}", name);
+            *icc_state = color::IccState::Applied(name);
+            converted
+        }
+        Err(e) => {
+            info!("ICC プロファイルを適用できないため未変換で表示します: {}", e);
+            *icc_state = color::IccState::Failed(e);
+            image
+        }
+    }
+}
+
+/// 浮動小数の画像を保持したまま、現在の露出・トーンマッピングで最初の表示用画像を作る
+fn prepare_hdr(image: image::DynamicImage, settings: &DecodeSettings) -> Prepared {
+    // HDR はタイル表示の対象外。テクスチャ上限を超える場合は浮動小数のまま縮小する
+    let image = fit_texture_limit(image, settings.max_texture_side);
+    let hdr_image = hdr::HdrImage::new(image.into_rgba32f());
+    let exposure = if settings.auto_exposure {
+        hdr_image.auto_exposure
+    } else {
+        0.0
+    };
+    info!("HDR 画像を読み込みました（自動露出 {:+.2}EV）", hdr_image.auto_exposure);
+    let params = hdr::ToneParams {
+        exposure,
+        gamma: settings.hdr_gamma,
+        tone_map: settings.tone_mapping,
+    };
+    let first = Arc::new(hdr_image.render(params));
+    Prepared::Hdr {
+        image: hdr_image,
+        exposure,
+        params,
+        first,
+    }
+}
+
+/// 全フレームを（テクスチャ上限に収めて）表示用画像にする
+fn prepare_animation(frames: Vec<(image::RgbaImage, Duration)>, settings: &DecodeSettings) -> Prepared {
+    let frames = frames
+        .into_iter()
+        .map(|(frame, delay)| {
+            // 静止画と同じく、テクスチャ上限を超えるフレームは縮小する
+            let frame = fit_texture_limit(image::DynamicImage::ImageRgba8(frame), settings.max_texture_side)
+                .to_rgba8();
+            (Arc::new(color_image(&frame)), delay)
+        })
+        .collect();
+    Prepared::Animated(frames)
+}
+
+/// EXR の選択中レイヤー・チャンネルを表示用に準備する。
+/// 色レイヤーは HDR として、深度・法線・単一チャンネルは値域を合わせた 8bit 画像として表示する
+pub fn prepare_exr_view(
+    file: &exr_layers::ExrFile,
+    selection: exr_layers::Selection,
+    settings: &DecodeSettings,
+) -> Result<Prepared, String> {
+    Ok(match file.render(selection)? {
+        exr_layers::View::Color(image) => {
+            prepare_hdr(image::DynamicImage::ImageRgba32F(image), settings)
+        }
+        exr_layers::View::Display(image) => {
+            let image = fit_texture_limit(image::DynamicImage::ImageRgba8(image), settings.max_texture_side);
+            Prepared::Raster(Arc::new(color_image(&image.to_rgba8())))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> DecodeSettings {
+        DecodeSettings {
+            auto_orient: true,
+            color_management: true,
+            output_profile: Arc::new(moxcms::ColorProfile::new_srgb()),
+            max_texture_side: 8192,
+            texture_options: egui::TextureOptions::LINEAR,
+            hdr_gamma: 2.2,
+            tone_mapping: hdr::ToneMap::Aces,
+            auto_exposure: true,
+        }
+    }
+
+    fn write_png(dir: &Path, name: &str, v: u8) -> PathBuf {
+        let path = dir.join(name);
+        image::GrayImage::from_pixel(8, 8, image::Luma([v])).save(&path).unwrap();
+        path
+    }
+
+    fn wait(loader: &mut Loader) -> Result<Decoded, (PathBuf, String)> {
+        for _ in 0..500 {
+            if let Some(res) = loader.poll() {
+                return res;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("デコードが終わらない");
+    }
+
+    #[test]
+    fn decodes_in_background_and_serves_prefetch_from_cache() {
+        let dir = std::env::temp_dir().join(format!("msbt-loader-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = write_png(&dir, "a.png", 10);
+        let b = write_png(&dir, "b.png", 200);
+        let ctx = egui::Context::default();
+        let mut loader = Loader::new(&ctx, 64 * 1024 * 1024);
+        let s = settings();
+
+        assert!(loader.request(&a, 0, &s).is_none(), "初回はワーカーでデコード");
+        assert!(loader.is_loading());
+        let decoded = wait(&mut loader).unwrap();
+        assert_eq!(decoded.path, a);
+        match decoded.prepared {
+            Prepared::Raster(img) => assert_eq!(img.size, [8, 8]),
+            _ => panic!("静止画はラスタとして準備される"),
+        }
+
+        loader.prefetch(&[(b.clone(), 0)], &s);
+        for _ in 0..500 {
+            loader.poll();
+            if loader.cache.contains(&CacheKey::new(&b, 0)) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(loader.request(&b, 0, &s).is_some(), "先読み済みなら即座に返る");
+        assert!(!loader.is_loading());
+
+        // 書き換えられたファイル（更新日時が変わったもの）はデコードし直す
+        let file = std::fs::File::options().write(true).open(&b).unwrap();
+        file.set_modified(std::time::SystemTime::now() + Duration::from_secs(60)).unwrap();
+        drop(file);
+        assert!(loader.request(&b, 0, &s).is_none());
+        assert!(wait(&mut loader).is_ok());
+
+        // 設定が変わるとキャッシュは破棄される
+        let changed = DecodeSettings {
+            auto_orient: false,
+            ..s.clone()
+        };
+        assert!(loader.request(&b, 0, &changed).is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn pane_requests_share_the_cache_and_survive_display_requests() {
+        let dir = std::env::temp_dir().join(format!("msbt-loader-pane-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = write_png(&dir, "a.png", 10);
+        let b = write_png(&dir, "b.png", 200);
+        let ctx = egui::Context::default();
+        let mut loader = Loader::new(&ctx, 64 * 1024 * 1024);
+        let s = settings();
+
+        assert!(loader.request_pane(&b, 0, &s).is_none());
+        // 表示要求を出しても、ペインの要求は取り消されない
+        assert!(loader.request(&a, 0, &s).is_none());
+        let mut panes = Vec::new();
+        for _ in 0..500 {
+            panes.extend(loader.poll_panes());
+            if !panes.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(panes.len(), 1);
+        assert_eq!(panes[0].0, (b.clone(), 0));
+        assert!(panes[0].1.is_ok());
+        assert!(wait(&mut loader).is_ok(), "表示要求の結果も届く");
+        // ペイン用にデコードした結果はキャッシュに入り、表示にもそのまま使える
+        assert!(loader.request(&b, 0, &s).is_some());
+        assert!(loader.request_pane(&a, 0, &s).is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_file_reports_error() {
+        let ctx = egui::Context::default();
+        let mut loader = Loader::new(&ctx, 64 * 1024 * 1024);
+        loader.request(Path::new("no/such/image.png"), 0, &settings());
+        assert!(wait(&mut loader).is_err());
+    }
+}
@@ -19,13 +19,33 @@ use std::io::Cursor;
 use std::panic;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use resvg::tiny_skia::{self, Pixmap};
 use usvg::{Options, Tree};
 // vello はバージョン整合のため vello_svg の再エクスポートを使う
 use vello_svg::vello;
 
+mod animation;
+mod archive;
+mod cache;
+mod color;
+mod diff;
+mod exr_layers;
+mod folders;
+mod hdr;
+mod histogram;
+mod loader;
+mod metadata;
+mod pages;
+mod playlist;
+mod slideshow;
+mod sort;
+mod spread;
+mod thumb_cache;
+mod thumbs;
+mod tiles;
 mod updater;
+mod watcher;
 use updater::UpdateStatus;
 
 /// 対応する画像拡張子。image クレートでデコードできるもの（png/jpg/gif/webp/bmp/tiff/ico/tga/
@@ -34,7 +54,7 @@ use updater::UpdateStatus;
 const SUPPORTED_EXTS: &[&str] = &[
     "png", "jpg", "jpeg", "jfif", "gif", "webp", "bmp", "dib", "svg", "svgz", "heic", "heif",
     "avif", "tif", "tiff", "ico", "tga", "dds", "exr", "hdr", "qoi", "pnm", "ppm", "pgm", "pbm",
-    "pam", "ff", "farbfeld", "jxr", "wdp",
+    "pam", "ff", "farbfeld", "jxr", "wdp", "cur",
 ];
 
 /// 拡大率の下限・上限
@@ -44,6 +64,20 @@ const MAX_SCALE: f32 = 64.0;
 const KEY_ZOOM_STEP: f32 = 1.2;
 /// SVG のパン中の再レンダリング回数を減らすため、可視領域の外側に付ける描画余白（物理px）
 const SVG_RENDER_MARGIN_PX: f32 = 256.0;
+/// フィルムストリップ・グリッド表示のセル 1 つの辺（論理px）
+const FILMSTRIP_CELL: f32 = 88.0;
+const GRID_CELL: f32 = 184.0;
+/// 比較表示のペイン数の上限（表示中の画像を含む）
+const MAX_COMPARE_PANES: usize = 4;
+/// ワイプの境界線をつかめる左右の幅（論理 px）
+const WIPE_GRAB_WIDTH: f32 = 8.0;
+/// ピクセルインスペクターの履歴に残す件数の上限（超えたら古いものから捨てる）
+const PIXEL_HISTORY_LIMIT: usize = 200;
+/// タイルの格子を描く最小の間隔（論理 px。これより細かいと線で埋まるので描かない）
+const MIN_GRID_SPACING: f32 = 4.0;
+/// スライドショーの間隔（秒）の下限・上限
+const MIN_SLIDESHOW_INTERVAL: f32 = 0.5;
+const MAX_SLIDESHOW_INTERVAL: f32 = 3600.0;
 
 /// 設定ファイル（TOML）の内容
 #[derive(Serialize, Deserialize, Debug)]
@@ -66,16 +100,150 @@ pub struct ViewerConfig {
     /// 失敗時や未対応機能を含む SVG では自動的に CPU（resvg）へフォールバックする
     #[serde(default = "default_true")]
     pub gpu_rendering: bool,
+    /// EXIF の Orientation（左右反転を含む）に従って読み込み時に向きを補正するかどうか。
+    /// L/R の手動回転は補正後の画像に対して掛かる
+    #[serde(default = "default_true")]
+    pub auto_orient: bool,
+    /// 埋め込み ICC プロファイルに従って色を変換するかどうか（C キーで一時的に切り替え可）
+    #[serde(default = "default_true")]
+    pub color_management: bool,
+    /// 変換先の ICC プロファイルのパス。空なら sRGB
+    #[serde(default)]
+    pub output_icc_profile: String,
+    /// HDR（EXR / HDR 等の浮動小数画像）のトーンマッピング方式
+    #[serde(default)]
+    pub tone_mapping: hdr::ToneMap,
+    /// HDR を 8bit 表示へ変換するときのガンマ
+    #[serde(default = "default_hdr_gamma")]
+    pub hdr_gamma: f32,
+    /// HDR を開いたときに輝度ヒストグラムから露出を自動で決めるかどうか（false なら 0EV）
+    #[serde(default = "default_true")]
+    pub auto_exposure: bool,
+    /// デコード済み画像・解析済み SVG を保持するキャッシュの上限（MB）。先読みもこの範囲で行う
+    #[serde(default = "default_cache_budget_mb")]
+    pub cache_budget_mb: usize,
+    /// 画面下部にフォルダ内のサムネイル（フィルムストリップ）を表示するかどうか（T キーで切り替え）
+    #[serde(default)]
+    pub show_filmstrip: bool,
+    /// 作ったサムネイルをディスクに保存して次回から使うかどうか
+    #[serde(default = "default_true")]
+    pub thumbnail_cache: bool,
+    /// サムネイルのディスクキャッシュの上限（MB）。超えた分は起動時に古いものから消す
+    #[serde(default = "default_thumbnail_cache_mb")]
+    pub thumbnail_cache_mb: u64,
+    /// フォルダ内の画像の並び順
+    #[serde(default)]
+    pub sort_order: sort::SortKey,
+    /// 並び順を逆（降順）にするかどうか
+    #[serde(default)]
+    pub sort_descending: bool,
+    /// フォルダ送りでサブフォルダの画像も続けて辿るかどうか
+    #[serde(default)]
+    pub recursive_folders: bool,
+    /// フォルダ・表示中のファイルの変更を監視して自動で反映するかどうか
+    #[serde(default = "default_true")]
+    pub watch_changes: bool,
+    /// スライドショーの表示間隔（秒）
+    #[serde(default = "default_slideshow_interval")]
+    pub slideshow_interval: f32,
+    /// スライドショーの再生順
+    #[serde(default)]
+    pub slideshow_order: slideshow::SlideOrder,
+    /// スライドショーを最後まで表示したら先頭へ戻って続けるかどうか
+    #[serde(default = "default_true")]
+    pub slideshow_loop: bool,
+    /// スライドショーで画像をクロスフェードで切り替えるかどうか
+    #[serde(default = "default_true")]
+    pub slideshow_crossfade: bool,
+    /// スライドショーを全画面で始めるかどうか（止めると元に戻る）
+    #[serde(default)]
+    pub slideshow_fullscreen: bool,
+    /// 2 ページずつ並べる見開き表示にするかどうか（D キーで切り替え）
+    #[serde(default)]
+    pub spread_view: bool,
+    /// 見開きを右から左へ並べる（右綴じ）かどうか。← キーで次の見開きへ進む
+    #[serde(default)]
+    pub spread_right_to_left: bool,
+    /// 見開き表示で先頭ページ（表紙）を 1 ページで表示するかどうか
+    #[serde(default = "default_true")]
+    pub spread_cover_single: bool,
+    /// 見開き表示で横長のページを 1 ページで表示するかどうか
+    #[serde(default = "default_true")]
+    pub spread_landscape_single: bool,
+    /// 比較表示の差分モードで、差を何倍の明るさで表示するか
+    #[serde(default = "default_difference_gain")]
+    pub difference_gain: f32,
+    /// カーソル下の画素の座標と色を表示するかどうか（I キーで切り替え）
+    #[serde(default)]
+    pub pixel_inspector: bool,
+    /// 拡大したときに画素の境界の格子を表示するかどうか
+    #[serde(default = "default_true")]
+    pub pixel_grid: bool,
+    /// 画面上の 1 画素がこの大きさ（論理 px）以上になったら画素の格子を表示する
+    #[serde(default = "default_pixel_grid_threshold")]
+    pub pixel_grid_threshold: f32,
+    /// タイル単位の格子（スプライト・テクスチャ作業用）を表示するかどうか
+    #[serde(default)]
+    pub tile_grid: bool,
+    /// タイルの幅（画素）
+    #[serde(default = "default_tile_grid_size")]
+    pub tile_grid_width: u32,
+    /// タイルの高さ（画素）
+    #[serde(default = "default_tile_grid_size")]
+    pub tile_grid_height: u32,
+    /// ヒストグラムとチャンネルごとの統計のパネルを表示するかどうか
+    #[serde(default)]
+    pub show_histogram: bool,
+    /// ヒストグラムパネルの位置
+    #[serde(default)]
+    pub histogram_dock: histogram::Dock,
 }
 
 fn default_wheel_zoom_factor() -> f32 {
     0.001
 }
 
+fn default_slideshow_interval() -> f32 {
+    5.0
+}
+
+/// スライドショーの間隔を使える範囲に収める（NaN は既定値にする）
+fn clamp_slideshow_interval(secs: f32) -> f32 {
+    if secs.is_nan() {
+        default_slideshow_interval()
+    } else {
+        secs.clamp(MIN_SLIDESHOW_INTERVAL, MAX_SLIDESHOW_INTERVAL)
+    }
+}
+
+fn default_difference_gain() -> f32 {
+    1.0
+}
+
+fn default_pixel_grid_threshold() -> f32 {
+    8.0
+}
+
+fn default_tile_grid_size() -> u32 {
+    16
+}
+
 fn default_true() -> bool {
     true
 }
 
+fn default_hdr_gamma() -> f32 {
+    2.2
+}
+
+fn default_cache_budget_mb() -> usize {
+    512
+}
+
+fn default_thumbnail_cache_mb() -> u64 {
+    256
+}
+
 impl Default for ViewerConfig {
     fn default() -> Self {
         Self {
@@ -85,6 +253,38 @@ impl Default for ViewerConfig {
             smooth_zoom: true,
             check_updates: true,
             gpu_rendering: true,
+            auto_orient: true,
+            color_management: true,
+            output_icc_profile: String::new(),
+            tone_mapping: hdr::ToneMap::default(),
+            hdr_gamma: default_hdr_gamma(),
+            auto_exposure: true,
+            cache_budget_mb: default_cache_budget_mb(),
+            show_filmstrip: false,
+            thumbnail_cache: true,
+            thumbnail_cache_mb: default_thumbnail_cache_mb(),
+            sort_order: sort::SortKey::default(),
+            sort_descending: false,
+            recursive_folders: false,
+            watch_changes: true,
+            slideshow_interval: default_slideshow_interval(),
+            slideshow_order: slideshow::SlideOrder::default(),
+            slideshow_loop: true,
+            slideshow_crossfade: true,
+            slideshow_fullscreen: false,
+            spread_view: false,
+            spread_right_to_left: false,
+            spread_cover_single: true,
+            spread_landscape_single: true,
+            difference_gain: default_difference_gain(),
+            pixel_inspector: false,
+            pixel_grid: true,
+            pixel_grid_threshold: default_pixel_grid_threshold(),
+            tile_grid: false,
+            tile_grid_width: default_tile_grid_size(),
+            tile_grid_height: default_tile_grid_size(),
+            show_histogram: false,
+            histogram_dock: histogram::Dock::default(),
         }
     }
 }
@@ -98,7 +298,8 @@ impl ViewerConfig {
             .to_string_lossy();
         let config_file = format!("{}.toml", exe_name);
         let config_str = fs::read_to_string(&config_file)?;
-        let config: ViewerConfig = toml::from_str(&config_str)?;
+        let mut config: ViewerConfig = toml::from_str(&config_str)?;
+        config.slideshow_interval = clamp_slideshow_interval(config.slideshow_interval);
         info!("設定を読み込みました: wheel_zoom_factor = {}", config.wheel_zoom_factor);
         Ok(config)
     }
@@ -110,9 +311,13 @@ impl ViewerConfig {
             .ok_or("Failed to get executable name")?
             .to_string_lossy();
         let config_file = format!("{}.toml", exe_name);
+        fs::write(config_file, self.to_toml())?;
+        Ok(())
+    }
 
-        // 設定ファイルのテンプレート
-        let config_template = format!(
+    /// 設定ファイルの内容（説明のコメント付きのテンプレート）。文字列の値は TOML の規則でエスケープする
+    fn to_toml(&self) -> String {
+        format!(
             "# 初期表示モード: \"fit\"=画面に合わせて表示, \"original\"=原寸大(100%)\n\
              initial_display_mode = \"{}\"\n\
              \n\
@@ -129,17 +334,142 @@ impl ViewerConfig {
              check_updates = {}\n\
              \n\
              # SVGをGPUで描画するかどうか（失敗時は自動でCPUに切り替え）\n\
-             gpu_rendering = {}\n",
+             gpu_rendering = {}\n\
+             \n\
+             # EXIFの向き情報に従って自動で回転・反転するかどうか\n\
+             auto_orient = {}\n\
+             \n\
+             # 埋め込みICCプロファイルに従って色を変換するかどうか\n\
+             color_management = {}\n\
+             \n\
+             # 変換先ICCプロファイルのパス（空欄ならsRGB）\n\
+             output_icc_profile = {}\n\
+             \n\
+             # HDR(EXR/HDR)のトーンマッピング: \"clamp\", \"reinhard\", \"aces\"\n\
+             tone_mapping = \"{}\"\n\
+             \n\
+             # HDRを表示するときのガンマ\n\
+             hdr_gamma = {}\n\
+             \n\
+             # HDRを開いたときに露出を自動で合わせるかどうか\n\
+             auto_exposure = {}\n\
+             \n\
+             # デコード済み画像のキャッシュの上限(MB)。前後の画像の先読みもこの範囲で行う\n\
+             cache_budget_mb = {}\n\
+             \n\
+             # 画面下部にサムネイルの一覧(フィルムストリップ)を表示するかどうか\n\
+             show_filmstrip = {}\n\
+             \n\
+             # サムネイルをディスクに保存して次回から使うかどうか\n\
+             # (Linux は ~/.cache/thumbnails、Windows は %LOCALAPPDATA%\\MSBT-yuina\\thumbnails)\n\
+             thumbnail_cache = {}\n\
+             \n\
+             # サムネイルのディスクキャッシュの上限(MB)\n\
+             thumbnail_cache_mb = {}\n\
+             \n\
+             # フォルダ内の並び順: \"natural\"=名前(自然順), \"name\"=名前(文字コード順), \"modified\"=更新日時,\n\
+             # \"exif\"=撮影日時, \"size\"=ファイルサイズ, \"extension\"=拡張子\n\
+             sort_order = \"{}\"\n\
+             \n\
+             # 並び順を逆(降順)にするかどうか\n\
+             sort_descending = {}\n\
+             \n\
+             # フォルダ送りでサブフォルダの画像も(名前の自然順で)続けて辿るかどうか\n\
+             recursive_folders = {}\n\
+             \n\
+             # フォルダ・表示中のファイルの変更(追加・削除・書き換え)を監視して自動で反映するかどうか\n\
+             watch_changes = {}\n\
+             \n\
+             # スライドショーの表示間隔(秒)\n\
+             slideshow_interval = {}\n\
+             \n\
+             # スライドショーの再生順: \"sequential\"=一覧の順, \"reverse\"=逆順, \"shuffle\"=ランダム\n\
+             slideshow_order = \"{}\"\n\
+             \n\
+             # スライドショーを最後まで表示したら先頭へ戻って続けるかどうか\n\
+             slideshow_loop = {}\n\
+             \n\
+             # スライドショーで画像をクロスフェードで切り替えるかどうか\n\
+             slideshow_crossfade = {}\n\
+             \n\
+             # スライドショーを全画面で始めるかどうか(止めると元に戻る)\n\
+             slideshow_fullscreen = {}\n\
+             \n\
+             # 2ページずつ並べる見開き表示にするかどうか(D キーで切り替え)\n\
+             spread_view = {}\n\
+             \n\
+             # 見開きを右から左へ並べる(右綴じ)かどうか\n\
+             spread_right_to_left = {}\n\
+             \n\
+             # 見開き表示で先頭ページ(表紙)を1ページで表示するかどうか\n\
+             spread_cover_single = {}\n\
+             \n\
+             # 見開き表示で横長のページを1ページで表示するかどうか\n\
+             spread_landscape_single = {}\n\
+             \n\
+             # 比較表示の差分モードで、差を何倍の明るさで表示するか(1〜64)\n\
+             difference_gain = {}\n\
+             \n\
+             # カーソル下の画素の座標と色を表示するかどうか(I キーで切り替え)\n\
+             pixel_inspector = {}\n\
+             \n\
+             # 拡大したときに画素の境界の格子を表示するかどうか\n\
+             pixel_grid = {}\n\
+             \n\
+             # 画面上の1画素がこの大きさ(px)以上になったら画素の格子を表示する\n\
+             pixel_grid_threshold = {}\n\
+             \n\
+             # タイル単位の格子(スプライト・テクスチャ作業用)を表示するかどうか\n\
+             tile_grid = {}\n\
+             \n\
+             # タイルの大きさ(幅・高さ、画素)\n\
+             tile_grid_width = {}\n\
+             tile_grid_height = {}\n\
+             \n\
+             # ヒストグラムとチャンネルごとの統計のパネルを表示するかどうか\n\
+             show_histogram = {}\n\
+             \n\
+             # ヒストグラムパネルの位置: \"left\"=左, \"right\"=右, \"bottom\"=下\n\
+             histogram_dock = \"{}\"\n",
             self.initial_display_mode,
             self.enable_debug_log,
             self.wheel_zoom_factor,
             self.smooth_zoom,
             self.check_updates,
-            self.gpu_rendering
-        );
-
-        fs::write(config_file, config_template)?;
-        Ok(())
+            self.gpu_rendering,
+            self.auto_orient,
+            self.color_management,
+            toml::Value::String(self.output_icc_profile.clone()),
+            self.tone_mapping.name(),
+            self.hdr_gamma,
+            self.auto_exposure,
+            self.cache_budget_mb,
+            self.show_filmstrip,
+            self.thumbnail_cache,
+            self.thumbnail_cache_mb,
+            self.sort_order.name(),
+            self.sort_descending,
+            self.recursive_folders,
+            self.watch_changes,
+            self.slideshow_interval,
+            self.slideshow_order.name(),
+            self.slideshow_loop,
+            self.slideshow_crossfade,
+            self.slideshow_fullscreen,
+            self.spread_view,
+            self.spread_right_to_left,
+            self.spread_cover_single,
+            self.spread_landscape_single,
+            self.difference_gain,
+            self.pixel_inspector,
+            self.pixel_grid,
+            self.pixel_grid_threshold,
+            self.tile_grid,
+            self.tile_grid_width,
+            self.tile_grid_height,
+            self.show_histogram,
+            self.histogram_dock.name()
+        )
     }
 }
 
@@ -197,12 +527,20 @@ fn main() -> eframe::Result<()> {
     }
     info!("アプリケーション起動開始");
 
-    // コマンドライン引数を取得
-    let args: Vec<String> = std::env::args().collect();
-    let initial_image = if args.len() > 1 {
-        let path = PathBuf::from(&args[1]);
-        if !path.exists() {
-            let message = format!("指定された画像が見つかりません: {}", path.display());
+    // コマンドライン引数を取得（複数のファイル・リストファイル・"-"（標準入力）はプレイリストとして開く）
+    let args: Vec<PathBuf> = std::env::args_os().skip(1).map(PathBuf::from).collect();
+    let initial = if args.is_empty() {
+        None
+    } else {
+        let initial = playlist::resolve(&args);
+        let missing = match &initial {
+            Some(playlist::Open::File(path)) if !path.exists() => {
+                Some(format!("指定された画像が見つかりません: {}", path.display()))
+            }
+            None => Some("指定されたファイルに開ける画像がありません（詳細はログを参照）".to_string()),
+            _ => None,
+        };
+        if let Some(message) = missing {
             error!("{}", message);
             eprintln!("エラー: {}", message);
             rfd::MessageDialog::new()
@@ -210,13 +548,16 @@ fn main() -> eframe::Result<()> {
                 .set_description(&message)
                 .show();
         }
-        Some(path)
-    } else {
-        None
+        initial
     };
 
-    if let Some(path) = &initial_image {
-        info!("コマンドライン引数で指定された画像: {}", path.display());
+    match &initial {
+        Some(playlist::Open::File(path)) => info!("コマンドライン引数で指定された画像: {}", path.display()),
+        Some(playlist::Open::Folder(dir)) => info!("コマンドライン引数で指定されたフォルダ: {}", dir.display()),
+        Some(playlist::Open::Playlist(paths)) => {
+            info!("コマンドライン引数で指定されたプレイリスト: {} 件", paths.len())
+        }
+        None => {}
     }
 
     let options = match create_app_options() {
@@ -239,7 +580,7 @@ fn main() -> eframe::Result<()> {
         options,
         Box::new(move |cc| {
             info!("アプリケーションコンテキストの作成開始");
-            Ok(Box::new(ImageViewer::new(cc, initial_image, config)))
+            Ok(Box::new(ImageViewer::new(cc, initial, config)))
         }),
     )
 }
@@ -263,14 +604,35 @@ fn create_app_options() -> Result<eframe::NativeOptions, Box<dyn std::error::Err
     Ok(options)
 }
 
+/// 左右・上下の反転。回転前の画像空間で適用する（表示 = 回転(反転(画像))）。
+/// 表示上の左右反転キーは、奇数回転中は画像空間の上下反転に相当する（toggle_flip 参照）
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+struct Flip {
+    h: bool,
+    v: bool,
+}
+
+impl Flip {
+    /// 表示上で左右（horizontal=true）または上下に反転する。
+    /// 90°/270° 回転中は画像空間の軸が入れ替わっているので、反対側の軸を切り替える
+    fn toggle(self, horizontal: bool, rot: u8) -> Self {
+        if horizontal != (rot % 2 == 1) {
+            Self { h: !self.h, ..self }
+        } else {
+            Self { v: !self.v, ..self }
+        }
+    }
+}
+
 /// SVG テクスチャが現在保持している描画領域。
-/// crop は「回転適用後の表示空間」における物理 px の矩形 [x, y, w, h]（画像原点基準）。
+/// crop は「回転・反転適用後の表示空間」における物理 px の矩形 [x, y, w, h]（画像原点基準）。
 #[derive(Clone, Copy, PartialEq)]
 struct SvgView {
     /// SVG のユーザー単位 → 物理 px の倍率（表示倍率 × pixels_per_point）
     scale_px: f32,
     /// 90°単位の回転（0..=3、時計回り）
     rot: u8,
+    flip: Flip,
     crop: [u32; 4],
 }
 
@@ -278,6 +640,7 @@ struct SvgView {
 struct SvgRenderJob {
     scale_px: f32,
     rot: u8,
+    flip: Flip,
     /// 表示空間（回転後）の crop [x,y,w,h]（物理px）
     crop: [u32; 4],
     /// SVG 空間の crop（レンダリングに使う矩形）
@@ -673,7 +1036,7 @@ impl GpuRenderer {
 /// （連続ズーム中の中間状態は描いても無駄になるので捨てる）。
 /// 送信側（LoadedImage::Svg）が破棄されるとスレッドは自動終了する。
 fn spawn_svg_render_worker(
-    tree: Tree,
+    tree: Arc<Tree>,
     use_gpu: bool,
     ctx: egui::Context,
 ) -> (mpsc::Sender<SvgRenderJob>, mpsc::Receiver<SvgRenderResult>) {
@@ -792,6 +1155,7 @@ fn spawn_svg_render_worker(
                 view: SvgView {
                     scale_px: job.scale_px,
                     rot: job.rot,
+                    flip: job.flip,
                     crop: job.crop,
                 },
                 image,
@@ -807,11 +1171,41 @@ fn spawn_svg_render_worker(
 
 /// 読み込んだ画像の種類を表す型
 /// Raster: 通常画像
+/// Animated: GIF/APNG/アニメーション WebP。全フレームをテクスチャ化し、タイマーで切り替える
+/// Hdr: 浮動小数の画像（EXR/HDR 等）。画素を保持し、露出・トーンマッピングの変更時にテクスチャを作り直す
+/// Tiled: GPU のテクスチャ上限を超える巨大画像。ミップピラミッドから可視タイルだけをテクスチャ化する
 /// Svg: 専用ワーカースレッドが可視領域だけを表示解像度でラスタライズする。
 ///      UI スレッドは要求を送り、完成までは手持ちのテクスチャを引き伸ばして表示する
 enum LoadedImage {
     Raster {
         texture: egui::TextureHandle,
+        /// texture の画素（ピクセルインスペクター用。先読みキャッシュと共有）
+        pixels: Arc<egui::ColorImage>,
+        path: PathBuf,
+    },
+    Animated {
+        /// 全フレーム（合成済み）のテクスチャ
+        frames: Vec<egui::TextureHandle>,
+        /// frames の画素
+        pixels: Vec<Arc<egui::ColorImage>>,
+        playback: animation::Playback,
+        /// 直近に再生を進めた時刻（次フレームまでの経過時間の計測用）
+        last_tick: std::time::Instant,
+        path: PathBuf,
+    },
+    Hdr {
+        texture: egui::TextureHandle,
+        image: hdr::HdrImage,
+        /// 露出（EV）。ファイルを開くたびに自動露出（または 0）に戻る
+        exposure: f32,
+        /// texture の作成に使ったパラメータ（変化したら作り直す）
+        applied: hdr::ToneParams,
+        /// ワーカースレッドで作り直している画素と、そのパラメータ（同時に 1 つだけ）
+        rendering: Option<(hdr::ToneParams, mpsc::Receiver<egui::ColorImage>)>,
+        path: PathBuf,
+    },
+    Tiled {
+        image: tiles::TiledImage,
         path: PathBuf,
     },
     Svg {
@@ -827,10 +1221,610 @@ enum LoadedImage {
         result_rx: mpsc::Receiver<SvgRenderResult>,
         /// 直近にワーカーへ依頼した内容（同一要求の重複送信を防ぐ）
         last_requested: Option<SvgView>,
+        /// 差分モードで、比べる相手の大きさにラスタライズし直すための木
+        tree: Arc<Tree>,
         path: PathBuf,
     },
 }
 
+impl LoadedImage {
+    /// 準備済みの画像をテクスチャ化する（EXR は表示用に準備したレイヤーを使う）
+    fn from_prepared(
+        ctx: &egui::Context,
+        path: &Path,
+        prepared: loader::Prepared,
+        options: egui::TextureOptions,
+        gpu_rendering: bool,
+    ) -> Self {
+        let name = path.to_string_lossy().to_string();
+        let path = path.to_path_buf();
+        match prepared {
+            loader::Prepared::Raster(image) => LoadedImage::Raster {
+                texture: ctx.load_texture(name, image.clone(), options),
+                pixels: image,
+                path,
+            },
+            loader::Prepared::Animated(frames) => {
+                let mut textures = Vec::with_capacity(frames.len());
+                let mut pixels = Vec::with_capacity(frames.len());
+                let mut delays = Vec::with_capacity(frames.len());
+                for (i, (frame, delay)) in frames.into_iter().enumerate() {
+                    textures.push(ctx.load_texture(format!("{}#{}", name, i), frame.clone(), options));
+                    pixels.push(frame);
+                    delays.push(delay);
+                }
+                info!("アニメーションを読み込みました: {} フレーム", textures.len());
+                LoadedImage::Animated {
+                    frames: textures,
+                    pixels,
+                    playback: animation::Playback::new(delays),
+                    last_tick: std::time::Instant::now(),
+                    path,
+                }
+            }
+            loader::Prepared::Hdr {
+                image,
+                exposure,
+                params,
+                first,
+            } => LoadedImage::Hdr {
+                texture: ctx.load_texture(name, first, options),
+                image,
+                exposure,
+                applied: params,
+                rendering: None,
+                path,
+            },
+            loader::Prepared::Tiled(image) => LoadedImage::Tiled { image, path },
+            loader::Prepared::Exr { view, .. } => {
+                Self::from_prepared(ctx, &path, *view, options, gpu_rendering)
+            }
+            loader::Prepared::Svg { tree, .. } => {
+                let size = tree.size();
+                let (w, h) = (size.width(), size.height());
+                info!("SVGサイズ: {}x{}", w, h);
+                // 以後のラスタライズはすべてワーカースレッドで行う。
+                // 前の画像のワーカーは、旧 LoadedImage が破棄されて送信側が閉じると自動終了する。
+                let (job_tx, result_rx) = spawn_svg_render_worker(tree.clone(), gpu_rendering, ctx.clone());
+                LoadedImage::Svg {
+                    size: [w, h],
+                    texture: None,
+                    view: None,
+                    job_tx,
+                    result_rx,
+                    last_requested: None,
+                    tree,
+                    path,
+                }
+            }
+        }
+    }
+
+    /// 回転前の大きさ（拡大率 1.0 のときの論理ポイント ＝ 画像の px）
+    fn base_size(&self) -> Vec2 {
+        match self {
+            LoadedImage::Raster { texture, .. } | LoadedImage::Hdr { texture, .. } => texture.size_vec2(),
+            LoadedImage::Animated { frames, .. } => frames.first().map_or(Vec2::ZERO, |t| t.size_vec2()),
+            LoadedImage::Tiled { image, .. } => {
+                let [w, h] = image.size();
+                Vec2::new(w as f32, h as f32)
+            }
+            LoadedImage::Svg { size, .. } => Vec2::new(size[0], size[1]),
+        }
+    }
+
+    /// ヒストグラムの計算に使う画素（アニメーションは表示中のフレーム。SVG は None）
+    fn histogram_source(&self) -> Option<HistogramSource> {
+        match self {
+            LoadedImage::Raster { pixels, .. } => Some(HistogramSource::Color(pixels.clone())),
+            LoadedImage::Animated { pixels, playback, .. } => {
+                pixels.get(playback.current()).cloned().map(HistogramSource::Color)
+            }
+            LoadedImage::Hdr { image, .. } => Some(HistogramSource::Float(image.clone())),
+            LoadedImage::Tiled { image, .. } => Some(HistogramSource::Tiled(image.clone())),
+            LoadedImage::Svg { .. } => None,
+        }
+    }
+
+    /// 差分の計算に使う画素（アニメーションは先頭フレーム、HDR は表示中の露出でトーンマッピングする）
+    fn difference_source(&self, hdr_gamma: f32, tone_map: hdr::ToneMap) -> DifferenceSource {
+        match self {
+            LoadedImage::Raster { pixels, .. } => DifferenceSource::Color(Some(pixels.clone())),
+            LoadedImage::Animated { pixels, .. } => DifferenceSource::Color(pixels.first().cloned()),
+            LoadedImage::Hdr { image, exposure, .. } => DifferenceSource::Hdr(
+                image.clone(),
+                hdr::ToneParams {
+                    exposure: *exposure,
+                    gamma: hdr_gamma,
+                    tone_map,
+                },
+            ),
+            LoadedImage::Tiled { image, .. } => DifferenceSource::Tiled(image.clone()),
+            LoadedImage::Svg { tree, .. } => DifferenceSource::Svg(tree.clone()),
+        }
+    }
+
+    /// 画像空間の座標 pos（SVG は等倍で描いたときの px）の画素を調べる。画像の外なら None
+    fn sample(&self, pos: Pos2) -> Option<PixelSample> {
+        let size = self.base_size();
+        if !(pos.x >= 0.0 && pos.y >= 0.0 && pos.x < size.x && pos.y < size.y) {
+            return None;
+        }
+        let (x, y) = (pos.x as u32, pos.y as u32);
+        let color_at = |image: &egui::ColorImage| {
+            image.pixels[y as usize * image.size[0] + x as usize].to_srgba_unmultiplied()
+        };
+        let (rgba, hdr) = match self {
+            LoadedImage::Raster { pixels, .. } => (Some(color_at(pixels)), None),
+            LoadedImage::Animated { pixels, playback, .. } => {
+                (pixels.get(playback.current()).map(|frame| color_at(frame)), None)
+            }
+            LoadedImage::Hdr { image, applied, .. } => {
+                let value = image.pixel(x, y);
+                (Some(applied.encode(value)), Some(value))
+            }
+            LoadedImage::Tiled { image, .. } => (Some(image.original().get_pixel(x, y).0), None),
+            LoadedImage::Svg { .. } => {
+                return Some(PixelSample {
+                    pos,
+                    rgba: None,
+                    hdr: None,
+                    vector: true,
+                })
+            }
+        };
+        Some(PixelSample {
+            pos: egui::pos2(x as f32, y as f32),
+            rgba,
+            hdr,
+            vector: false,
+        })
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            LoadedImage::Raster { path, .. }
+            | LoadedImage::Animated { path, .. }
+            | LoadedImage::Hdr { path, .. }
+            | LoadedImage::Tiled { path, .. }
+            | LoadedImage::Svg { path, .. } => path,
+        }
+    }
+
+    /// image_rect（回転・反転後の画像全体の矩形）に描く。タイル分割した画像と SVG は
+    /// panel_rect（表示領域。比較表示ではそのペイン）に見えている部分だけを用意する
+    fn draw(&mut self, ui: &mut egui::Ui, image_rect: Rect, panel_rect: Rect, params: &DrawParams) {
+        let DrawParams {
+            scale,
+            rotation,
+            flip,
+            ppp,
+            max_dim,
+            hdr_gamma,
+            tone_map,
+            raster_options,
+        } = *params;
+        let ctx = &ui.ctx().clone();
+        match self {
+            LoadedImage::Raster { texture, .. } => {
+                draw_texture_rotated(ui.painter(), texture, image_rect, rotation, flip);
+            }
+            LoadedImage::Animated {
+                frames,
+                playback,
+                last_tick,
+                ..
+            } => {
+                // 前回からの経過時間だけ再生を進め、次のフレーム切り替え時刻に再描画を予約する
+                let now = std::time::Instant::now();
+                playback.advance(now - *last_tick);
+                *last_tick = now;
+                if let Some(t) = frames.get(playback.current()) {
+                    draw_texture_rotated(ui.painter(), t, image_rect, rotation, flip);
+                }
+                if let Some(wait) = playback.time_to_next() {
+                    ctx.request_repaint_after(wait);
+                }
+            }
+            LoadedImage::Hdr {
+                texture,
+                image,
+                exposure,
+                applied,
+                rendering,
+                ..
+            } => {
+                // 作り直した画素が届いたらテクスチャを差し替える
+                if let Some((params, rx)) = rendering {
+                    match rx.try_recv() {
+                        Ok(colors) => {
+                            texture.set(colors, raster_options);
+                            *applied = *params;
+                            *rendering = None;
+                        }
+                        Err(mpsc::TryRecvError::Empty) => {}
+                        Err(mpsc::TryRecvError::Disconnected) => *rendering = None,
+                    }
+                }
+                // 露出・ガンマ・方式が変わったら保持している浮動小数の画素から裏で作り直す。
+                // 作り直している間は前のテクスチャを描き、届いた時点でまだ違っていればもう一度依頼する
+                // （スライダーをドラッグしている間も、依頼は同時に 1 つまで）
+                let params = hdr::ToneParams {
+                    exposure: *exposure,
+                    gamma: hdr_gamma,
+                    tone_map,
+                };
+                if params != *applied && rendering.is_none() {
+                    let (colors_tx, colors_rx) = mpsc::channel();
+                    let image = image.clone();
+                    let thread_ctx = ctx.clone();
+                    std::thread::spawn(move || {
+                        if colors_tx.send(image.render(params)).is_ok() {
+                            thread_ctx.request_repaint();
+                        }
+                    });
+                    *rendering = Some((params, colors_rx));
+                }
+                draw_texture_rotated(ui.painter(), texture, image_rect, rotation, flip);
+            }
+            LoadedImage::Tiled { image, .. } => {
+                // 可視部分を画像空間（原寸 px）へ戻し、そこに掛かるタイルだけを描く
+                let visible = image_rect.intersect(panel_rect);
+                if visible.is_positive() && scale > 0.0 {
+                    let [w, h] = image.size();
+                    let region = map_view_crop_to_svg(
+                        rotation,
+                        flip,
+                        w as f32,
+                        h as f32,
+                        [
+                            (visible.min.x - image_rect.min.x) / scale,
+                            (visible.min.y - image_rect.min.y) / scale,
+                            visible.width() / scale,
+                            visible.height() / scale,
+                        ],
+                    );
+                    let (tiles, pending) = image.tiles_for_view(ctx, region, scale * ppp);
+                    for tile in &tiles {
+                        let [x, y, tw, th] = map_image_rect_to_view(
+                            rotation,
+                            flip,
+                            w as f32,
+                            h as f32,
+                            tile.rect,
+                        );
+                        let rect = Rect::from_min_size(
+                            image_rect.min + egui::vec2(x, y) * scale,
+                            egui::vec2(tw, th) * scale,
+                        );
+                        draw_texture_rotated(ui.painter(), &tile.texture, rect, rotation, flip);
+                    }
+                    if pending {
+                        ctx.request_repaint();
+                    }
+                }
+            }
+            LoadedImage::Svg {
+                size,
+                texture,
+                view,
+                job_tx,
+                result_rx,
+                last_requested,
+                ..
+            } => {
+                // SVG はベクターなので、可視領域（＋余白）だけを表示解像度ちょうどで
+                // ラスタライズする（どんな拡大率でも 1px=1texel の鮮明さ）。
+                // ラスタライズはワーカースレッドで行い、UI はブロックしない。
+                // 完成までは手持ちのテクスチャを引き伸ばして表示する
+                // （精密な SVG ではズーム中に一瞬ぼやけ、止まると鮮明になる）。
+
+                // ワーカーからの完成テクスチャを受け取る（最後の 1 件だけ反映すれば十分）
+                let mut arrived: Option<SvgRenderResult> = None;
+                while let Ok(res) = result_rx.try_recv() {
+                    arrived = Some(res);
+                }
+                if let Some(res) = arrived {
+                    match texture.as_mut() {
+                        Some(t) => t.set(res.image, egui::TextureOptions::LINEAR),
+                        None => {
+                            *texture = Some(ctx.load_texture(
+                                "svg_view",
+                                res.image,
+                                egui::TextureOptions::LINEAR,
+                            ))
+                        }
+                    }
+                    *view = Some(res.view);
+                }
+
+                let scale_px = scale * ppp;
+                let visible = image_rect.intersect(panel_rect);
+                if visible.is_positive() && scale_px > 0.0 {
+                    let (full_w, full_h) = rotated_dims(size[0], size[1], rotation);
+                    let full_w_px = full_w * scale_px;
+                    let full_h_px = full_h * scale_px;
+                    // 可視部分（画像原点基準の物理px）
+                    let nx0 = ((visible.min.x - image_rect.min.x) * ppp)
+                        .floor()
+                        .clamp(0.0, full_w_px);
+                    let ny0 = ((visible.min.y - image_rect.min.y) * ppp)
+                        .floor()
+                        .clamp(0.0, full_h_px);
+                    let nx1 = ((visible.max.x - image_rect.min.x) * ppp)
+                        .ceil()
+                        .clamp(0.0, full_w_px);
+                    let ny1 = ((visible.max.y - image_rect.min.y) * ppp)
+                        .ceil()
+                        .clamp(0.0, full_h_px);
+
+                    // このフレームで必要な crop（入力から決定的に計算される）
+                    let (tx, tw) =
+                        crop_axis(nx0, nx1, full_w_px, SVG_RENDER_MARGIN_PX, max_dim);
+                    let (ty, th) =
+                        crop_axis(ny0, ny1, full_h_px, SVG_RENDER_MARGIN_PX, max_dim);
+                    let target = SvgView {
+                        scale_px,
+                        rot: rotation,
+                        flip,
+                        crop: [tx, ty, tw, th],
+                    };
+
+                    // いまのテクスチャで十分か: スケール・回転が一致し、必要領域を
+                    // 丸ごと含む（パン余白内）か、必要 crop と一致（可視領域が GPU 上限を
+                    // 超えるケース）していれば追加のラスタライズは不要。
+                    let satisfied = texture.is_some()
+                        && view.as_ref().map_or(false, |v| {
+                            v.rot == rotation
+                                && v.flip == flip
+                                && (v.scale_px - scale_px).abs() <= scale_px * 1e-4
+                                && (v.crop == target.crop
+                                    || (v.crop[0] as f32 <= nx0
+                                        && v.crop[1] as f32 <= ny0
+                                        && (v.crop[0] + v.crop[2]) as f32 >= nx1
+                                        && (v.crop[1] + v.crop[3]) as f32 >= ny1))
+                        });
+
+                    // 足りなければワーカーに依頼（同一要求の重複送信はしない）
+                    if !satisfied && *last_requested != Some(target) {
+                        let svg_crop = map_view_crop_to_svg(
+                            rotation,
+                            flip,
+                            size[0] * scale_px,
+                            size[1] * scale_px,
+                            [
+                                target.crop[0] as f32,
+                                target.crop[1] as f32,
+                                target.crop[2] as f32,
+                                target.crop[3] as f32,
+                            ],
+                        );
+                        let job = SvgRenderJob {
+                            scale_px,
+                            rot: rotation,
+                            flip,
+                            crop: target.crop,
+                            svg_crop,
+                        };
+                        if job_tx.send(job).is_ok() {
+                            *last_requested = Some(target);
+                        }
+                    }
+
+                    // 描画: テクスチャが保持する領域を現在のビューへ写像して描く。
+                    // スケールが一致していれば 1px=1texel の等倍描画。新しい結果が
+                    // まだ届いていない間は旧テクスチャが引き伸ばされる（ボケるが固まらない）。
+                    let mut drawn = false;
+                    if let (Some(t), Some(v)) = (texture.as_ref(), view.as_ref()) {
+                        if v.rot == rotation && v.flip == flip && v.scale_px > 0.0 {
+                            let factor = scale_px / v.scale_px;
+                            let tex_rect = Rect::from_min_size(
+                                image_rect.min
+                                    + egui::vec2(v.crop[0] as f32, v.crop[1] as f32)
+                                        * factor
+                                        / ppp,
+                                egui::vec2(v.crop[2] as f32, v.crop[3] as f32) * factor
+                                    / ppp,
+                            );
+                            draw_texture_rotated(ui.painter(), t, tex_rect, v.rot, v.flip);
+                            drawn = true;
+                        }
+                    }
+
+                    if !satisfied {
+                        // レンダリング待ちを示すスピナー（未描画なら中央、描画済みなら右上に小さく）
+                        let spinner_rect = if drawn {
+                            Rect::from_center_size(
+                                panel_rect.right_top() + egui::vec2(-24.0, 24.0),
+                                Vec2::splat(18.0),
+                            )
+                        } else {
+                            Rect::from_center_size(panel_rect.center(), Vec2::splat(32.0))
+                        };
+                        ui.put(spinner_rect, egui::Spinner::new());
+                        // 結果の取りこぼし防止の保険（通常はワーカーが repaint を要求する）
+                        ctx.request_repaint_after(std::time::Duration::from_millis(100));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 画像の描画に使う表示状態（比較表示では全ペインで共通）
+#[derive(Clone, Copy)]
+struct DrawParams {
+    scale: f32,
+    rotation: u8,
+    flip: Flip,
+    ppp: f32,
+    max_dim: f32,
+    hdr_gamma: f32,
+    tone_map: hdr::ToneMap,
+    raster_options: egui::TextureOptions,
+}
+
+/// ヒストグラムの計算に使う画素（共有されるので複製は軽い）
+enum HistogramSource {
+    Color(Arc<egui::ColorImage>),
+    Tiled(tiles::TiledImage),
+    /// EXR / HDR はトーンマッピング前の値で数える
+    Float(hdr::HdrImage),
+}
+
+impl HistogramSource {
+    fn compute(self) -> histogram::Stats {
+        match self {
+            HistogramSource::Color(image) => {
+                histogram::from_rgba8(image.pixels.iter().map(|c| c.to_srgba_unmultiplied()))
+            }
+            HistogramSource::Tiled(image) => histogram::from_rgba8(image.original().pixels().map(|p| p.0)),
+            HistogramSource::Float(image) => histogram::from_rgba32f(image.pixels()),
+        }
+    }
+}
+
+/// 差分の計算に使う画素（共有されるので複製は軽い）
+enum DifferenceSource {
+    Color(Option<Arc<egui::ColorImage>>),
+    Tiled(tiles::TiledImage),
+    /// HDR / EXR は表示と同じパラメータでトーンマッピングする
+    Hdr(hdr::HdrImage, hdr::ToneParams),
+    /// 相手の大きさ（相手も SVG なら SVG 本来の大きさ）でラスタライズする
+    Svg(Arc<Tree>),
+}
+
+impl DifferenceSource {
+    fn size(&self) -> Option<[u32; 2]> {
+        match self {
+            DifferenceSource::Color(image) => image.as_ref().map(|c| [c.size[0] as u32, c.size[1] as u32]),
+            DifferenceSource::Tiled(image) => Some(image.size()),
+            DifferenceSource::Hdr(image, _) => Some(image.size()),
+            DifferenceSource::Svg(_) => None,
+        }
+    }
+
+    /// 差分の計算し直しが要るかを判断するためのトーンマッピングのパラメータ
+    fn tone(&self) -> Option<hdr::ToneParams> {
+        match self {
+            DifferenceSource::Hdr(_, params) => Some(*params),
+            _ => None,
+        }
+    }
+
+    /// 表示と同じ色（カラーマネジメント・トーンマッピング後）の画素にする。
+    /// SVG は size（None なら SVG 本来の大きさ）でラスタライズする
+    fn pixels(&self, size: Option<[u32; 2]>) -> Result<image::RgbaImage, String> {
+        let unmultiply = |colors: &egui::ColorImage| {
+            let data = colors.pixels.iter().flat_map(|c| c.to_srgba_unmultiplied()).collect();
+            image::RgbaImage::from_raw(colors.size[0] as u32, colors.size[1] as u32, data)
+                .ok_or_else(|| "バッファサイズが不一致".to_string())
+        };
+        match self {
+            DifferenceSource::Color(Some(colors)) => unmultiply(colors),
+            DifferenceSource::Color(None) => Err("差分を求められない画像です".to_string()),
+            DifferenceSource::Tiled(image) => Ok(image.original().clone()),
+            DifferenceSource::Hdr(image, params) => unmultiply(&image.render(*params)),
+            DifferenceSource::Svg(tree) => {
+                let svg_size = tree.size();
+                let [w, h] = size.unwrap_or([
+                    svg_size.width().round().max(1.0) as u32,
+                    svg_size.height().round().max(1.0) as u32,
+                ]);
+                let mut pixmap = Pixmap::new(w, h).ok_or("差分表示用のバッファを確保できません")?;
+                let ts = usvg::Transform::from_scale(w as f32 / svg_size.width(), h as f32 / svg_size.height());
+                resvg::render(tree, ts, &mut pixmap.as_mut());
+                unmultiply(&egui::ColorImage::from_rgba_premultiplied([w as usize, h as usize], pixmap.data()))
+            }
+        }
+    }
+}
+
+/// 差分モード: a と b の差を求める。
+/// 片方だけが SVG なら、もう片方（書き出した PNG など）と同じ大きさにラスタライズして比べる
+fn compute_difference(a: &DifferenceSource, b: &DifferenceSource) -> Result<diff::Difference, String> {
+    let a_pixels = a.pixels(b.size())?;
+    let b_pixels = b.pixels(a.size())?;
+    diff::Difference::compute(&a_pixels, &b_pixels)
+}
+
+/// ヒストグラムパネルの計算結果
+struct HistogramView {
+    /// 計算した画像（ImageViewer::image_generation）。変わったら計算し直す
+    generation: u64,
+    /// バックグラウンドでの計算結果の受信元（届いたら None）
+    pending: Option<mpsc::Receiver<histogram::Stats>>,
+    /// 計算し直している間は前の画像の結果を出しておく。SVG では None
+    stats: Option<histogram::Stats>,
+}
+
+/// ピクセルインスペクターで調べた 1 点
+struct PixelSample {
+    /// 画像空間の座標（ラスタは画素の左上の整数座標、SVG は等倍で描いたときの px。
+    /// viewBox がある SVG ではユーザー単位とは一致しない）
+    pos: Pos2,
+    /// 表示している色（不透明度を掛ける前の 8bit RGBA）。SVG は None
+    rgba: Option<[u8; 4]>,
+    /// HDR のトーンマッピング前の値
+    hdr: Option<[f32; 4]>,
+    /// SVG の点（座標を小数まで出し、画素の枠は描かない）
+    vector: bool,
+}
+
+impl PixelSample {
+    /// OSD に出す行
+    fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.vector {
+            lines.push(format!("({:.2}, {:.2}) px @100%", self.pos.x, self.pos.y));
+        } else {
+            lines.push(format!("({}, {})", self.pos.x, self.pos.y));
+        }
+        if let Some([r, g, b, a]) = self.rgba {
+            let float = |v: u8| v as f32 / 255.0;
+            lines.push(format!("RGBA  {:3} {:3} {:3} {:3}", r, g, b, a));
+            lines.push(format!(
+                "Float {:.3} {:.3} {:.3} {:.3}",
+                float(r),
+                float(g),
+                float(b),
+                float(a)
+            ));
+            lines.push(format!("Hex   #{:02X}{:02X}{:02X}{:02X}", r, g, b, a));
+        }
+        if let Some([r, g, b, a]) = self.hdr {
+            lines.push(format!("HDR   {:.4} {:.4} {:.4} {:.4}", r, g, b, a));
+        }
+        lines
+    }
+
+    /// 履歴パネルに残す 1 行
+    fn to_text(&self, name: &str) -> String {
+        let mut text = format!("{} {}", name, self.lines()[0]);
+        if let Some([r, g, b, a]) = self.rgba {
+            text += &format!(
+                " #{:02X}{:02X}{:02X}{:02X} rgba({}, {}, {}, {}) float({:.3}, {:.3}, {:.3}, {:.3})",
+                r,
+                g,
+                b,
+                a,
+                r,
+                g,
+                b,
+                a,
+                r as f32 / 255.0,
+                g as f32 / 255.0,
+                b as f32 / 255.0,
+                a as f32 / 255.0
+            );
+        }
+        if let Some([r, g, b, a]) = self.hdr {
+            text += &format!(" hdr({:.4}, {:.4}, {:.4}, {:.4})", r, g, b, a);
+        }
+        text
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 enum GestureDirection {
     Left,
@@ -839,6 +1833,16 @@ enum GestureDirection {
     Down,
 }
 
+/// スライドショーのクロスフェードで重ねる直前の画像
+struct Fade {
+    texture: egui::TextureHandle,
+    rect: Rect,
+    rotation: u8,
+    flip: Flip,
+    /// 次の画像が表示された時刻（デコード待ちの間は直前の画像がそのまま表示されているので None）
+    start: Option<std::time::Instant>,
+}
+
 struct MouseGesture {
     is_active: bool,
     last_pos: Option<Vec2>,
@@ -991,37 +1995,254 @@ fn map_display_crop_to_svg(rot: u8, ws: f32, hs: f32, crop: [f32; 4]) -> [f32; 4
     }
 }
 
-/// テクスチャを rot×90°（時計回り）回転させて rect に描画する。
-/// egui::Image は非正方形の 90° 回転を素直に扱えないため、UV を回した Mesh で描く。
-fn draw_texture_rotated(painter: &egui::Painter, texture: &egui::TextureHandle, rect: Rect, rot: u8) {
-    use egui::epaint::{Mesh, Vertex};
-    let mut mesh = Mesh::with_texture(texture.id());
-    let corners = [
-        rect.left_top(),
-        rect.right_top(),
-        rect.right_bottom(),
-        rect.left_bottom(),
-    ];
-    let base = [
-        egui::pos2(0.0, 0.0),
-        egui::pos2(1.0, 0.0),
-        egui::pos2(1.0, 1.0),
-        egui::pos2(0.0, 1.0),
-    ];
-    for (i, corner) in corners.iter().enumerate() {
-        mesh.vertices.push(Vertex {
-            pos: *corner,
-            uv: base[(i + 4 - rot as usize % 4) % 4],
-            color: Color32::WHITE,
-        });
+/// 表示空間の矩形 crop を、反転前の画像空間の矩形へ写像する（map_display_crop_to_svg の反転対応版）。
+/// 回転を戻した後に反転を戻す。反転は自己逆なので同じ式で戻せる。
+fn map_view_crop_to_svg(rot: u8, flip: Flip, ws: f32, hs: f32, crop: [f32; 4]) -> [f32; 4] {
+    let [x, y, w, h] = map_display_crop_to_svg(rot, ws, hs, crop);
+    [
+        if flip.h { ws - x - w } else { x },
+        if flip.v { hs - y - h } else { y },
+        w,
+        h,
+    ]
+}
+
+/// map_view_crop_to_svg の逆。画像空間の矩形を、反転・回転後の表示空間の矩形へ写像する
+/// （タイル表示で各タイルの描画位置を求めるのに使う）。
+fn map_image_rect_to_view(rot: u8, flip: Flip, ws: f32, hs: f32, rect: [f32; 4]) -> [f32; 4] {
+    let [x, y, w, h] = rect;
+    let x = if flip.h { ws - x - w } else { x };
+    let y = if flip.v { hs - y - h } else { y };
+    match rot % 4 {
+        0 => [x, y, w, h],
+        1 => [hs - y - h, x, h, w],
+        2 => [ws - x - w, hs - y - h, w, h],
+        _ => [y, ws - x - w, h, w],
     }
-    mesh.indices.extend_from_slice(&[0, 1, 2, 0, 2, 3]);
-    painter.add(egui::Shape::mesh(mesh));
 }
 
-/// SVG 描画範囲の1軸ぶんを決める。可視区間 [n0, n1] を優先しつつ余白 margin を付け、
-/// 画像全体 full と GPU 上限 max_dim に収める。戻り値は (開始, 幅) の整数px。
-/// 可視区間そのものが max_dim を超える場合は中央の max_dim 窓に絞る（単一テクスチャでは
+/// 画面上の点を、image_rect に描いた画像（回転前の大きさ size）の回転・反転前の座標へ写像する
+/// （zoom_at・draw_texture_rotated による配置の逆。ピクセルインスペクター用）
+fn screen_to_image(pos: Pos2, image_rect: Rect, size: Vec2, rot: u8, flip: Flip) -> Pos2 {
+    let (vw, vh) = rotated_dims(size.x, size.y, rot);
+    let view = (pos - image_rect.min) * Vec2::new(vw / image_rect.width(), vh / image_rect.height());
+    let [x, y, _, _] = map_view_crop_to_svg(rot, flip, size.x, size.y, [view.x, view.y, 0.0, 0.0]);
+    egui::pos2(x, y)
+}
+
+/// 画像空間の矩形 rect を、image_rect に描いた画像の上の画面上の矩形へ写像する（screen_to_image の逆）
+fn image_rect_to_screen(rect: [f32; 4], image_rect: Rect, size: Vec2, rot: u8, flip: Flip) -> Rect {
+    let (vw, _) = rotated_dims(size.x, size.y, rot);
+    let k = image_rect.width() / vw;
+    let [x, y, w, h] = map_image_rect_to_view(rot, flip, size.x, size.y, rect);
+    Rect::from_min_size(image_rect.min + egui::vec2(x, y) * k, egui::vec2(w, h) * k)
+}
+
+/// ヒストグラムを 1 つ描く。縦軸は平方根で、端の段（黒つぶれ・白飛び）の突出で
+/// 他が潰れないよう、高さの基準は端を除いた段の最大値にする
+fn histogram_plot(ui: &mut egui::Ui, channels: &[(&histogram::Channel, Color32)]) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 80.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, Color32::from_gray(16));
+    let peak = channels
+        .iter()
+        .flat_map(|(channel, _)| &channel.bins[1..histogram::BINS - 1])
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1) as f32;
+    for (channel, color) in channels {
+        let points = channel
+            .bins
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| {
+                let x = rect.left() + rect.width() * i as f32 / (histogram::BINS - 1) as f32;
+                let y = rect.bottom() - rect.height() * (n as f32 / peak).sqrt().min(1.0);
+                egui::pos2(x, y)
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, *color)));
+    }
+}
+
+/// 画像空間の visible の範囲に、step 画素ごとの縦横の線を描く（to_screen は画像空間から画面への写像）
+fn draw_image_grid(
+    painter: &egui::Painter,
+    visible: Rect,
+    step: Vec2,
+    stroke: egui::Stroke,
+    to_screen: impl Fn(Pos2) -> Pos2,
+) {
+    for x in grid_positions(visible.min.x, visible.max.x, step.x) {
+        painter.line_segment(
+            [to_screen(egui::pos2(x, visible.min.y)), to_screen(egui::pos2(x, visible.max.y))],
+            stroke,
+        );
+    }
+    for y in grid_positions(visible.min.y, visible.max.y, step.y) {
+        painter.line_segment(
+            [to_screen(egui::pos2(visible.min.x, y)), to_screen(egui::pos2(visible.max.x, y))],
+            stroke,
+        );
+    }
+}
+
+/// from..=to にある step の倍数
+fn grid_positions(from: f32, to: f32, step: f32) -> impl Iterator<Item = f32> {
+    let first = (from / step).ceil() as i64;
+    let last = (to / step).floor() as i64;
+    (first..=last).map(move |k| k as f32 * step)
+}
+
+/// 画素の格子の色。見えている範囲の平均の明るさから、明るい画像には暗い線、暗い画像には明るい線にする
+/// （透明な部分は背景の市松模様の中間の明るさとして混ぜる）
+fn grid_color(image: &LoadedImage, visible: Rect) -> Color32 {
+    const SAMPLES: usize = 16;
+    let mut sum = 0.0;
+    let mut count = 0;
+    for j in 0..SAMPLES {
+        for i in 0..SAMPLES {
+            let t = Vec2::new(i as f32 + 0.5, j as f32 + 0.5) / SAMPLES as f32;
+            if let Some(PixelSample {
+                rgba: Some([r, g, b, a]),
+                ..
+            }) = image.sample(visible.min + visible.size() * t)
+            {
+                let luminance = (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32) / 255.0;
+                let alpha = a as f32 / 255.0;
+                sum += luminance * alpha + 0.5 * (1.0 - alpha);
+                count += 1;
+            }
+        }
+    }
+    if count > 0 && sum / count as f32 > 0.5 {
+        Color32::from_black_alpha(96)
+    } else {
+        Color32::from_white_alpha(80)
+    }
+}
+
+/// ピクセルインスペクターの OSD をカーソルの右下に描く（パネルからはみ出すときは反対側へ）
+fn draw_pixel_inspector(painter: &egui::Painter, panel_rect: Rect, cursor: Pos2, sample: &PixelSample) {
+    const SWATCH: f32 = 24.0;
+    let galley = painter.layout_no_wrap(sample.lines().join("\n"), egui::FontId::monospace(13.0), Color32::WHITE);
+    let swatch_width = if sample.rgba.is_some() { SWATCH + 8.0 } else { 0.0 };
+    let size = galley.size() + egui::vec2(16.0 + swatch_width, 12.0);
+    let mut min = cursor + egui::vec2(16.0, 16.0);
+    if min.x + size.x > panel_rect.right() {
+        min.x = cursor.x - 16.0 - size.x;
+    }
+    if min.y + size.y > panel_rect.bottom() {
+        min.y = cursor.y - 16.0 - size.y;
+    }
+    let rect = Rect::from_min_size(min, size);
+    painter.rect_filled(rect, 4.0, Color32::from_black_alpha(200));
+    painter.galley(rect.min + egui::vec2(8.0, 6.0), galley, Color32::WHITE);
+    if let Some([r, g, b, a]) = sample.rgba {
+        let swatch = Rect::from_min_size(
+            egui::pos2(rect.right() - 8.0 - SWATCH, rect.top() + 6.0),
+            Vec2::splat(SWATCH),
+        );
+        painter.rect_filled(swatch, 2.0, Color32::from_rgba_unmultiplied(r, g, b, a));
+        painter.rect_stroke(
+            swatch,
+            2.0,
+            egui::Stroke::new(1.0, Color32::GRAY),
+            egui::StrokeKind::Outside,
+        );
+    }
+}
+
+/// パネルを n 等分した比較表示のペインの矩形（左から順）
+fn pane_rects(panel_rect: Rect, n: usize) -> Vec<Rect> {
+    let width = panel_rect.width() / n as f32;
+    (0..n)
+        .map(|i| {
+            Rect::from_min_size(
+                panel_rect.min + egui::vec2(width * i as f32, 0.0),
+                egui::vec2(width, panel_rect.height()),
+            )
+        })
+        .collect()
+}
+
+/// pos を含むペインの番号。どれにも入らなければ 0（表示中の画像のペイン）
+fn pane_index(rects: &[Rect], pos: Pos2) -> usize {
+    rects.iter().position(|r| r.contains(pos)).unwrap_or(0)
+}
+
+/// avail を panes 個に横分割した 1 ペインに、bases のうちいちばん大きい画像が収まる拡大率。
+/// 大きさが 0 なら None
+fn fit_scale(avail: Vec2, panes: usize, bases: &[Vec2]) -> Option<f32> {
+    let avail = Vec2::new(avail.x / panes as f32, avail.y);
+    let base = bases.iter().copied().fold(Vec2::ZERO, Vec2::max);
+    (base.x > 0.0 && base.y > 0.0 && avail.x > 0.0 && avail.y > 0.0)
+        .then(|| (avail.x / base.x).min(avail.y / base.y))
+}
+
+/// 表示矩形の四隅（左上・右上・右下・左下）に割り当てるテクスチャ UV。
+/// 回転で隅をずらしてから、反転した軸の UV を 1 - u / 1 - v にする。
+fn texture_uvs(rot: u8, flip: Flip) -> [Pos2; 4] {
+    let base = [
+        egui::pos2(0.0, 0.0),
+        egui::pos2(1.0, 0.0),
+        egui::pos2(1.0, 1.0),
+        egui::pos2(0.0, 1.0),
+    ];
+    std::array::from_fn(|i| {
+        let uv = base[(i + 4 - rot as usize % 4) % 4];
+        egui::pos2(
+            if flip.h { 1.0 - uv.x } else { uv.x },
+            if flip.v { 1.0 - uv.y } else { uv.y },
+        )
+    })
+}
+
+/// テクスチャを反転してから rot×90°（時計回り）回転させて rect に描画する。
+/// egui::Image は非正方形の 90° 回転を素直に扱えないため、UV を回した Mesh で描く。
+fn draw_texture_rotated(
+    painter: &egui::Painter,
+    texture: &egui::TextureHandle,
+    rect: Rect,
+    rot: u8,
+    flip: Flip,
+) {
+    draw_texture_tinted(painter, texture, rect, rot, flip, Color32::WHITE);
+}
+
+/// draw_texture_rotated の色（不透明度）指定版
+fn draw_texture_tinted(
+    painter: &egui::Painter,
+    texture: &egui::TextureHandle,
+    rect: Rect,
+    rot: u8,
+    flip: Flip,
+    tint: Color32,
+) {
+    use egui::epaint::{Mesh, Vertex};
+    let mut mesh = Mesh::with_texture(texture.id());
+    let corners = [
+        rect.left_top(),
+        rect.right_top(),
+        rect.right_bottom(),
+        rect.left_bottom(),
+    ];
+    let uvs = texture_uvs(rot, flip);
+    for (corner, uv) in corners.iter().zip(uvs) {
+        mesh.vertices.push(Vertex {
+            pos: *corner,
+            uv,
+            color: tint,
+        });
+    }
+    mesh.indices.extend_from_slice(&[0, 1, 2, 0, 2, 3]);
+    painter.add(egui::Shape::mesh(mesh));
+}
+
+/// SVG 描画範囲の1軸ぶんを決める。可視区間 [n0, n1] を優先しつつ余白 margin を付け、
+/// 画像全体 full と GPU 上限 max_dim に収める。戻り値は (開始, 幅) の整数px。
+/// 可視区間そのものが max_dim を超える場合は中央の max_dim 窓に絞る（単一テクスチャでは
 /// 描ききれないため。この場合でも戻り値は決定的なので、ビューが動かない限り再描画されない）。
 fn crop_axis(n0: f32, n1: f32, full: f32, margin: f32, max_dim: f32) -> (u32, u32) {
     let n0 = n0.clamp(0.0, full);
@@ -1178,6 +2399,195 @@ fn load_embedded_fonts(svg_text: &str, db: &mut usvg::fontdb::Database, base_dir
     loaded
 }
 
+/// システムフォントを読み込んだフォントDB。
+/// SVG 内のテキストはパース時にフォントDBを使ってパス化されるため、空だと文字が一切描画されない。
+/// 構築コストが高いので最初に必要になったとき一度だけ作り、以降は Arc を共有して再利用する
+/// （サムネイル生成のワーカーからも使う）。
+fn system_fontdb() -> Arc<usvg::fontdb::Database> {
+    static FONTDB: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTDB
+        .get_or_init(|| {
+            let mut db = usvg::fontdb::Database::new();
+            db.load_system_fonts();
+            info!("システムフォントをロードしました: {} faces", db.len());
+            Arc::new(db)
+        })
+        .clone()
+}
+
+/// SVG / SVGZ ファイルを読み込んで解析する。戻り値の usize は展開後のソースのバイト数
+fn parse_svg(path: &Path) -> Result<(Tree, usize), String> {
+    let raw = archive::read(path)
+        .map_err(|e| format!("SVGファイルの読み込みに失敗しました: {} - {}", path.display(), e))?;
+    // .svgz（gzip 圧縮 SVG）対応
+    let raw = decompress_if_gzip(raw)?;
+    let svg_text = String::from_utf8_lossy(&raw);
+    info!("SVGファイルを読み込みました: {} bytes", svg_text.len());
+
+    // @font-face による埋め込み・参照フォントがあれば、システムフォントDBの
+    // コピーへ追加登録して使う（usvg 自身は @font-face を解釈しない）。
+    let fontdb = system_fontdb();
+    let fontdb = if svg_text.contains("@font-face") {
+        let mut db = (*fontdb).clone();
+        let n = load_embedded_fonts(&svg_text, &mut db, path.parent());
+        info!("@font-face から {} 個のフォントを読み込みました", n);
+        Arc::new(db)
+    } else {
+        fontdb
+    };
+
+    let mut opt = Options::default();
+    opt.fontdb = fontdb;
+    // SVG から相対参照される画像などの解決基準ディレクトリ
+    opt.resources_dir = path.parent().map(|p| p.to_path_buf());
+
+    let tree = Tree::from_str(&svg_text, &opt)
+        .map_err(|e| format!("SVGの解析に失敗しました: {} - {}", path.display(), e))?;
+    Ok((tree, raw.len()))
+}
+
+/// 比較表示の 1 枚を clip の範囲に描く（読み込み中はスピナー、失敗したらメッセージ）。
+/// 画像は view_rect の中央を基準に置く。描いた画像の矩形を返す
+fn draw_compare_image(
+    ui: &mut egui::Ui,
+    clip: Rect,
+    view_rect: Rect,
+    pan: Vec2,
+    image: Option<&mut LoadedImage>,
+    error: Option<&str>,
+    params: &DrawParams,
+) -> Option<Rect> {
+    let mut child = ui.new_child(egui::UiBuilder::new().max_rect(clip));
+    child.set_clip_rect(clip.intersect(ui.clip_rect()));
+    match (image, error) {
+        (Some(image), _) => {
+            let image_rect = centered_image_rect(view_rect, pan, image.base_size(), params);
+            image.draw(&mut child, image_rect, view_rect, params);
+            return Some(image_rect);
+        }
+        (None, Some(error)) => {
+            child.painter().text(
+                clip.center(),
+                egui::Align2::CENTER_CENTER,
+                error,
+                egui::FontId::proportional(14.0),
+                Color32::LIGHT_RED,
+            );
+        }
+        (None, None) => {
+            child.put(Rect::from_center_size(clip.center(), Vec2::splat(32.0)), egui::Spinner::new());
+        }
+    }
+    None
+}
+
+/// 回転前の大きさ size の画像を view_rect の中央からパンした位置に置いたときの表示矩形
+fn centered_image_rect(view_rect: Rect, pan: Vec2, size: Vec2, params: &DrawParams) -> Rect {
+    let (w, h) = rotated_dims(size.x, size.y, params.rotation);
+    let scaled_size = Vec2::new(w, h) * params.scale;
+    Rect::from_min_size(view_rect.min + (view_rect.size() - scaled_size) * 0.5 + pan, scaled_size)
+}
+
+/// 比較表示のファイル名などを半透明の黒地に重ねる
+fn draw_compare_label(painter: &egui::Painter, pos: Pos2, align: egui::Align2, text: String) {
+    let galley = painter.layout_no_wrap(text, egui::FontId::proportional(14.0), Color32::WHITE);
+    let rect = align.anchor_size(pos, galley.size()).expand(4.0);
+    painter.rect_filled(rect, 4.0, Color32::from_black_alpha(160));
+    painter.galley(rect.min + egui::vec2(4.0, 4.0), galley, Color32::WHITE);
+}
+
+/// ファイル名（アーカイブ内ならエントリ名）
+fn file_label(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// 見開き表示で表示中のページの隣に並べるページ
+struct SpreadPage {
+    path: PathBuf,
+    /// デコード済みのテクスチャ（デコード待ちの間は None で、表示中のページだけを描く）
+    texture: Option<egui::TextureHandle>,
+}
+
+/// 比較表示で表示中の画像の右に並べる画像
+struct ComparePane {
+    path: PathBuf,
+    image: Option<LoadedImage>,
+    /// デコードワーカー（Loader）の結果を待っている
+    loading: bool,
+    /// 読み込みに失敗したときのメッセージ（ペインに表示する）
+    error: Option<String>,
+}
+
+impl ComparePane {
+    /// 読み込み結果を入れる（画像ならテクスチャ化する）
+    fn install(
+        &mut self,
+        result: Result<loader::Prepared, String>,
+        ctx: &egui::Context,
+        options: egui::TextureOptions,
+        gpu_rendering: bool,
+    ) {
+        self.loading = false;
+        match result {
+            Ok(prepared) => {
+                self.image = Some(LoadedImage::from_prepared(ctx, &self.path, prepared, options, gpu_rendering));
+            }
+            Err(e) => {
+                error!("比較する画像を読み込めません: {} - {}", self.path.display(), e);
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+/// 比較表示の見せ方。重ねるモードでは表示中の画像（A）と先頭のペイン（B）を同じ位置に描く
+#[derive(Clone, Copy, PartialEq)]
+enum CompareMode {
+    /// 横に並べる
+    SideBySide,
+    /// A と B を B キーで切り替える
+    Flicker,
+    /// 境界線の左に A、右に B を描く（境界線はドラッグで動かせる）
+    Wipe,
+    /// ピクセルごとの差の絶対値を描く
+    Difference,
+}
+
+impl CompareMode {
+    const ALL: [CompareMode; 4] = [
+        CompareMode::SideBySide,
+        CompareMode::Flicker,
+        CompareMode::Wipe,
+        CompareMode::Difference,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            CompareMode::SideBySide => "Side by side",
+            CompareMode::Flicker => "Flicker A/B (B)",
+            CompareMode::Wipe => "Wipe",
+            CompareMode::Difference => "Difference",
+        }
+    }
+}
+
+/// 差分モードの計算結果
+type DifferenceKey = (PathBuf, usize, PathBuf, [Option<hdr::ToneParams>; 2]);
+
+struct DifferenceView {
+    /// 計算した組み合わせ（A のパスとページ、B のパス、HDR ならそれぞれのトーンマッピング）。
+    /// 変わったら計算し直す
+    key: DifferenceKey,
+    /// バックグラウンドでの計算結果の受信元（届いたら None）
+    pending: Option<mpsc::Receiver<Result<diff::Difference, String>>>,
+    result: Option<Result<diff::Difference, String>>,
+    texture: Option<egui::TextureHandle>,
+    /// texture の作成に使った増幅率
+    gain: f32,
+}
+
 struct ImageViewer {
     config: ViewerConfig,
     current_image: Option<LoadedImage>,
@@ -1187,18 +2597,73 @@ struct ImageViewer {
     pan_offset: Vec2,
     /// 90°単位の回転（0..=3、時計回り）。画像を読み込むたびに 0 へ戻る
     rotation: u8,
+    /// 左右・上下反転（H/V キー）。rotation と同じく画像を読み込むたびに解除される
+    flip: Flip,
+    /// 複数ページを持つファイル（マルチページ TIFF・ICO/CUR の解像度・WIC フレーム）の表示中ページ（0 始まり）
+    page: usize,
+    /// 表示中ファイルのページ数とページ名（ファイルを開いたときに調べる）
+    page_info: pages::PageInfo,
+    /// 表示中の EXR の全レイヤー（レイヤー・チャンネル選択用。EXR 以外では None）
+    exr: Option<Arc<exr_layers::ExrFile>>,
+    /// 表示中の EXR のレイヤー・チャンネル（EXR メニューで選ぶ）
+    exr_selection: exr_layers::Selection,
+    /// 表示中の画像のカラーマネジメント状態（埋め込みプロファイルの有無・変換結果）
+    icc_state: color::IccState,
+    /// 色変換の出力先プロファイル（設定 output_icc_profile、既定は sRGB）
+    output_profile: Arc<moxcms::ColorProfile>,
+    /// ラスタ画像のバックグラウンドデコードと前後の画像の先読み
+    loader: loader::Loader,
+    /// デコード待ちの画像が届いたとき、表示位置・拡大率・回転・反転を保つか（設定変更による読み直し）
+    keep_view_on_load: bool,
+    /// フィルムストリップ・グリッド表示のサムネイル（バックグラウンドで生成）
+    thumbnails: thumbs::Thumbnails,
+    /// フォルダ内のサムネイルをウィンドウ全体に並べて表示中か（G キー）
+    grid_view: bool,
+    /// フィルムストリップ・グリッドを最後にスクロールして合わせた画像（表示中の画像が変わったら合わせ直す）
+    filmstrip_synced: Option<PathBuf>,
+    grid_synced: Option<PathBuf>,
+    /// image_paths を集めたフォルダ（サブフォルダを辿る設定ではその起点）
+    folder_root: Option<PathBuf>,
+    /// image_paths がフォルダの一覧ではなく、明示されたプレイリスト（複数ファイル・リスト・標準入力）か
+    is_playlist: bool,
+    /// folder_root と表示中のファイルの変更監視
+    watcher: watcher::Watcher,
+    slideshow: slideshow::Slideshow,
+    /// スライドショーを始めるときに全画面にしたか（止めたときに元へ戻す）
+    slideshow_entered_fullscreen: bool,
+    /// スライドショーのクロスフェードで上に重ねて消していく直前の画像
+    fade: Option<Fade>,
+    /// 最後に画像を描いた矩形（クロスフェードで直前の画像を同じ位置に描くため）
+    last_image_rect: Option<Rect>,
+    /// 見開き表示で並べているページ（表示中のページが見開きの先頭になる）
+    spread: Option<SpreadPage>,
+    /// 比較表示で並べている画像（空なら通常表示）。拡大率・パン位置・回転・反転は全ペインで共通
+    compare: Vec<ComparePane>,
+    compare_mode: CompareMode,
+    /// フリッカー表示で B を出しているか
+    show_b: bool,
+    /// ワイプの境界線の位置（パネルの幅に対する割合）
+    wipe_split: f32,
+    /// ワイプの境界線をドラッグ中か（そのドラッグではパンしない）
+    wipe_dragging: bool,
+    difference: Option<DifferenceView>,
+    /// ピクセルインスペクターで K を押して残した値（履歴パネルに表示）
+    pixel_history: Vec<String>,
+    histogram: Option<HistogramView>,
+    /// 表示中の画像を差し替えるたびに増やす（ヒストグラムの再計算の判定用）
+    image_generation: u64,
     image_paths: Vec<PathBuf>,
     // 前回の利用可能なウィンドウサイズ（"fit" モードで使用）
     last_available_size: Option<Vec2>,
     mouse_gesture: MouseGesture,
-    // SVG テキスト描画用のフォントDB（初回SVG読み込み時にシステムフォントを一度だけロードしてキャッシュ）
-    fontdb: Option<Arc<usvg::fontdb::Database>>,
     // コマンドライン等で指定された初期画像。最初の update() フレームで読み込む（new() 内で
     // 読み込むと、GL バックエンドが最大テクスチャサイズを報告する前なので、大きな画像で
     // 「maximum texture side is 2048」パニックが起きる）。
-    pending_open: Option<PathBuf>,
+    pending_open: Option<playlist::Open>,
     // 直近に設定したウィンドウタイトル（毎フレームの Title コマンド送信を避ける）
     last_title: String,
+    // タイトルに出すパスの表示（アーカイブ内かの判定でディスクを見るので、パスが変わった時だけ作る）
+    title_path: Option<(PathBuf, String)>,
     // 自動更新の状態（バックグラウンドスレッドと共有）
     update_status: updater::SharedStatus,
     // 起動時の自分自身のパス。exe 差し替え後の再起動に使う
@@ -1209,7 +2674,18 @@ struct ImageViewer {
 }
 
 impl ImageViewer {
-    fn new(cc: &eframe::CreationContext<'_>, initial_image: Option<PathBuf>, config: ViewerConfig) -> Self {
+    fn new(cc: &eframe::CreationContext<'_>, initial: Option<playlist::Open>, config: ViewerConfig) -> Self {
+        // 出力プロファイルが読めない場合は sRGB で続行する
+        let output_profile = color::output_profile(&config.output_icc_profile).unwrap_or_else(|e| {
+            error!("{}。sRGB を使用します", e);
+            moxcms::ColorProfile::new_srgb()
+        });
+        let loader = loader::Loader::new(&cc.egui_ctx, config.cache_budget_mb * 1024 * 1024);
+        let thumbnail_cache = if config.thumbnail_cache {
+            thumb_cache::DiskCache::new(config.thumbnail_cache_mb * 1024 * 1024)
+        } else {
+            None
+        };
         let mut viewer = Self {
             config,
             current_image: None,
@@ -1218,24 +2694,53 @@ impl ImageViewer {
             scale: 1.0,
             pan_offset: Vec2::ZERO,
             rotation: 0,
+            flip: Flip::default(),
+            page: 0,
+            page_info: pages::PageInfo::single(),
+            exr: None,
+            exr_selection: exr_layers::Selection::default(),
+            icc_state: color::IccState::None,
+            output_profile: Arc::new(output_profile),
+            loader,
+            keep_view_on_load: false,
+            thumbnails: thumbs::Thumbnails::new(&cc.egui_ctx, thumbnail_cache),
+            grid_view: false,
+            filmstrip_synced: None,
+            grid_synced: None,
+            folder_root: None,
+            is_playlist: false,
+            watcher: watcher::Watcher::new(&cc.egui_ctx),
+            slideshow: slideshow::Slideshow::new(),
+            slideshow_entered_fullscreen: false,
+            fade: None,
+            last_image_rect: None,
+            spread: None,
+            compare: Vec::new(),
+            compare_mode: CompareMode::SideBySide,
+            show_b: false,
+            wipe_split: 0.5,
+            wipe_dragging: false,
+            difference: None,
+            pixel_history: Vec::new(),
+            histogram: None,
+            image_generation: 0,
             image_paths: Vec::new(),
             last_available_size: None,
             mouse_gesture: MouseGesture::new(),
-            fontdb: None,
             pending_open: None,
             last_title: String::new(),
+            title_path: None,
             update_status: updater::new_shared_status(),
             exe_path: std::env::current_exe().ok(),
             restart_prompted: false,
         };
 
         // 初期画像は new() 内ではなく最初の update() フレームで読み込む（理由は pending_open の定義参照）。
-        if let Some(path) = initial_image {
-            if path.exists() {
-                viewer.pending_open = Some(path);
-            } else {
+        match initial {
+            Some(playlist::Open::File(path)) if !path.exists() => {
                 error!("指定された画像が見つかりません: {}", path.display());
             }
+            open => viewer.pending_open = open,
         }
 
         // 起動時の更新確認（バックグラウンド。失敗しても Help メニューに出るだけでアプリは動く）
@@ -1294,276 +2799,1231 @@ impl ImageViewer {
         }
     }
 
-    /// 回転を考慮した、拡大率 1.0 のときの表示サイズ（論理ポイント）
+    /// 回転を考慮した、拡大率 1.0 のときの表示サイズ（論理ポイント）。見開き表示では 2 ページ分
     fn display_base_size(&self) -> Vec2 {
-        let (w, h) = match &self.current_image {
-            Some(LoadedImage::Raster { texture, .. }) => {
-                let s = texture.size_vec2();
-                (s.x, s.y)
-            }
-            Some(LoadedImage::Svg { size, .. }) => (size[0], size[1]),
-            None => (0.0, 0.0),
-        };
-        let (w, h) = rotated_dims(w, h, self.rotation);
+        let page = self.page_base_size();
+        let size = self.spread_layout(page).map_or(page, |(unit, _, _)| unit);
+        let (w, h) = rotated_dims(size.x, size.y, self.rotation);
         Vec2::new(w, h)
     }
 
-    /// 画像（回転考慮）が利用可能領域全体に収まる scale を計算する
-    fn fit_to_screen(&mut self, avail: Vec2) {
-        let base = self.display_base_size();
-        if base.x > 0.0 && base.y > 0.0 && avail.x > 0.0 && avail.y > 0.0 {
-            self.scale = (avail.x / base.x).min(avail.y / base.y);
-            info!("画面に合わせてスケールを設定: {}", self.scale);
-        }
+    fn rotated_base_size(&self, image: &LoadedImage) -> Vec2 {
+        let size = image.base_size();
+        let (w, h) = rotated_dims(size.x, size.y, self.rotation);
+        Vec2::new(w, h)
     }
 
-    /// anchor（スクリーン座標）の位置にある画像上の点を固定したままズームする
-    fn zoom_at(&mut self, anchor: Vec2, panel_rect: &Rect, base_size: Vec2, new_scale: f32) {
-        let old_scale = self.scale;
-        if old_scale <= 0.0 || (new_scale - old_scale).abs() < f32::EPSILON {
-            return;
+    /// 表示中のページの回転前の大きさ（拡大率 1.0）
+    fn page_base_size(&self) -> Vec2 {
+        self.current_image.as_ref().map_or(Vec2::ZERO, LoadedImage::base_size)
+    }
+
+    /// 見開き表示で並べるページが表示できるとき、回転前の配置を返す。
+    /// 並べるページは表示中のページ（大きさ page）と同じ高さに揃える。
+    /// 戻り値は (全体の大きさ, 表示中のページの矩形, 並べるページの矩形)。矩形は [x, y, 幅, 高さ]
+    fn spread_layout(&self, page: Vec2) -> Option<(Vec2, [f32; 4], [f32; 4])> {
+        let other = self.spread.as_ref()?.texture.as_ref()?.size_vec2();
+        if page.y <= 0.0 || other.y <= 0.0 {
+            return None;
         }
-        let old_size = base_size * old_scale;
-        let new_size = base_size * new_scale;
-        let old_origin = panel_rect.min.to_vec2() + (panel_rect.size() - old_size) * 0.5 + self.pan_offset;
-        let new_origin = anchor - (anchor - old_origin) * (new_scale / old_scale);
-        self.scale = new_scale;
-        self.pan_offset =
-            new_origin - panel_rect.min.to_vec2() - (panel_rect.size() - new_size) * 0.5;
+        let other_w = other.x * page.y / other.y;
+        let unit = Vec2::new(page.x + other_w, page.y);
+        Some(if self.config.spread_right_to_left {
+            (unit, [other_w, 0.0, page.x, page.y], [0.0, 0.0, other_w, page.y])
+        } else {
+            (unit, [0.0, 0.0, page.x, page.y], [page.x, 0.0, other_w, page.y])
+        })
     }
 
-    /// 指定パスの画像を読み込み、拡大率、パン位置、画像サイズを更新する
-    fn load_image(&mut self, path: &Path, ctx: &egui::Context) -> bool {
-        info!("画像を読み込もうとしています: {:?}", path);
-        self.pan_offset = Vec2::ZERO;
-        self.scale = 1.0;
-        self.rotation = 0;
-        self.image_size = None;
+    fn spread_settings(&self) -> spread::Settings {
+        spread::Settings {
+            cover_single: self.config.spread_cover_single,
+            landscape_single: self.config.spread_landscape_single,
+        }
+    }
 
-        let ext = path
-            .extension()
-            .map(|e| e.to_string_lossy().to_lowercase())
-            .unwrap_or_default();
-        let result = if ext == "svg" || ext == "svgz" {
-            self.load_svg(path, ctx)
+    /// 一覧の index 番目が横長のページと分かっているか（表示中のページか、デコード済みのもの）
+    fn is_wide_page(&self, index: usize) -> bool {
+        let Some(path) = self.image_paths.get(index) else {
+            return false;
+        };
+        let size = if Some(path) == self.current_path.as_ref() && self.image_size.is_some() {
+            self.image_size
         } else {
-            // 拡張子が不明でも WIC フォールバック込みでラスタとして試す
-            self.load_raster(path, ctx)
+            self.loader.peek(path, 0).and_then(|d| d.prepared.size())
         };
+        size.is_some_and(|[w, h]| w > h)
+    }
 
-        if result {
-            self.current_path = Some(path.to_path_buf());
-            // フィット表示は次フレームの update() に委ねる。
-            // ここで（＝ImageViewer::new() からの初回読み込み時に）fit_to_screen を呼ぶと
-            // Context::run() 前に ctx.available_rect() を呼ぶことになり、egui 0.31 が
-            // 「Called `available_rect()` before `Context::run()`」でパニックする。
-            // last_available_size を None に戻すと、次フレームで新しい画像に対して再フィットされる。
-            self.last_available_size = None;
+    /// 見開き表示で表示中のページの隣に並べるページ（1 ページで表示するときは None）
+    fn spread_partner(&self) -> Option<PathBuf> {
+        if !self.config.spread_view || self.page_info.count > 1 || !self.compare.is_empty() {
+            return None;
         }
-
-        result
+        // SVG・タイル分割した画像は 1 ページで表示する
+        if !matches!(
+            self.current_image,
+            Some(LoadedImage::Raster { .. } | LoadedImage::Animated { .. } | LoadedImage::Hdr { .. })
+        ) {
+            return None;
+        }
+        let index = self.current_index()?;
+        let count = self.image_paths.len();
+        spread::pairs(index, count, self.spread_settings(), |i| self.is_wide_page(i))
+            .then(|| self.image_paths[index + 1].clone())
+            // SVG と、1 枚のテクスチャにできない（タイル分割する大きさの）ページは並べない
+            .filter(|path| {
+                !is_svg_path(path)
+                    && self
+                        .loader
+                        .peek(path, 0)
+                        .is_none_or(|d| d.prepared.still().is_some())
+            })
     }
 
-    fn load_svg(&mut self, path: &Path, ctx: &egui::Context) -> bool {
-        match self.try_load_svg(path, ctx) {
-            Ok(()) => true,
-            Err(message) => {
-                error!("{}", message);
-                rfd::MessageDialog::new()
-                    .set_title("エラー")
-                    .set_description(&message)
-                    .show();
-                false
+    /// 見開き表示で並べるページを表示中のページに合わせる。デコード済みになったらテクスチャにする
+    fn update_spread(&mut self, ctx: &egui::Context) {
+        // 表示中のページのデコード待ちの間は、前のページと並べたままにする
+        if self.loader.is_loading() {
+            return;
+        }
+        let partner = self.spread_partner();
+        if self.spread.as_ref().map(|s| &s.path) != partner.as_ref() {
+            if let Some(path) = &partner {
+                let settings = self.decode_settings(ctx);
+                self.loader.prefetch(&[(path.clone(), 0)], &settings);
             }
+            self.spread = partner.map(|path| SpreadPage { path, texture: None });
+            // 見開きの幅が変わるのでフィットし直す
+            self.last_available_size = None;
+        }
+        let Some(page) = &self.spread else { return };
+        if page.texture.is_some() {
+            return;
+        }
+        let Some(image) = self.loader.peek(&page.path, 0).and_then(|d| d.prepared.still()) else {
+            return;
+        };
+        let name = format!("{}#spread", page.path.to_string_lossy());
+        let texture = ctx.load_texture(name, image, self.raster_texture_options());
+        if let Some(page) = &mut self.spread {
+            page.texture = Some(texture);
         }
+        self.last_available_size = None;
     }
 
-    fn try_load_svg(&mut self, path: &Path, ctx: &egui::Context) -> Result<(), String> {
-        let raw = fs::read(path)
-            .map_err(|e| format!("SVGファイルの読み込みに失敗しました: {} - {}", path.display(), e))?;
-        // .svgz（gzip 圧縮 SVG）対応
-        let raw = decompress_if_gzip(raw)?;
-        let svg_text = String::from_utf8_lossy(&raw);
-        info!("SVGファイルを読み込みました: {} bytes", svg_text.len());
-
-        // SVG 内のテキストはパース時にフォントDBを使ってパス化される。
-        // フォントDBが空だと文字が一切描画されないため、システムフォントをロードしておく。
-        // 構築コストが高いので初回のみ作成し、以降は Arc を共有して再利用する。
-        let fontdb = self
-            .fontdb
-            .get_or_insert_with(|| {
-                let mut db = usvg::fontdb::Database::new();
-                db.load_system_fonts();
-                info!("システムフォントをロードしました: {} faces", db.len());
-                Arc::new(db)
-            })
-            .clone();
-
-        // @font-face による埋め込み・参照フォントがあれば、システムフォントDBの
-        // コピーへ追加登録して使う（usvg 自身は @font-face を解釈しない）。
-        let fontdb = if svg_text.contains("@font-face") {
-            let mut db = (*fontdb).clone();
-            let n = load_embedded_fonts(&svg_text, &mut db, path.parent());
-            info!("@font-face から {} 個のフォントを読み込みました", n);
-            Arc::new(db)
+    /// 比較表示のペインを追加し、画像をバックグラウンドで読み込む（先読みキャッシュは使わない）
+    fn add_compare_pane(&mut self, path: PathBuf, ctx: &egui::Context) {
+        if self.current_image.is_none() || 1 + self.compare.len() >= MAX_COMPARE_PANES {
+            info!("比較表示に追加できません（最大 {} ペイン）: {:?}", MAX_COMPARE_PANES, path);
+            return;
+        }
+        info!("比較表示に追加します: {:?}", path);
+        // 表示中の画像と同じく、SVG は UI スレッドで解析し、ラスタ画像はデコードワーカーに任せる
+        // （どちらも先読みキャッシュを共有する）
+        let prepared = if is_svg_path(&path) {
+            Some(self.svg_prepared(&path))
         } else {
-            fontdb
+            let settings = self.decode_settings(ctx);
+            self.loader.request_pane(&path, 0, &settings).map(|decoded| Ok(decoded.prepared))
+        };
+        let mut pane = ComparePane {
+            path,
+            image: None,
+            loading: prepared.is_none(),
+            error: None,
         };
+        if let Some(result) = prepared {
+            pane.install(result, ctx, self.raster_texture_options(), self.config.gpu_rendering);
+        }
+        self.compare.push(pane);
+        self.last_available_size = None;
+    }
 
-        let mut opt = Options::default();
-        opt.fontdb = fontdb;
-        // SVG から相対参照される画像などの解決基準ディレクトリ
-        opt.resources_dir = path.parent().map(|p| p.to_path_buf());
-
-        let tree = Tree::from_str(&svg_text, &opt)
-            .map_err(|e| format!("SVGの解析に失敗しました: {} - {}", path.display(), e))?;
-        let size = tree.size();
-        let (w, h) = (size.width(), size.height());
-        info!("SVGサイズ: {}x{}", w, h);
-        self.image_size = Some([w.ceil() as u32, h.ceil() as u32]);
-        // Tree はワーカースレッドへ移動し、以後のラスタライズはすべてそちらで行う。
-        // 前の画像のワーカーは、旧 LoadedImage が破棄されて送信側が閉じると自動終了する。
-        let (job_tx, result_rx) =
-            spawn_svg_render_worker(tree, self.config.gpu_rendering, ctx.clone());
-        self.current_image = Some(LoadedImage::Svg {
-            size: [w, h],
-            texture: None,
-            view: None,
-            job_tx,
-            result_rx,
-            last_requested: None,
-            path: path.to_path_buf(),
-        });
-        info!("SVGの読み込みが完了しました");
-        Ok(())
+    fn close_compare(&mut self) {
+        if !self.compare.is_empty() {
+            info!("比較表示を終了します");
+            self.compare.clear();
+            self.loader.cancel_panes();
+            self.last_available_size = None;
+        }
     }
 
-    fn load_raster(&mut self, path: &Path, ctx: &egui::Context) -> bool {
-        // まず image クレートで読む（png/jpg/gif/webp/bmp/tiff/ico/tga/dds/exr/hdr/qoi/pnm 等を網羅）。
-        // image が非対応の形式（HEIC/HEIF/AVIF/JPEG XR/カメラRAW 等）は、Windows の WIC
-        // （OS が持つ画像コーデック＋ストアの拡張機能）にフォールバックして可能な限り開く。
-        let ext = path
-            .extension()
-            .map(|e| e.to_string_lossy().to_lowercase())
-            .unwrap_or_default();
-        let decoded: Result<image::DynamicImage, String> = if ext == "heic" || ext == "heif" {
-            decode_via_wic(path) // image は HEIC 非対応なので最初から WIC
-        } else {
-            match image::open(path) {
-                Ok(img) => Ok(img),
-                Err(e) => {
-                    info!("image で読めず WIC にフォールバック: {} ({})", path.display(), e);
-                    decode_via_wic(path).map_err(|werr| format!("{e} / WIC: {werr}"))
-                }
+    /// ファイルダイアログで選んだ画像を比較表示に追加する
+    fn compare_file_dialog(&mut self, ctx: &egui::Context) {
+        if let Some(paths) = rfd::FileDialog::new()
+            .add_filter("Images", SUPPORTED_EXTS)
+            .add_filter("All Files", &["*"])
+            .pick_files()
+        {
+            for path in paths {
+                self.add_compare_pane(path, ctx);
+            }
+        }
+    }
+
+    /// デコードが終わったペインの画像をテクスチャ化する
+    fn update_compare(&mut self, ctx: &egui::Context) {
+        let options = self.raster_texture_options();
+        let mut arrived = false;
+        for ((path, _), result) in self.loader.poll_panes() {
+            for pane in self.compare.iter_mut().filter(|pane| pane.loading && pane.path == path) {
+                let result = result.clone().map(|decoded| decoded.prepared);
+                pane.install(result, ctx, options, self.config.gpu_rendering);
+                arrived = true;
             }
+        }
+        if arrived {
+            // 大きさの違う画像が来たらフィットし直す
+            self.last_available_size = None;
+        }
+    }
+
+    /// 比較表示の各ペインの矩形（表示中の画像が左端。通常表示・重ねるモードならパネル全体の 1 つ）
+    fn pane_rects(&self, panel_rect: Rect) -> Vec<Rect> {
+        pane_rects(panel_rect, self.pane_count())
+    }
+
+    /// pos を含むペインの矩形と、そこに表示している画像の回転後の大きさ（ズームの基準）。
+    /// どのペインにも入らなければ表示中の画像のペイン
+    fn pane_at(&self, panel_rect: Rect, pos: Pos2) -> (Rect, Vec2) {
+        let rects = self.pane_rects(panel_rect);
+        let index = pane_index(&rects, pos);
+        let base = match index.checked_sub(1) {
+            Some(i) => self.compare[i]
+                .image
+                .as_ref()
+                .map_or(Vec2::ZERO, |image| self.rotated_base_size(image)),
+            None => self.display_base_size(),
         };
-        match decoded {
-            Ok(mut image) => {
-                // GPU の最大テクスチャ辺を超える画像はそのまま load_texture するとパニックするため、
-                // アスペクト比を保ったまま収まるよう縮小する（通常サイズの画像には影響しない）。
-                let max = ctx.input(|i| i.max_texture_side).max(1) as u32;
-                if image.width() > max || image.height() > max {
-                    info!(
-                        "画像がテクスチャ上限({0})を超過: {1}x{2} → 縮小",
-                        max,
-                        image.width(),
-                        image.height()
-                    );
-                    image = image.resize(max, max, image::imageops::FilterType::Triangle);
+        (rects[index], base)
+    }
+
+    /// 比較表示: 各ペインに同じ拡大率・パン位置・回転・反転で描き、ファイル名を重ねる
+    fn draw_compare(&mut self, ui: &mut egui::Ui, panel_rect: Rect, params: &DrawParams) {
+        let rects = self.pane_rects(panel_rect);
+        let pan = self.pan_offset;
+        for (i, rect) in rects.into_iter().enumerate() {
+            let (image, path, error) = match i.checked_sub(1) {
+                None => (self.current_image.as_mut(), self.current_path.as_deref(), None),
+                Some(k) => {
+                    let pane = &mut self.compare[k];
+                    (pane.image.as_mut(), Some(pane.path.as_path()), pane.error.as_deref())
                 }
-                let image = image.to_rgba8();
-                let width = image.width() as usize;
-                let height = image.height() as usize;
-                self.image_size = Some([width as u32, height as u32]);
-                // フィットは load_image 側で last_available_size=None を介して次フレームに委ねる
-                // （ここで fit_to_screen を呼ぶと初回フレーム前に available_rect() を触り得る）
-                let size = [width, height];
-                let pixels = image.into_vec();
-                let color_image = egui::ColorImage::from_rgba_unmultiplied(size, &pixels);
-                // 拡大時の補間は設定で切り替え（滑らか／ピクセル等倍）。縮小は常にバイリニア。
-                let magnification = if self.config.smooth_zoom {
-                    egui::TextureFilter::Linear
-                } else {
-                    egui::TextureFilter::Nearest
-                };
-                let texture = ctx.load_texture(
-                    path.to_string_lossy().to_string(),
-                    color_image,
-                    egui::TextureOptions {
-                        magnification,
-                        minification: egui::TextureFilter::Linear,
-                        ..Default::default()
-                    },
+            };
+            let image_rect = draw_compare_image(ui, rect, rect, pan, image, error, params);
+            // ファイル名（アーカイブ内ならエントリ名）を左上に出す
+            if let Some(path) = path {
+                draw_compare_label(
+                    &ui.painter_at(rect),
+                    rect.left_top() + egui::vec2(6.0, 6.0),
+                    egui::Align2::LEFT_TOP,
+                    file_label(path),
                 );
-                self.current_image = Some(LoadedImage::Raster {
-                    texture,
-                    path: path.to_path_buf(),
-                });
-                info!("画像の読み込みが完了しました");
-                true
             }
-            Err(e) => {
-                let message = format!("画像の読み込みに失敗しました: {} - {}", path.display(), e);
-                error!("{}", message);
-                rfd::MessageDialog::new()
-                    .set_title("エラー")
-                    .set_description(&message)
-                    .show();
-                false
+            if i == 0 {
+                self.last_image_rect = image_rect;
+            } else {
+                ui.painter().vline(
+                    rect.left(),
+                    rect.y_range(),
+                    egui::Stroke::new(2.0, Color32::from_gray(16)),
+                );
             }
         }
     }
 
-    /// 現在の画像があるディレクトリ内の画像一覧を更新する
-    fn update_image_list(&mut self, current_path: &Path) {
-        if let Some(parent) = current_path.parent() {
-            info!("ディレクトリを読み込もうとしています: {:?}", parent);
-            match fs::read_dir(parent) {
-                Ok(entries) => {
-                    let mut files: Vec<_> = entries
-                        .filter_map(|entry| {
-                            match entry {
-                                Ok(entry) => {
-                                    let path = entry.path();
-                                    if path.extension().map_or(false, |ext| {
-                                        let ext = ext.to_string_lossy().to_lowercase();
-                                        SUPPORTED_EXTS.contains(&ext.as_str())
-                                    }) {
-                                        Some(path)
-                                    } else {
-                                        None
-                                    }
-                                }
-                                Err(e) => {
-                                    error!("ディレクトリエントリの読み込みに失敗: {}", e);
-                                    None
-                                }
-                            }
-                        })
-                        .collect();
-                    // エクスプローラーと同じ感覚で並ぶよう自然順（img2 < img10）でソート
-                    files.sort_by(|a, b| {
-                        let an = a.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
-                        let bn = b.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
-                        natural_cmp(&an, &bn).then_with(|| a.cmp(b))
-                    });
-                    self.image_paths = files;
-                    info!("ディレクトリの読み込みに成功しました: {:?}", parent);
-                }
-                Err(e) => {
-                    error!("ディレクトリの読み込みに失敗しました: {:?} - エラー: {}", parent, e);
+    /// 比較表示の重ねるモード: 表示中の画像（A）と先頭のペイン（B）を同じ位置に描く
+    fn draw_overlay(&mut self, ui: &mut egui::Ui, panel_rect: Rect, params: &DrawParams) {
+        let pan = self.pan_offset;
+        let split_x = self.wipe_split_x(panel_rect);
+        let a_name = self.current_path.as_deref().map(file_label).unwrap_or_default();
+        let a = self.current_image.as_mut();
+        let b = &mut self.compare[0];
+        let b_name = file_label(&b.path);
+        let label_pos = panel_rect.left_top() + egui::vec2(6.0, 6.0);
+        match self.compare_mode {
+            CompareMode::Wipe => {
+                let (left, right) = panel_rect.split_left_right_at_x(split_x);
+                self.last_image_rect = draw_compare_image(ui, left, panel_rect, pan, a, None, params);
+                draw_compare_image(ui, right, panel_rect, pan, b.image.as_mut(), b.error.as_deref(), params);
+                let painter = ui.painter();
+                let stroke = egui::Stroke::new(2.0, Color32::WHITE);
+                painter.vline(split_x, panel_rect.y_range(), stroke);
+                painter.circle(egui::pos2(split_x, panel_rect.center().y), 8.0, Color32::from_gray(32), stroke);
+                draw_compare_label(&ui.painter_at(left), label_pos, egui::Align2::LEFT_TOP, format!("A: {}", a_name));
+                draw_compare_label(
+                    &ui.painter_at(right),
+                    panel_rect.right_top() + egui::vec2(-6.0, 6.0),
+                    egui::Align2::RIGHT_TOP,
+                    format!("B: {}", b_name),
+                );
+            }
+            CompareMode::Difference => {
+                let size = a.map_or(Vec2::ZERO, |image| image.base_size());
+                let image_rect = centered_image_rect(panel_rect, pan, size, params);
+                self.last_image_rect = Some(image_rect);
+                let painter = ui.painter();
+                let summary = match &self.difference {
+                    Some(DifferenceView {
+                        texture: Some(texture),
+                        result: Some(Ok(difference)),
+                        ..
+                    }) => {
+                        draw_texture_rotated(painter, texture, image_rect, params.rotation, params.flip);
+                        format!(
+                            "Changed: {} / {} px ({:.3}%)   max diff: {}   gain: x{:.1}",
+                            difference.changed,
+                            difference.total(),
+                            difference.changed as f64 * 100.0 / difference.total().max(1) as f64,
+                            difference.max,
+                            self.config.difference_gain
+                        )
+                    }
+                    Some(DifferenceView {
+                        result: Some(Err(e)), ..
+                    }) => {
+                        painter.text(
+                            panel_rect.center(),
+                            egui::Align2::CENTER_CENTER,
+                            e,
+                            egui::FontId::proportional(14.0),
+                            Color32::LIGHT_RED,
+                        );
+                        String::new()
+                    }
+                    _ => {
+                        ui.put(Rect::from_center_size(panel_rect.center(), Vec2::splat(32.0)), egui::Spinner::new());
+                        String::new()
+                    }
+                };
+                let painter = ui.painter();
+                draw_compare_label(painter, label_pos, egui::Align2::LEFT_TOP, format!("|A - B|: {} / {}", a_name, b_name));
+                if !summary.is_empty() {
+                    draw_compare_label(
+                        painter,
+                        panel_rect.center_bottom() + egui::vec2(0.0, -12.0),
+                        egui::Align2::CENTER_BOTTOM,
+                        summary,
+                    );
                 }
             }
+            CompareMode::SideBySide | CompareMode::Flicker => {
+                let label = if self.show_b {
+                    draw_compare_image(ui, panel_rect, panel_rect, pan, b.image.as_mut(), b.error.as_deref(), params);
+                    format!("B: {}", b_name)
+                } else {
+                    self.last_image_rect = draw_compare_image(ui, panel_rect, panel_rect, pan, a, None, params);
+                    format!("A: {}", a_name)
+                };
+                draw_compare_label(ui.painter(), label_pos, egui::Align2::LEFT_TOP, label);
+            }
         }
     }
 
-    /// 前後の画像へ切り替え
-    fn load_adjacent_image(&mut self, ctx: &egui::Context, next: bool) {
-        if let Some(current_path) = &self.current_path {
+    /// ワイプの境界線の x 座標（スクリーン座標）
+    fn wipe_split_x(&self, panel_rect: Rect) -> f32 {
+        panel_rect.left() + panel_rect.width() * self.wipe_split
+    }
+
+    /// 表示中の画像が見えている画面上の範囲（比較表示では A のペイン・ワイプの左側など。差分・B の表示中は None）
+    fn current_image_clip(&self, panel_rect: Rect) -> Option<Rect> {
+        match self.compare_mode {
+            _ if self.compare.is_empty() => Some(panel_rect),
+            CompareMode::SideBySide => Some(self.pane_rects(panel_rect)[0]),
+            CompareMode::Flicker if !self.show_b => Some(panel_rect),
+            CompareMode::Wipe => Some(panel_rect.split_left_right_at_x(self.wipe_split_x(panel_rect)).0),
+            CompareMode::Flicker | CompareMode::Difference => None,
+        }
+    }
+
+    /// 画面上の点 pos にある、表示中の画像の画素を調べる
+    fn inspect(&self, panel_rect: Rect, pos: Pos2) -> Option<PixelSample> {
+        if !self.current_image_clip(panel_rect).is_some_and(|clip| clip.contains(pos)) {
+            return None;
+        }
+        let image = self.current_image.as_ref()?;
+        let image_rect = self.last_image_rect?;
+        image.sample(screen_to_image(pos, image_rect, image.base_size(), self.rotation, self.flip))
+    }
+
+    /// ピクセルインスペクター: cursor の下の画素の座標と色を OSD に出し、K で履歴パネルへ残す
+    fn show_pixel_inspector(&mut self, ui: &egui::Ui, panel_rect: Rect, cursor: Pos2) {
+        let Some(sample) = self.inspect(panel_rect, cursor) else {
+            return;
+        };
+        // 1 画素が十分大きく表示されているときは、調べている画素を枠で囲む
+        if let (false, Some(image), Some(image_rect)) =
+            (sample.vector, &self.current_image, self.last_image_rect)
+        {
+            if self.scale >= 4.0 {
+                let cell = image_rect_to_screen(
+                    [sample.pos.x, sample.pos.y, 1.0, 1.0],
+                    image_rect,
+                    image.base_size(),
+                    self.rotation,
+                    self.flip,
+                );
+                ui.painter().rect_stroke(
+                    cell,
+                    0.0,
+                    egui::Stroke::new(1.0, Color32::WHITE),
+                    egui::StrokeKind::Outside,
+                );
+            }
+        }
+        draw_pixel_inspector(ui.painter(), panel_rect, cursor, &sample);
+        if ui.input(|i| i.key_pressed(Key::K)) {
+            let name = self.current_path.as_deref().map(file_label).unwrap_or_default();
+            let text = sample.to_text(&name);
+            info!("ピクセルの値を履歴に残します: {}", text);
+            self.pixel_history.push(text);
+            if self.pixel_history.len() > PIXEL_HISTORY_LIMIT {
+                self.pixel_history.remove(0);
+            }
+        }
+    }
+
+    /// 拡大時の画素の境界と、タイル単位の格子を表示中の画像に重ねる。
+    /// 画像空間の線を回転・反転・パンに合わせて写像するので、常に画素の境目に揃う
+    fn draw_grids(&self, ui: &egui::Ui, panel_rect: Rect) {
+        let tile = Vec2::new(
+            self.config.tile_grid_width.max(1) as f32,
+            self.config.tile_grid_height.max(1) as f32,
+        );
+        let tile_grid = self.config.tile_grid && tile.min_elem() * self.scale >= MIN_GRID_SPACING;
+        let pixel_grid = self.config.pixel_grid && self.scale >= self.config.pixel_grid_threshold;
+        if !tile_grid && !pixel_grid {
+            return;
+        }
+        let (Some(image), Some(image_rect), Some(clip)) = (
+            &self.current_image,
+            self.last_image_rect,
+            self.current_image_clip(panel_rect),
+        ) else {
+            return;
+        };
+        let clip = clip.intersect(image_rect);
+        if !clip.is_positive() {
+            return;
+        }
+        let size = image.base_size();
+        let (rotation, flip) = (self.rotation, self.flip);
+        // 見えている範囲（画像空間）。90° 単位の回転なので対角の 2 点で決まる
+        let visible = Rect::from_two_pos(
+            screen_to_image(clip.left_top(), image_rect, size, rotation, flip),
+            screen_to_image(clip.right_bottom(), image_rect, size, rotation, flip),
+        )
+        .intersect(Rect::from_min_size(Pos2::ZERO, size));
+        let to_screen =
+            |p: Pos2| image_rect_to_screen([p.x, p.y, 0.0, 0.0], image_rect, size, rotation, flip).min;
+        let painter = ui.painter_at(clip);
+        // SVG には画素がないので、画素の格子は出さない
+        if pixel_grid && !matches!(image, LoadedImage::Svg { .. }) {
+            let stroke = egui::Stroke::new(1.0, grid_color(image, visible));
+            draw_image_grid(&painter, visible, Vec2::splat(1.0), stroke, to_screen);
+        }
+        if tile_grid {
+            let stroke = egui::Stroke::new(1.0, Color32::from_rgba_unmultiplied(0, 200, 255, 200));
+            draw_image_grid(&painter, visible, tile, stroke, to_screen);
+        }
+    }
+
+    /// ヒストグラムを必要なら計算し直し（表示中の画像が変わったとき）、結果を受け取る
+    fn update_histogram(&mut self, ctx: &egui::Context) {
+        if !self.config.show_histogram || self.current_image.is_none() {
+            self.histogram = None;
+            return;
+        }
+        if self.histogram.as_ref().is_none_or(|h| h.generation != self.image_generation) {
+            let pending = self
+                .current_image
+                .as_ref()
+                .and_then(LoadedImage::histogram_source)
+                .map(|source| {
+                    let (stats_tx, stats_rx) = mpsc::channel();
+                    let thread_ctx = ctx.clone();
+                    std::thread::spawn(move || {
+                        if stats_tx.send(source.compute()).is_ok() {
+                            thread_ctx.request_repaint();
+                        }
+                    });
+                    stats_rx
+                });
+            let stats = match pending {
+                Some(_) => self.histogram.take().and_then(|h| h.stats),
+                None => None,
+            };
+            self.histogram = Some(HistogramView {
+                generation: self.image_generation,
+                pending,
+                stats,
+            });
+        }
+        if let Some(view) = &mut self.histogram {
+            if let Some(rx) = &view.pending {
+                match rx.try_recv() {
+                    Ok(stats) => {
+                        view.stats = Some(stats);
+                        view.pending = None;
+                    }
+                    Err(mpsc::TryRecvError::Empty) => {}
+                    Err(mpsc::TryRecvError::Disconnected) => view.pending = None,
+                }
+            }
+        }
+    }
+
+    /// ヒストグラムとチャンネルごとの統計のパネル（左右・下のどこに置くかはパネル内で選ぶ）
+    fn show_histogram_panel(&mut self, ctx: &egui::Context) {
+        let dock = self.config.histogram_dock;
+        let contents = |ui: &mut egui::Ui| {
+            egui::ScrollArea::vertical()
+                .auto_shrink(false)
+                .show(ui, |ui| self.histogram_contents(ui));
+        };
+        match dock {
+            histogram::Dock::Left => {
+                egui::SidePanel::left("histogram").default_width(300.0).show(ctx, contents);
+            }
+            histogram::Dock::Right => {
+                egui::SidePanel::right("histogram").default_width(300.0).show(ctx, contents);
+            }
+            histogram::Dock::Bottom => {
+                egui::TopBottomPanel::bottom("histogram")
+                    .resizable(true)
+                    .default_height(240.0)
+                    .show(ctx, contents);
+            }
+        }
+    }
+
+    fn histogram_contents(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.strong("Histogram");
+            egui::ComboBox::from_id_salt("histogram_dock")
+                .selected_text(self.config.histogram_dock.label())
+                .show_ui(ui, |ui| {
+                    for dock in histogram::Dock::ALL {
+                        ui.selectable_value(&mut self.config.histogram_dock, dock, dock.label());
+                    }
+                });
+            if ui.button("Close").clicked() {
+                self.config.show_histogram = false;
+            }
+            if self.histogram.as_ref().is_some_and(|h| h.pending.is_some()) {
+                ui.spinner();
+            }
+        });
+        ui.separator();
+        let Some(stats) = self.histogram.as_ref().and_then(|h| h.stats.as_ref()) else {
+            if self.histogram.as_ref().is_some_and(|h| h.pending.is_none()) {
+                ui.label("Not available for SVG");
+            }
+            return;
+        };
+        let [r, g, b, ..] = &stats.channels;
+        ui.label("RGB");
+        histogram_plot(
+            ui,
+            &[
+                (r, Color32::from_rgb(255, 80, 80)),
+                (g, Color32::from_rgb(80, 220, 80)),
+                (b, Color32::from_rgb(90, 140, 255)),
+            ],
+        );
+        ui.label("Luminance");
+        histogram_plot(ui, &[(&stats.channels[histogram::LUMA], Color32::LIGHT_GRAY)]);
+        ui.label("Alpha");
+        histogram_plot(ui, &[(&stats.channels[histogram::ALPHA], Color32::GRAY)]);
+        ui.separator();
+
+        let value = |v: f32| {
+            if stats.float {
+                format!("{:.4}", v)
+            } else {
+                format!("{:.1}", v)
+            }
+        };
+        egui::Grid::new("histogram_stats").striped(true).show(ui, |ui| {
+            for header in ["", "Min", "Max", "Mean", "Clip low", "Clip high"] {
+                ui.strong(header);
+            }
+            ui.end_row();
+            for (name, channel) in histogram::CHANNEL_NAMES.iter().zip(&stats.channels) {
+                ui.label(*name);
+                ui.monospace(value(channel.min));
+                ui.monospace(value(channel.max));
+                ui.monospace(value(channel.mean));
+                ui.monospace(format!("{:.2}%", channel.clipped_low));
+                ui.monospace(format!("{:.2}%", channel.clipped_high));
+                ui.end_row();
+            }
+        });
+        ui.label(format!("Pixels: {}", stats.pixels));
+        if stats.float {
+            ui.label(format!("Range: 0 - {:.3} (before tone mapping)", stats.range));
+        }
+        match stats.unique_colors {
+            Some(n) => {
+                ui.label(format!("Unique colors: {}", n));
+            }
+            None if !stats.float => {
+                ui.label("Unique colors: (image too large)");
+            }
+            None => {}
+        }
+    }
+
+    /// ピクセルインスペクターの履歴パネル（K で残した値。選択してコピーできる）
+    fn show_pixel_history(&mut self, ctx: &egui::Context) {
+        egui::SidePanel::right("pixel_history")
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong("Pixel history");
+                    if ui.button("Copy all").clicked() {
+                        ctx.copy_text(self.pixel_history.join("\n"));
+                    }
+                    if ui.button("Clear").clicked() {
+                        self.pixel_history.clear();
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .auto_shrink(false)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in &self.pixel_history {
+                            ui.add(egui::Label::new(egui::RichText::new(line).monospace()).wrap());
+                        }
+                    });
+            });
+    }
+
+    /// 比較表示で並べるペインの数（重ねるモードでは 1）
+    fn pane_count(&self) -> usize {
+        if self.compare_mode == CompareMode::SideBySide {
+            1 + self.compare.len()
+        } else {
+            1
+        }
+    }
+
+    /// 差分モードの計算を必要なら始め、結果が届いたら（増幅率が変わったら）テクスチャを作る
+    fn update_difference(&mut self, ctx: &egui::Context) {
+        let (gamma, tone_map) = (self.config.hdr_gamma, self.config.tone_mapping);
+        let (key, a, b) = match (&self.current_path, &self.current_image, self.compare.first()) {
+            (Some(path), Some(image), Some(pane)) if self.compare_mode == CompareMode::Difference => {
+                // 表示中の画像と先頭のペインがデコード済みの画素から求める（読み込み中なら待つ）
+                let b = match (&pane.image, &pane.error) {
+                    (Some(image), _) => Ok(image.difference_source(gamma, tone_map)),
+                    (None, Some(e)) => Err(e.clone()),
+                    (None, None) => {
+                        self.difference = None;
+                        return;
+                    }
+                };
+                let a = image.difference_source(gamma, tone_map);
+                let tone = [a.tone(), b.as_ref().ok().and_then(DifferenceSource::tone)];
+                ((path.clone(), self.page, pane.path.clone(), tone), a, b)
+            }
+            _ => {
+                self.difference = None;
+                return;
+            }
+        };
+        if self.difference.as_ref().is_none_or(|d| d.key != key) {
+            info!("差分を計算します: {:?} と {:?}", key.0, key.2);
+            let (result_tx, result_rx) = mpsc::channel();
+            let thread_ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let result = b.and_then(|b| compute_difference(&a, &b));
+                if result_tx.send(result).is_ok() {
+                    thread_ctx.request_repaint();
+                }
+            });
+            self.difference = Some(DifferenceView {
+                key,
+                pending: Some(result_rx),
+                result: None,
+                texture: None,
+                gain: self.config.difference_gain,
+            });
+        }
+        let gain = self.config.difference_gain;
+        let options = self.raster_texture_options();
+        let Some(view) = &mut self.difference else { return };
+        if let Some(rx) = &view.pending {
+            let result = match rx.try_recv() {
+                Ok(result) => result,
+                Err(mpsc::TryRecvError::Empty) => return,
+                Err(mpsc::TryRecvError::Disconnected) => Err("差分の計算スレッドが終了しました".to_string()),
+            };
+            match &result {
+                Ok(d) => info!("差分: {} / {} px が異なります（最大 {}）", d.changed, d.total(), d.max),
+                Err(e) => error!("差分を計算できません: {}", e),
+            }
+            view.pending = None;
+            view.result = Some(result);
+        }
+        if let Some(Ok(difference)) = &view.result {
+            if view.texture.is_none() || view.gain != gain {
+                view.texture = Some(ctx.load_texture("difference", difference.to_image(gain), options));
+                view.gain = gain;
+            }
+        }
+    }
+
+    /// 画像（回転考慮）が利用可能領域全体に収まる scale を計算する。
+    /// 比較表示では 1 ペインの大きさに、いちばん大きい画像が収まるようにする
+    fn fit_to_screen(&mut self, avail: Vec2) {
+        let panes: Vec<Vec2> = std::iter::once(self.display_base_size())
+            .chain(
+                self.compare
+                    .iter()
+                    .filter_map(|pane| pane.image.as_ref())
+                    .map(|image| self.rotated_base_size(image)),
+            )
+            .collect();
+        if let Some(scale) = fit_scale(avail, self.pane_count(), &panes) {
+            self.scale = scale;
+            info!("画面に合わせてスケールを設定: {}", self.scale);
+        }
+    }
+
+    /// anchor（スクリーン座標）の位置にある画像上の点を固定したままズームする
+    fn zoom_at(&mut self, anchor: Vec2, panel_rect: &Rect, base_size: Vec2, new_scale: f32) {
+        let old_scale = self.scale;
+        if old_scale <= 0.0 || (new_scale - old_scale).abs() < f32::EPSILON {
+            return;
+        }
+        let old_size = base_size * old_scale;
+        let new_size = base_size * new_scale;
+        let old_origin = panel_rect.min.to_vec2() + (panel_rect.size() - old_size) * 0.5 + self.pan_offset;
+        let new_origin = anchor - (anchor - old_origin) * (new_scale / old_scale);
+        self.scale = new_scale;
+        self.pan_offset =
+            new_origin - panel_rect.min.to_vec2() - (panel_rect.size() - new_size) * 0.5;
+    }
+
+    /// 指定パスの画像（先頭ページ）を読み込み、拡大率、パン位置、画像サイズを更新する
+    fn load_image(&mut self, path: &Path, ctx: &egui::Context) -> bool {
+        self.load_page(path, 0, ctx)
+    }
+
+    /// 指定パスの画像の page 番目（0 始まり）のページを読み込む。
+    /// ラスタ画像はワーカーへ依頼した時点で true を返す（失敗は届いたときにダイアログで知らせる）
+    fn load_page(&mut self, path: &Path, page: usize, ctx: &egui::Context) -> bool {
+        info!("画像を読み込もうとしています: {:?} (ページ {})", path, page + 1);
+        if !is_svg_path(path) {
+            // 拡張子が不明でも WIC フォールバック込みでラスタとして試す
+            self.request_raster(path, page, false, ctx);
+            return true;
+        }
+        // SVG は読み込み（パース）だけをここで行い、ラスタライズは専用ワーカーに任せる
+        self.loader.cancel();
+        self.reset_view();
+        self.exr = None;
+        if !self.load_svg(path, ctx) {
+            return false;
+        }
+        self.page_info = pages::probe(path);
+        self.page = page;
+        self.prefetch_neighbors(ctx);
+        true
+    }
+
+    /// 表示位置・拡大率・回転・反転を初期状態に戻す（新しい画像を表示する前に呼ぶ）
+    fn reset_view(&mut self) {
+        self.pan_offset = Vec2::ZERO;
+        self.scale = 1.0;
+        self.rotation = 0;
+        self.flip = Flip::default();
+        self.image_size = None;
+        self.icc_state = color::IccState::None;
+        // フィット表示は次フレームの update() に委ねる。
+        // ここで（＝ImageViewer::new() からの初回読み込み時に）fit_to_screen を呼ぶと
+        // Context::run() 前に ctx.available_rect() を呼ぶことになり、egui 0.31 が
+        // 「Called `available_rect()` before `Context::run()`」でパニックする。
+        // last_available_size を None に戻すと、次フレームで新しい画像に対して再フィットされる。
+        self.last_available_size = None;
+    }
+
+    fn load_svg(&mut self, path: &Path, ctx: &egui::Context) -> bool {
+        match self.try_load_svg(path, ctx) {
+            Ok(()) => true,
+            Err(message) => {
+                error!("{}", message);
+                rfd::MessageDialog::new()
+                    .set_title("エラー")
+                    .set_description(&message)
+                    .show();
+                false
+            }
+        }
+    }
+
+    fn try_load_svg(&mut self, path: &Path, ctx: &egui::Context) -> Result<(), String> {
+        let prepared = self.svg_prepared(path)?;
+        self.set_prepared(path, prepared, ctx);
+        Ok(())
+    }
+
+    /// SVG を解析する。同じファイル（更新日時も同じ）を解析済みならツリーを使い回す
+    fn svg_prepared(&mut self, path: &Path) -> Result<loader::Prepared, String> {
+        let key = cache::CacheKey::new(path, 0);
+        if let Some(decoded) = self.loader.lookup(&key) {
+            info!("SVGの読み込みが完了しました（キャッシュ）");
+            return Ok(decoded.prepared);
+        }
+        let (tree, source_len) = parse_svg(path)?;
+        let prepared = loader::Prepared::Svg {
+            tree: Arc::new(tree),
+            source_len,
+        };
+        self.loader.store(loader::Decoded {
+            path: path.to_path_buf(),
+            page: 0,
+            mtime: key.mtime,
+            page_info: pages::PageInfo::single(),
+            icc: color::IccState::None,
+            prepared: prepared.clone(),
+        });
+        info!("SVGの読み込みが完了しました");
+        Ok(prepared)
+    }
+
+    /// ラスタ画像用のテクスチャオプション。
+    /// 拡大時の補間は設定で切り替え（滑らか／ピクセル等倍）。縮小は常にバイリニア。
+    fn raster_texture_options(&self) -> egui::TextureOptions {
+        let magnification = if self.config.smooth_zoom {
+            egui::TextureFilter::Linear
+        } else {
+            egui::TextureFilter::Nearest
+        };
+        egui::TextureOptions {
+            magnification,
+            minification: egui::TextureFilter::Linear,
+            ..Default::default()
+        }
+    }
+
+    /// デコード結果に影響する現在の設定
+    fn decode_settings(&self, ctx: &egui::Context) -> loader::DecodeSettings {
+        loader::DecodeSettings {
+            auto_orient: self.config.auto_orient,
+            color_management: self.config.color_management,
+            output_profile: self.output_profile.clone(),
+            max_texture_side: ctx.input(|i| i.max_texture_side).max(1) as u32,
+            texture_options: self.raster_texture_options(),
+            hdr_gamma: self.config.hdr_gamma,
+            tone_mapping: self.config.tone_mapping,
+            auto_exposure: self.config.auto_exposure,
+        }
+    }
+
+    /// ラスタ画像のデコードをワーカーに依頼する。先読み済みならその場で表示する。
+    /// 表示中の画像はデコードが終わるまでそのまま残し、位置・ページだけ先に進める
+    /// （連打しても要求は最新の 1 件に置き換わる）
+    fn request_raster(&mut self, path: &Path, page: usize, keep_view: bool, ctx: &egui::Context) {
+        let settings = self.decode_settings(ctx);
+        self.keep_view_on_load = keep_view;
+        if let Some(decoded) = self.loader.request(path, page, &settings) {
+            self.install_decoded(decoded, ctx);
+        }
+    }
+
+    /// デコード済みの画像をテクスチャ化して表示し、前後の画像の先読みを始める
+    fn install_decoded(&mut self, decoded: loader::Decoded, ctx: &egui::Context) {
+        let keep = (
+            self.scale,
+            self.pan_offset,
+            self.last_available_size,
+            self.rotation,
+            self.flip,
+        );
+        self.reset_view();
+        self.page = decoded.page;
+        self.page_info = decoded.page_info;
+        if self.page_info.count > 1 {
+            info!("複数ページのファイルです: {} ページ", self.page_info.count);
+        }
+        self.icc_state = decoded.icc;
+        self.exr = None;
+        self.set_prepared(&decoded.path, decoded.prepared, ctx);
+        if std::mem::take(&mut self.keep_view_on_load) {
+            (
+                self.scale,
+                self.pan_offset,
+                self.last_available_size,
+                self.rotation,
+                self.flip,
+            ) = keep;
+        }
+        info!("画像の読み込みが完了しました");
+        self.prefetch_neighbors(ctx);
+    }
+
+    /// 準備済みの画像をテクスチャ化して current_image にする。current_path もここで切り替える
+    /// （デコードに失敗したときは、表示中の画像とパスが食い違わないよう前のままにする）
+    fn set_prepared(&mut self, path: &Path, prepared: loader::Prepared, ctx: &egui::Context) {
+        let prepared = match prepared {
+            loader::Prepared::Exr {
+                file,
+                selection,
+                view,
+            } => {
+                info!("EXR のレイヤーを表示: {}", file.label(selection));
+                self.exr = Some(file);
+                self.exr_selection = selection;
+                *view
+            }
+            prepared => prepared,
+        };
+        let options = self.raster_texture_options();
+        let image = LoadedImage::from_prepared(ctx, path, prepared, options, self.config.gpu_rendering);
+        let size = image.base_size();
+        self.image_size = Some([size.x.ceil() as u32, size.y.ceil() as u32]);
+        self.current_image = Some(image);
+        self.current_path = Some(path.to_path_buf());
+        self.image_generation += 1;
+    }
+
+    /// 読み込み済み EXR の選択中レイヤー・チャンネルを表示する（ファイルは読み直さない）
+    fn show_exr_selection(&mut self, ctx: &egui::Context) {
+        let (Some(file), Some(path)) = (self.exr.clone(), self.current_path.clone()) else {
+            return;
+        };
+        let view = match loader::prepare_exr_view(&file, self.exr_selection, &self.decode_settings(ctx)) {
+            Ok(view) => view,
+            Err(e) => {
+                let message = format!("画像の読み込みに失敗しました: {} - {}", path.display(), e);
+                error!("{}", message);
+                rfd::MessageDialog::new()
+                    .set_title("エラー")
+                    .set_description(&message)
+                    .show();
+                return;
+            }
+        };
+        let prepared = loader::Prepared::Exr {
+            file,
+            selection: self.exr_selection,
+            view: Box::new(view),
+        };
+        self.set_prepared(&path, prepared, ctx);
+    }
+
+    /// 表示中の画像の前後（端では反対側へ回り込む）を先読みする。SVG は対象外
+    fn prefetch_neighbors(&mut self, ctx: &egui::Context) {
+        let Some(current) = &self.current_path else {
+            return;
+        };
+        let n = self.image_paths.len();
+        let Some(index) = self.image_paths.iter().position(|p| p == current) else {
+            return;
+        };
+        if n < 2 {
+            return;
+        }
+        let keys: Vec<loader::Key> = [index + 1, index + n - 1]
+            .into_iter()
+            .map(|i| &self.image_paths[i % n])
+            .filter(|p| !is_svg_path(p))
+            .map(|p| (p.clone(), 0))
+            .collect();
+        let settings = self.decode_settings(ctx);
+        self.loader.prefetch(&keys, &settings);
+    }
+
+    /// 表示位置・拡大率・回転・反転を保ったまま現在の画像（ページ）を読み直す。
+    /// デコード時に決まる設定（補間・色変換）を切り替えたときに使う
+    fn reload_keeping_view(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.current_path.clone() else {
+            return;
+        };
+        if self.exr.is_some() {
+            // EXR は保持しているレイヤーから作り直す（選択中のレイヤーを保つ）
+            self.show_exr_selection(ctx);
+            return;
+        }
+        self.request_raster(&path, self.page, true, ctx);
+    }
+
+    /// カラーマネジメントの切り替えを反映する。プロファイル付きのラスタ画像だけ読み直せばよい
+    fn reload_color_managed(&mut self, ctx: &egui::Context) {
+        info!("カラーマネジメント: {}", if self.config.color_management { "有効" } else { "無効" });
+        if self.icc_state != color::IccState::None {
+            self.reload_keeping_view(ctx);
+        }
+    }
+
+    /// 現在の画像があるディレクトリ（アーカイブ内の画像ならそのアーカイブ）内の画像一覧を更新する
+    fn update_image_list(&mut self, current_path: &Path) {
+        if let Some((archive, _)) = archive::split(current_path) {
+            self.set_folder_root(archive.to_path_buf());
+        } else if let Some(parent) = current_path.parent() {
+            self.set_folder_root(parent.to_path_buf());
+        }
+    }
+
+    /// root 内（設定によりサブフォルダも）の画像を一覧にする。失敗はログに残して飛ばす
+    fn set_folder_root(&mut self, root: PathBuf) {
+        info!("ディレクトリを読み込もうとしています: {:?}", root);
+        // 既定はエクスプローラーと同じ感覚で並ぶ自然順（img2 < img10）。Options で変更可
+        self.image_paths = folders::collect_images(&root, self.list_options());
+        self.thumbnails.retain(&self.image_paths);
+        info!("ディレクトリの読み込みが完了しました: {:?} ({} 件)", root, self.image_paths.len());
+        self.folder_root = Some(root);
+        self.is_playlist = false;
+    }
+
+    fn list_options(&self) -> folders::ListOptions {
+        folders::ListOptions {
+            recursive: self.config.recursive_folders,
+            sort_order: self.config.sort_order,
+            descending: self.config.sort_descending,
+        }
+    }
+
+    /// S キー: 停止中なら表示中の画像から始め、一時停止中なら再開し、再生中なら止める
+    fn toggle_slideshow(&mut self, ctx: &egui::Context) {
+        if !self.slideshow.is_active() {
+            self.start_slideshow(ctx);
+        } else if self.slideshow.is_paused() {
+            info!("スライドショーを再開します");
+            self.slideshow.resume(self.current_path.as_deref());
+        } else {
+            self.stop_slideshow(ctx);
+        }
+    }
+
+    fn start_slideshow(&mut self, ctx: &egui::Context) {
+        if self.image_paths.is_empty() {
+            return;
+        }
+        info!(
+            "スライドショーを開始します: {} 秒間隔, {}",
+            self.config.slideshow_interval,
+            self.config.slideshow_order.name()
+        );
+        self.grid_view = false;
+        self.slideshow.start(
+            &self.image_paths,
+            self.current_path.as_deref(),
+            self.config.slideshow_order,
+        );
+        let fullscreen = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+        if self.config.slideshow_fullscreen && !fullscreen {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
+            self.slideshow_entered_fullscreen = true;
+        }
+    }
+
+    fn stop_slideshow(&mut self, ctx: &egui::Context) {
+        info!("スライドショーを終了します");
+        self.slideshow.stop();
+        self.fade = None;
+        if std::mem::take(&mut self.slideshow_entered_fullscreen) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+        }
+    }
+
+    /// スライドショー中の毎フレームの処理: ユーザー操作での一時停止と、時間が来たら次の画像へ
+    fn update_slideshow(&mut self, ctx: &egui::Context) {
+        if !self.slideshow.is_active() {
+            return;
+        }
+        // S（再開・停止）、Esc（停止）、F11（全画面）以外の操作で一時停止する
+        let user_input = ctx.input(|i| {
+            i.pointer.any_pressed()
+                || i.raw_scroll_delta != Vec2::ZERO
+                || i.events.iter().any(|e| {
+                    matches!(e, egui::Event::Key { key, pressed: true, .. }
+                        if !matches!(key, Key::S | Key::Escape | Key::F11))
+                })
+        });
+        if user_input && !self.slideshow.is_paused() {
+            info!("操作があったためスライドショーを一時停止します");
+            self.slideshow.pause();
+        }
+        let settings = slideshow::Settings {
+            interval: std::time::Duration::from_secs_f32(clamp_slideshow_interval(self.config.slideshow_interval)),
+            order: self.config.slideshow_order,
+            looping: self.config.slideshow_loop,
+        };
+        let tick = self.slideshow.tick(
+            std::time::Instant::now(),
+            self.current_path.as_deref(),
+            self.loader.is_loading(),
+            &self.image_paths,
+            settings,
+        );
+        match tick {
+            slideshow::Tick::Idle => {}
+            slideshow::Tick::Wait(wait) => ctx.request_repaint_after(wait),
+            slideshow::Tick::Advance(path) => {
+                self.fade = if self.config.slideshow_crossfade {
+                    self.current_texture().cloned().zip(self.last_image_rect).map(|(texture, rect)| Fade {
+                        texture,
+                        rect,
+                        rotation: self.rotation,
+                        flip: self.flip,
+                        start: None,
+                    })
+                } else {
+                    None
+                };
+                self.load_image(&path, ctx);
+            }
+            slideshow::Tick::Finished => {
+                info!("スライドショーが最後の画像まで進みました");
+                self.stop_slideshow(ctx);
+            }
+        }
+    }
+
+    /// 表示中の画像を 1 枚のテクスチャで描いているならそのテクスチャ（クロスフェード用）。
+    /// タイル分割した画像と SVG は対象外
+    fn current_texture(&self) -> Option<&egui::TextureHandle> {
+        match self.current_image.as_ref()? {
+            LoadedImage::Raster { texture, .. } | LoadedImage::Hdr { texture, .. } => Some(texture),
+            LoadedImage::Animated { frames, playback, .. } => frames.get(playback.current()),
+            LoadedImage::Tiled { .. } | LoadedImage::Svg { .. } => None,
+        }
+    }
+
+    /// 監視スレッドが見つけた変更を反映する
+    fn handle_watch_event(&mut self, event: watcher::Event, ctx: &egui::Context) {
+        match event {
+            watcher::Event::ListChanged { added, removed } => {
+                info!(
+                    "フォルダの内容が変わったため一覧を更新します（追加 {} 件・削除 {} 件）",
+                    added.len(),
+                    removed.len()
+                );
+                self.apply_list_changes(&added, &removed, ctx);
+            }
+            watcher::Event::Modified(path) if self.current_path.as_ref() == Some(&path) => {
+                info!("表示中のファイルが更新されたため読み直します: {:?}", path);
+                self.thumbnails.invalidate(&path);
+                self.reload_changed_file(ctx);
+            }
+            watcher::Event::Removed(path) if self.current_path.as_ref() == Some(&path) => {
+                info!("表示中のファイルが削除されました: {:?}", path);
+                // 削除前の位置にある画像（末尾なら直前の画像）へ移る
+                let index = self.current_index().unwrap_or(0);
+                self.refresh_image_list(ctx);
+                match self.image_paths.len() {
+                    0 => info!("フォルダに表示できる画像が残っていません"),
+                    n => self.load_image_at(ctx, index.min(n - 1)),
+                }
+            }
+            // 監視対象を切り替える前に見つかった、前の画像についての通知
+            _ => {}
+        }
+    }
+
+    /// 監視で見つかった追加・削除を一覧に反映する。変わったフォルダの中だけを並べ直し、
+    /// 位置を決められない（新しいフォルダ等）ときやプレイリスト・アーカイブは作り直す
+    fn apply_list_changes(&mut self, added: &[PathBuf], removed: &[PathBuf], ctx: &egui::Context) {
+        let options = self.list_options();
+        let incremental = !self.is_playlist
+            && self.folder_root.as_deref().is_some_and(|root| !archive::is_archive_path(root))
+            && folders::apply_changes(&mut self.image_paths, added, removed, options);
+        if !incremental {
+            self.refresh_image_list(ctx);
+            return;
+        }
+        self.thumbnails.retain(&self.image_paths);
+        self.filmstrip_synced = None;
+        self.grid_synced = None;
+        self.prefetch_neighbors(ctx);
+    }
+
+    /// 一覧を今のフォルダの内容で作り直す（表示中の画像はそのまま）
+    fn refresh_image_list(&mut self, ctx: &egui::Context) {
+        if self.is_playlist {
+            // プレイリストは作り直さず、無くなったファイルだけを除く
+            self.image_paths.retain(|p| p.is_file() || archive::split(p).is_some());
+            self.thumbnails.retain(&self.image_paths);
+        } else if let Some(root) = self.folder_root.clone() {
+            self.set_folder_root(root);
+        } else {
+            return;
+        }
+        self.filmstrip_synced = None;
+        self.grid_synced = None;
+        self.prefetch_neighbors(ctx);
+    }
+
+    /// 表示中のファイルが書き換えられたとき、表示位置・拡大率・回転・反転を保って読み直す
+    fn reload_changed_file(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.current_path.clone() else {
+            return;
+        };
+        if !is_svg_path(&path) {
+            // EXR もファイルから読み直す（保持しているレイヤーは古い内容のため）
+            self.request_raster(&path, self.page, true, ctx);
+            return;
+        }
+        let keep = (
+            self.scale,
+            self.pan_offset,
+            self.last_available_size,
+            self.rotation,
+            self.flip,
+        );
+        if self.load_page(&path, self.page, ctx) {
+            (
+                self.scale,
+                self.pan_offset,
+                self.last_available_size,
+                self.rotation,
+                self.flip,
+            ) = keep;
+        }
+    }
+
+    /// 並び順の変更を反映する。表示中の画像はそのままで、一覧内の位置だけが変わる
+    fn resort_image_list(&mut self, ctx: &egui::Context) {
+        if self.is_playlist {
+            // プレイリストは指定された順のまま（並び順はフォルダの一覧にだけ適用する）
+            return;
+        }
+        info!(
+            "並び順を変更: {}{}",
+            self.config.sort_order.name(),
+            if self.config.sort_descending { "（降順）" } else { "" }
+        );
+        let options = self.list_options();
+        folders::resort(&mut self.image_paths, options);
+        // フィルムストリップ・グリッドを表示中の画像の新しい位置へスクロールし直す
+        self.filmstrip_synced = None;
+        self.grid_synced = None;
+        // 前後の画像が変わるので先読みし直す
+        self.prefetch_neighbors(ctx);
+    }
+
+    /// 前後の画像へ切り替え（見開き表示では前後の見開きへ）
+    fn load_adjacent_image(&mut self, ctx: &egui::Context, next: bool) {
+        let origin = self.navigation_origin();
+        if self.config.spread_view {
+            let index = origin
+                .as_ref()
+                .and_then(|(path, _)| self.image_paths.iter().position(|p| p == path));
+            if let Some(index) = index {
+                let target = self.adjacent_spread_start(index, next);
+                self.load_image_at(ctx, target);
+                return;
+            }
+        }
+        if let Some((current_path, _)) = origin {
             let current_index = self
                 .image_paths
                 .iter()
-                .position(|p| p == current_path)
+                .position(|p| *p == current_path)
                 .unwrap_or(0);
             let new_index = if next {
                 if current_index + 1 < self.image_paths.len() {
@@ -1584,10 +4044,61 @@ impl ImageViewer {
         }
     }
 
+    /// 見開き表示で index 番目のページから始まる見開きの次（前）の見開きの先頭。端では反対側へ回る
+    fn adjacent_spread_start(&self, index: usize, next: bool) -> usize {
+        let count = self.image_paths.len();
+        let settings = self.spread_settings();
+        let wide = |i| self.is_wide_page(i);
+        // SVG 等で 1 ページだけ表示しているときは 1 ページ進める
+        let shown_alone = self.spread.is_none() && !self.loader.is_loading();
+        if !next {
+            spread::previous_start(index, count, settings, wide)
+        } else if shown_alone {
+            (index + 1) % count
+        } else {
+            spread::next_start(index, count, settings, wide)
+        }
+    }
+
+    /// ファイル内の前後のページへ切り替え（Ctrl+PageUp/PageDown）。先頭・末尾では止まる
+    fn load_adjacent_page(&mut self, ctx: &egui::Context, next: bool) {
+        let Some((path, page)) = self.navigation_origin() else { return };
+        let target = if next {
+            page + 1
+        } else {
+            match page.checked_sub(1) {
+                Some(p) => p,
+                None => return,
+            }
+        };
+        if target < self.page_info.count {
+            self.load_page(&path, target, ctx);
+        }
+    }
+
+    /// 次（前）のフォルダの先頭の画像へ切り替え（Ctrl+←/→）。一覧内に無ければ
+    /// 同じ階層の隣のフォルダへ移る（端では反対側へ回る）。前のフォルダへ移るときは
+    /// その中の最後のフォルダの先頭を開く
+    fn load_adjacent_folder(&mut self, ctx: &egui::Context, next: bool) {
+        let Some(current) = self.current_index() else { return };
+        if let Some(index) = folders::adjacent_folder_start(&self.image_paths, current, next) {
+            self.load_image_at(ctx, index);
+            return;
+        }
+        let Some(root) = self.folder_root.clone() else { return };
+        let Some(sibling) = folders::sibling_folder(&root, next, self.list_options()) else {
+            info!("移動できる隣のフォルダがありません: {:?}", root);
+            return;
+        };
+        self.set_folder_root(sibling);
+        let index = if next { 0 } else { folders::last_folder_start(&self.image_paths) };
+        self.load_image_at(ctx, index);
+    }
+
     /// フォルダ内の指定インデックスの画像へ切り替え（Home/End 用）
     fn load_image_at(&mut self, ctx: &egui::Context, index: usize) {
         if let Some(path) = self.image_paths.get(index).cloned() {
-            if Some(&path) != self.current_path.as_ref() {
+            if self.navigation_origin().is_none_or(|(origin, _)| origin != path) {
                 self.load_image(&path, ctx);
             }
         }
@@ -1595,13 +4106,54 @@ impl ImageViewer {
 
     /// ファイルダイアログで画像を開く
     fn open_file_dialog(&mut self, ctx: &egui::Context) {
-        if let Some(file_path) = rfd::FileDialog::new()
+        // 複数選択したときはプレイリストとして開く
+        if let Some(paths) = rfd::FileDialog::new()
             .add_filter("Images", SUPPORTED_EXTS)
+            .add_filter("Playlists", playlist::LIST_EXTS)
+            .add_filter("Archives", archive::ARCHIVE_EXTS)
             .add_filter("All Files", &["*"])
-            .pick_file()
+            .pick_files()
         {
-            self.load_image(&file_path, ctx);
-            self.update_image_list(&file_path);
+            if let Some(open) = playlist::resolve(&paths) {
+                self.open(open, ctx);
+            }
+        }
+    }
+
+    /// 1 ファイルならそれとフォルダの一覧を、フォルダ・プレイリストならその先頭を開く
+    fn open(&mut self, open: playlist::Open, ctx: &egui::Context) {
+        match open {
+            playlist::Open::File(path) => {
+                self.load_image(&path, ctx);
+                self.update_image_list(&path);
+            }
+            playlist::Open::Folder(dir) => {
+                self.set_folder_root(dir.clone());
+                self.filmstrip_synced = None;
+                self.grid_synced = None;
+                if self.image_paths.is_empty() {
+                    let mut message = format!("フォルダに表示できる画像がありません: {}", dir.display());
+                    if !self.config.recursive_folders && !archive::is_archive_path(&dir) {
+                        message.push_str("\n（サブフォルダの画像も表示するには Options の Include subfolders を有効にしてください）");
+                    }
+                    error!("{}", message);
+                    rfd::MessageDialog::new()
+                        .set_title("エラー")
+                        .set_description(&message)
+                        .show();
+                    return;
+                }
+                self.load_image_at(ctx, 0);
+            }
+            playlist::Open::Playlist(paths) => {
+                self.image_paths = paths;
+                self.thumbnails.retain(&self.image_paths);
+                self.folder_root = None;
+                self.is_playlist = true;
+                self.filmstrip_synced = None;
+                self.grid_synced = None;
+                self.load_image_at(ctx, 0);
+            }
         }
     }
 
@@ -1611,31 +4163,82 @@ impl ImageViewer {
     /// ・"fit" モードの場合、ウィンドウサイズ変更時に scale 再計算
     /// ・SVG は毎フレーム可視領域だけを表示解像度でラスタライズし、どの倍率でも線が鮮明なまま
     /// ・キー操作: ←→/PgUp/PgDn/Space/BS=前後, Home/End=先頭末尾, F=フィット, 0=100%,
-    ///   +/-=ズーム, L/R=回転, F11=全画面, O=開く, Esc=終了
+    ///   +/-=ズーム, L/R=回転, F11=全画面, O=開く, Esc=終了,
+    ///   P=アニメーション再生/一時停止, ,/.=コマ戻し/コマ送り, [/]=再生速度,
+    ///   Ctrl+PgUp/PgDn=ファイル内の前後のページ, Ctrl+←→=前後のフォルダ, H/V=左右/上下反転, C=カラーマネジメント切り替え,
+    ///   S=スライドショー開始/再開/停止（操作で一時停止、Esc で終了）,
+    ///   T=フィルムストリップ, G=グリッド表示（←→↑↓で選択、Enter/クリックで開く、Esc で戻る）
     fn update(&mut self, ctx: &egui::Context) {
         // 初期画像の遅延読み込み（最初のフレームで一度だけ）。
         // ここなら GL バックエンドが報告した正しい max_texture_side が使えるため、
         // 大きな画像でもパニックしない。take() で一度きりにしてエラー時の無限リトライも防ぐ。
-        if let Some(path) = self.pending_open.take() {
-            self.load_image(&path, ctx);
-            self.update_image_list(&path);
+        if let Some(open) = self.pending_open.take() {
+            self.open(open, ctx);
+        }
+
+        // バックグラウンドでデコードした画像が届いていれば表示する
+        if let Some(result) = self.loader.poll() {
+            match result {
+                Ok(decoded) => self.install_decoded(decoded, ctx),
+                Err((path, e)) => {
+                    let message = format!("画像の読み込みに失敗しました: {} - {}", path.display(), e);
+                    error!("{}", message);
+                    rfd::MessageDialog::new()
+                        .set_title("エラー")
+                        .set_description(&message)
+                        .show();
+                }
+            }
+        }
+
+        // フォルダ・表示中のファイルの変更を反映する
+        if self.config.watch_changes {
+            self.watcher.watch(
+                self.folder_root.as_deref(),
+                self.config.recursive_folders,
+                self.current_path.as_deref(),
+            );
+        } else {
+            self.watcher.watch(None, false, None);
+        }
+        while let Some(event) = self.watcher.poll() {
+            self.handle_watch_event(event, ctx);
         }
 
-        // ドラッグ＆ドロップ対応（複数ドロップ時は先頭のみ開く。同フォルダの残りは前後送りで辿れる）
-        let dropped = ctx.input(|i| i.raw.dropped_files.first().and_then(|f| f.path.clone()));
-        if let Some(path) = dropped {
-            self.load_image(&path, ctx);
-            self.update_image_list(&path);
+        // ドラッグ＆ドロップ対応（1 ファイルならそのフォルダを、複数ならドロップしたものをプレイリストとして開く）
+        let dropped: Vec<PathBuf> =
+            ctx.input(|i| i.raw.dropped_files.iter().filter_map(|f| f.path.clone()).collect());
+        if !dropped.is_empty() {
+            if let Some(open) = playlist::resolve(&dropped) {
+                self.open(open, ctx);
+            }
         }
 
         // メニューバー（File / Options）の表示
-        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+        // 全画面でのスライドショー中はメニューバーとフィルムストリップを隠す
+        let presenting = self.slideshow.is_active()
+            && ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+        egui::TopBottomPanel::top("menu_bar").show_animated(ctx, !presenting, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
                     if ui.button("Open... (O)").clicked() {
                         self.open_file_dialog(ctx);
                         ui.close_menu();
                     }
+                    if ui.button("Open Folder...").clicked() {
+                        if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                            self.open(playlist::Open::Folder(dir), ctx);
+                        }
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(self.current_image.is_some(), egui::Button::new("Compare with..."))
+                        .clicked()
+                    {
+                        self.compare_file_dialog(ctx);
+                        ui.close_menu();
+                    }
                 });
                 ui.menu_button("Options", |ui| {
                     ui.group(|ui| {
@@ -1651,6 +4254,28 @@ impl ImageViewer {
                             "original".to_string(),
                             "Original Size",
                         );
+                        ui.separator();
+                        if ui
+                            .checkbox(&mut self.config.auto_orient, "Auto-rotate by EXIF")
+                            .changed()
+                        {
+                            // 向きはデコード時に適用するため、読み直して反映する（回転は補正後基準なので 0 に戻す）
+                            if let Some(
+                                LoadedImage::Raster { path, .. }
+                                | LoadedImage::Hdr { path, .. }
+                                | LoadedImage::Tiled { path, .. },
+                            ) = &self.current_image
+                            {
+                                let path = path.clone();
+                                self.load_page(&path, self.page, ctx);
+                            }
+                        }
+                        if ui
+                            .checkbox(&mut self.config.color_management, "Color management (C)")
+                            .changed()
+                        {
+                            self.reload_color_managed(ctx);
+                        }
                     });
 
                     ui.add_space(8.0);
@@ -1663,24 +4288,20 @@ impl ImageViewer {
                             .changed()
                         {
                             // ラスタ画像のテクスチャフィルタは生成時に決まるため、読み直して反映する
-                            if let Some(LoadedImage::Raster { path, .. }) = &self.current_image {
-                                let path = path.clone();
-                                let keep = (
-                                    self.scale,
-                                    self.pan_offset,
-                                    self.last_available_size,
-                                    self.rotation,
-                                );
-                                if self.load_image(&path, ctx) {
-                                    (
-                                        self.scale,
-                                        self.pan_offset,
-                                        self.last_available_size,
-                                        self.rotation,
-                                    ) = keep;
-                                }
+                            if matches!(
+                                self.current_image,
+                                Some(
+                                    LoadedImage::Raster { .. }
+                                        | LoadedImage::Animated { .. }
+                                        | LoadedImage::Hdr { .. }
+                                        | LoadedImage::Tiled { .. }
+                                )
+                            ) {
+                                self.reload_keeping_view(ctx);
                             }
                         }
+                        ui.checkbox(&mut self.config.pixel_inspector, "Pixel inspector (I)");
+                        ui.checkbox(&mut self.config.show_histogram, "Histogram panel");
                         ui.add(
                             egui::Slider::new(&mut self.config.wheel_zoom_factor, 0.0002..=0.005)
                                 .logarithmic(true)
@@ -1698,6 +4319,7 @@ impl ImageViewer {
                                     self.pan_offset,
                                     self.last_available_size,
                                     self.rotation,
+                                    self.flip,
                                 );
                                 if self.load_image(&path, ctx) {
                                     (
@@ -1705,31 +4327,282 @@ impl ImageViewer {
                                         self.pan_offset,
                                         self.last_available_size,
                                         self.rotation,
+                                        self.flip,
                                     ) = keep;
                                 }
                             }
                         }
                     });
-
-                    ui.add_space(8.0);
-
-                    ui.group(|ui| {
-                        ui.label("Other Settings");
+
+                    ui.add_space(8.0);
+
+                    ui.group(|ui| {
+                        ui.label("Grid");
+                        ui.separator();
+                        ui.checkbox(&mut self.config.pixel_grid, "Pixel grid");
+                        ui.add_enabled(
+                            self.config.pixel_grid,
+                            egui::Slider::new(&mut self.config.pixel_grid_threshold, 2.0..=64.0)
+                                .logarithmic(true)
+                                .text("Show at pixel size (px)"),
+                        );
+                        ui.checkbox(&mut self.config.tile_grid, "Tile grid");
+                        ui.add_enabled_ui(self.config.tile_grid, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.add(egui::DragValue::new(&mut self.config.tile_grid_width).range(1..=4096));
+                                ui.label("x");
+                                ui.add(egui::DragValue::new(&mut self.config.tile_grid_height).range(1..=4096));
+                                ui.label("px");
+                            });
+                        });
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.group(|ui| {
+                        ui.label("Sort Order");
+                        ui.separator();
+                        let mut changed = false;
+                        for key in sort::SortKey::ALL {
+                            changed |= ui
+                                .radio_value(&mut self.config.sort_order, key, key.label())
+                                .changed();
+                        }
+                        changed |= ui.checkbox(&mut self.config.sort_descending, "Descending").changed();
+                        if changed {
+                            self.resort_image_list(ctx);
+                        }
+                        ui.separator();
+                        ui.checkbox(&mut self.config.watch_changes, "Watch for file changes");
+                        if ui
+                            .checkbox(&mut self.config.recursive_folders, "Include subfolders")
+                            .changed()
+                        {
+                            // 一覧を作り直す（プレイリストはそのまま）。表示中の画像が起点のフォルダの
+                            // サブフォルダにあってサブフォルダを辿らなくなった場合は、その画像のフォルダを起点にする
+                            let current = self.current_path.clone().filter(|_| !self.is_playlist);
+                            if let Some(path) = current {
+                                if path.parent() == self.folder_root.as_deref() {
+                                    self.refresh_image_list(ctx);
+                                } else {
+                                    self.update_image_list(&path);
+                                    self.filmstrip_synced = None;
+                                    self.grid_synced = None;
+                                    self.prefetch_neighbors(ctx);
+                                }
+                            }
+                        }
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.group(|ui| {
+                        ui.label("Slideshow");
+                        ui.separator();
+                        let label = if self.slideshow.is_active() { "Stop slideshow (S)" } else { "Start slideshow (S)" };
+                        if ui.button(label).clicked() {
+                            self.toggle_slideshow(ctx);
+                            ui.close_menu();
+                        }
+                        ui.add(
+                            egui::Slider::new(&mut self.config.slideshow_interval, 1.0..=60.0)
+                                .logarithmic(true)
+                                .suffix(" s")
+                                .text("Interval"),
+                        );
+                        for order in slideshow::SlideOrder::ALL {
+                            ui.radio_value(&mut self.config.slideshow_order, order, order.label());
+                        }
+                        ui.checkbox(&mut self.config.slideshow_loop, "Loop");
+                        ui.checkbox(&mut self.config.slideshow_crossfade, "Cross-fade");
+                        ui.checkbox(&mut self.config.slideshow_fullscreen, "Start in fullscreen");
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.group(|ui| {
+                        ui.label("Spread");
+                        ui.separator();
+                        // ページ割りは次のフレームの update_spread で作り直される
+                        ui.checkbox(&mut self.config.spread_view, "Two-page spread (D)");
+                        ui.checkbox(&mut self.config.spread_right_to_left, "Right to left");
+                        ui.checkbox(&mut self.config.spread_cover_single, "Show cover alone");
+                        ui.checkbox(&mut self.config.spread_landscape_single, "Show landscape pages alone");
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.group(|ui| {
+                        ui.label("Thumbnails");
+                        ui.separator();
+                        ui.checkbox(&mut self.config.show_filmstrip, "Filmstrip (T)");
+                        ui.checkbox(&mut self.grid_view, "Grid view (G)");
+                        if ui.button("Clear thumbnail cache").clicked() {
+                            match self.thumbnails.clear_disk_cache() {
+                                Ok(n) => info!("サムネイルキャッシュを消去しました: {} 件", n),
+                                Err(e) => error!("サムネイルキャッシュの消去に失敗しました: {}", e),
+                            }
+                            ui.close_menu();
+                        }
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.group(|ui| {
+                        ui.label("Other Settings");
+                        ui.separator();
+                        ui.checkbox(&mut self.config.enable_debug_log, "Enable Debug Log");
+                        ui.checkbox(&mut self.config.check_updates, "Check updates on startup");
+                        if ui
+                            .add(
+                                egui::Slider::new(&mut self.config.cache_budget_mb, 0..=4096)
+                                    .text("Image cache (MB)"),
+                            )
+                            .changed()
+                        {
+                            self.loader
+                                .set_cache_budget(self.config.cache_budget_mb * 1024 * 1024);
+                        }
+                    });
+
+                    ui.add_space(8.0);
+
+                    if ui.button("Save Settings").clicked() {
+                        match self.config.save() {
+                            Ok(_) => info!("設定が保存されました"),
+                            Err(e) => error!("設定の保存に失敗しました: {}", e),
+                        }
+                        ui.close_menu();
+                    }
+                });
+                // HDR 画像を開いているときだけ、露出・トーンマッピングのメニューを出す（読み直し不要）
+                if let Some(LoadedImage::Hdr { image, exposure, .. }) = &mut self.current_image {
+                    ui.menu_button("HDR", |ui| {
+                        ui.add(
+                            egui::Slider::new(exposure, -10.0..=10.0)
+                                .step_by(0.1)
+                                .text("Exposure (EV)"),
+                        );
+                        if ui.button("Auto exposure").clicked() {
+                            *exposure = image.auto_exposure;
+                        }
+                        ui.add(egui::Slider::new(&mut self.config.hdr_gamma, 1.0..=3.0).text("Gamma"));
+                        ui.separator();
+                        ui.label("Tone mapping");
+                        for op in hdr::ToneMap::ALL {
+                            ui.radio_value(&mut self.config.tone_mapping, op, op.name());
+                        }
+                        ui.separator();
+                        ui.checkbox(&mut self.config.auto_exposure, "Auto exposure on open");
+                    });
+                }
+                // EXR を開いているときだけ、レイヤー・チャンネルの選択メニューを出す
+                if let Some(file) = &self.exr {
+                    let mut selected = None;
+                    ui.menu_button("EXR", |ui| {
+                        egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                            for (i, layer) in file.layers.iter().enumerate() {
+                                let sel = exr_layers::Selection { layer: i, channel: None };
+                                if ui
+                                    .selectable_label(self.exr_selection == sel, file.label(sel))
+                                    .clicked()
+                                {
+                                    selected = Some(sel);
+                                }
+                                ui.indent(i, |ui| {
+                                    for (c, channel) in layer.channels.iter().enumerate() {
+                                        let sel = exr_layers::Selection {
+                                            layer: i,
+                                            channel: Some(c),
+                                        };
+                                        if ui
+                                            .selectable_label(self.exr_selection == sel, &channel.name)
+                                            .clicked()
+                                        {
+                                            selected = Some(sel);
+                                        }
+                                    }
+                                });
+                            }
+                        });
+                        if selected.is_some() {
+                            ui.close_menu();
+                        }
+                    });
+                    if let Some(sel) = selected {
+                        self.exr_selection = sel;
+                        self.show_exr_selection(ctx);
+                    }
+                }
+                // 複数ページのファイルを開いているときだけ、ページ（ICO/CUR は解像度）の選択メニューを出す
+                if self.page_info.count > 1 {
+                    ui.menu_button("Page", |ui| {
+                        let mut selected = None;
+                        egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                            for i in 0..self.page_info.count {
+                                let label = match self.page_info.labels.get(i) {
+                                    Some(l) => l.clone(),
+                                    None => format!("Page {}", i + 1),
+                                };
+                                if ui.selectable_label(i == self.page, label).clicked() {
+                                    selected = Some(i);
+                                }
+                            }
+                        });
+                        if let Some(i) = selected {
+                            ui.close_menu();
+                            if let Some(path) = self.current_path.clone() {
+                                self.load_page(&path, i, ctx);
+                            }
+                        }
+                    });
+                }
+                // 比較表示中だけ、ペインの追加・削除メニューを出す
+                if !self.compare.is_empty() {
+                    ui.menu_button("Compare", |ui| {
+                        let mut removed = None;
+                        for (i, pane) in self.compare.iter().enumerate() {
+                            let name = pane.path.file_name().unwrap_or_default().to_string_lossy();
+                            if ui.button(format!("Remove {}", name)).clicked() {
+                                removed = Some(i);
+                            }
+                        }
+                        if let Some(i) = removed {
+                            self.compare.remove(i);
+                            self.last_available_size = None;
+                            ui.close_menu();
+                        }
                         ui.separator();
-                        ui.checkbox(&mut self.config.enable_debug_log, "Enable Debug Log");
-                        ui.checkbox(&mut self.config.check_updates, "Check updates on startup");
-                    });
-
-                    ui.add_space(8.0);
-
-                    if ui.button("Save Settings").clicked() {
-                        match self.config.save() {
-                            Ok(_) => info!("設定が保存されました"),
-                            Err(e) => error!("設定の保存に失敗しました: {}", e),
+                        // 重ねるモードでは先頭の画像を B として表示中の画像（A）と重ねる
+                        for mode in CompareMode::ALL {
+                            if ui.radio_value(&mut self.compare_mode, mode, mode.label()).changed() {
+                                self.last_available_size = None;
+                            }
                         }
-                        ui.close_menu();
-                    }
-                });
+                        if self.compare_mode == CompareMode::Difference {
+                            ui.add(
+                                egui::Slider::new(&mut self.config.difference_gain, 1.0..=64.0)
+                                    .logarithmic(true)
+                                    .text("Gain"),
+                            );
+                        }
+                        ui.separator();
+                        if ui
+                            .add_enabled(
+                                1 + self.compare.len() < MAX_COMPARE_PANES,
+                                egui::Button::new("Add..."),
+                            )
+                            .clicked()
+                        {
+                            self.compare_file_dialog(ctx);
+                            ui.close_menu();
+                        }
+                        if ui.button("Close compare (Esc)").clicked() {
+                            self.close_compare();
+                            ui.close_menu();
+                        }
+                    });
+                }
                 ui.menu_button("Help", |ui| {
                     ui.label(format!("MSBT-yuina v{}", updater::CURRENT_VERSION));
                     ui.separator();
@@ -1841,6 +4714,23 @@ impl ImageViewer {
 
         ctx.set_visuals(egui::Visuals::dark());
 
+        self.thumbnails.poll(ctx);
+        if self.config.show_filmstrip && !self.grid_view && !self.image_paths.is_empty() && !presenting {
+            self.show_filmstrip(ctx);
+        }
+        if !self.pixel_history.is_empty() && !presenting {
+            self.show_pixel_history(ctx);
+        }
+        self.update_histogram(ctx);
+        if self.config.show_histogram && !presenting {
+            self.show_histogram_panel(ctx);
+        }
+
+        self.update_slideshow(ctx);
+        self.update_spread(ctx);
+        self.update_compare(ctx);
+        self.update_difference(ctx);
+
         egui::CentralPanel::default()
             // 既定フレームの内側余白をなくし、画像領域をパネル全体に広げる
             .frame(egui::Frame::default())
@@ -1855,9 +4745,17 @@ impl ImageViewer {
                     self.last_available_size = Some(panel_rect.size());
                 }
 
-                // 画像の有無に関わらず有効なキー
+                // 画像の有無に関わらず有効なキー（Esc はグリッド表示中なら通常表示へ戻るだけ）
                 if ui.input(|i| i.key_pressed(Key::Escape)) {
-                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    if self.grid_view {
+                        self.grid_view = false;
+                    } else if self.slideshow.is_active() {
+                        self.stop_slideshow(ctx);
+                    } else if !self.compare.is_empty() {
+                        self.close_compare();
+                    } else {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    }
                 }
                 if ui.input(|i| i.key_pressed(Key::F11)) {
                     let fullscreen = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
@@ -1866,6 +4764,24 @@ impl ImageViewer {
                 if ui.input(|i| i.key_pressed(Key::O)) {
                     self.open_file_dialog(ctx);
                 }
+                if ui.input(|i| i.key_pressed(Key::S)) {
+                    self.toggle_slideshow(ctx);
+                }
+                if ui.input(|i| i.key_pressed(Key::T)) {
+                    self.config.show_filmstrip = !self.config.show_filmstrip;
+                }
+                if ui.input(|i| i.key_pressed(Key::G)) && !self.image_paths.is_empty() {
+                    self.grid_view = !self.grid_view;
+                }
+                if ui.input(|i| i.key_pressed(Key::D)) {
+                    self.config.spread_view = !self.config.spread_view;
+                }
+
+                // グリッド表示中はサムネイルの一覧だけを描く（画像のズーム・パン等は受け付けない）
+                if self.grid_view && !self.image_paths.is_empty() {
+                    self.show_grid(ui, ctx);
+                    return;
+                }
 
                 let response = ui.interact(
                     panel_rect,
@@ -1874,15 +4790,30 @@ impl ImageViewer {
                 );
 
                 if self.current_image.is_some() {
-                    // ---- フォルダ内ナビゲーション ----
-                    if ui.input(|i| {
-                        i.key_pressed(Key::ArrowRight)
+                    // ---- ファイル内のページ送り（Ctrl+PageUp/PageDown）／フォルダ内ナビゲーション ----
+                    // 右綴じの見開き表示では ← で次の見開きへ進む
+                    let (forward_arrow, backward_arrow) =
+                        if self.config.spread_view && self.config.spread_right_to_left {
+                            (Key::ArrowLeft, Key::ArrowRight)
+                        } else {
+                            (Key::ArrowRight, Key::ArrowLeft)
+                        };
+                    if ui.input(|i| i.modifiers.command && i.key_pressed(Key::PageDown)) {
+                        self.load_adjacent_page(ctx, true);
+                    } else if ui.input(|i| i.modifiers.command && i.key_pressed(Key::PageUp)) {
+                        self.load_adjacent_page(ctx, false);
+                    } else if ui.input(|i| i.modifiers.command && i.key_pressed(Key::ArrowRight)) {
+                        self.load_adjacent_folder(ctx, true);
+                    } else if ui.input(|i| i.modifiers.command && i.key_pressed(Key::ArrowLeft)) {
+                        self.load_adjacent_folder(ctx, false);
+                    } else if ui.input(|i| {
+                        i.key_pressed(forward_arrow)
                             || i.key_pressed(Key::PageDown)
                             || i.key_pressed(Key::Space)
                     }) {
                         self.load_adjacent_image(ctx, true);
                     } else if ui.input(|i| {
-                        i.key_pressed(Key::ArrowLeft)
+                        i.key_pressed(backward_arrow)
                             || i.key_pressed(Key::PageUp)
                             || i.key_pressed(Key::Backspace)
                     }) {
@@ -1890,7 +4821,13 @@ impl ImageViewer {
                     } else if ui.input(|i| i.key_pressed(Key::Home)) {
                         self.load_image_at(ctx, 0);
                     } else if ui.input(|i| i.key_pressed(Key::End)) {
-                        self.load_image_at(ctx, self.image_paths.len().saturating_sub(1));
+                        // 見開き表示では最後の見開きの先頭へ
+                        let last = if self.config.spread_view {
+                            self.adjacent_spread_start(0, false)
+                        } else {
+                            self.image_paths.len().saturating_sub(1)
+                        };
+                        self.load_image_at(ctx, last);
                     }
 
                     // マウスジェスチャー（右ドラッグ）で前後送り
@@ -1919,6 +4856,59 @@ impl ImageViewer {
                         }
                     }
 
+                    // ---- 反転（H=左右, V=上下。いま見えている向きに対して反転する）----
+                    if ui.input(|i| i.key_pressed(Key::H)) {
+                        self.flip = self.flip.toggle(true, self.rotation);
+                    }
+                    if ui.input(|i| i.key_pressed(Key::V)) {
+                        self.flip = self.flip.toggle(false, self.rotation);
+                    }
+
+                    // ---- カラーマネジメントの切り替え（C。変換あり／なしの見比べ用）----
+                    if ui.input(|i| i.key_pressed(Key::C)) {
+                        self.config.color_management = !self.config.color_management;
+                        self.reload_color_managed(ctx);
+                    }
+
+                    // ---- ピクセルインスペクターの切り替え（I）----
+                    if ui.input(|i| i.key_pressed(Key::I)) {
+                        self.config.pixel_inspector = !self.config.pixel_inspector;
+                    }
+
+                    // ---- 比較表示の A/B 切り替え（B。他のモードからはフリッカー表示にして B を出す）----
+                    if ui.input(|i| i.key_pressed(Key::B)) && !self.compare.is_empty() {
+                        if self.compare_mode == CompareMode::Flicker {
+                            self.show_b = !self.show_b;
+                        } else {
+                            self.compare_mode = CompareMode::Flicker;
+                            self.show_b = true;
+                            self.last_available_size = None;
+                        }
+                    }
+
+                    // ---- アニメーション操作（P=再生/一時停止, ,/.=コマ送り, [/]=速度）----
+                    if let Some(LoadedImage::Animated { playback, last_tick, .. }) =
+                        &mut self.current_image
+                    {
+                        if ui.input(|i| i.key_pressed(Key::P)) {
+                            playback.toggle_pause();
+                            // 再開時に停止中の時間をまとめて進めないよう計測をやり直す
+                            *last_tick = std::time::Instant::now();
+                        }
+                        if ui.input(|i| i.key_pressed(Key::Period)) {
+                            playback.step(true);
+                        }
+                        if ui.input(|i| i.key_pressed(Key::Comma)) {
+                            playback.step(false);
+                        }
+                        if ui.input(|i| i.key_pressed(Key::CloseBracket)) {
+                            playback.change_speed(true);
+                        }
+                        if ui.input(|i| i.key_pressed(Key::OpenBracket)) {
+                            playback.change_speed(false);
+                        }
+                    }
+
                     // ---- 表示リセット ----
                     if ui.input(|i| i.key_pressed(Key::F)) {
                         // Fキー：位置リセット＆フィットウィンドウ表示
@@ -1939,14 +4929,15 @@ impl ImageViewer {
                     }
 
                     // ---- ズーム（回転を考慮した表示サイズを基準に、アンカー位置固定で計算）----
-                    let base_size = self.display_base_size();
+                    // 比較表示ではアンカーのあるペインを基準にし、拡大率・パン位置は全ペインで共有する
+                    let (pane_rect, base_size) = self.pane_at(panel_rect, panel_rect.center());
                     if ui.input(|i| i.key_pressed(Key::Plus) || i.key_pressed(Key::Equals)) {
                         let new_scale = (self.scale * KEY_ZOOM_STEP).clamp(MIN_SCALE, MAX_SCALE);
-                        self.zoom_at(panel_rect.center().to_vec2(), &panel_rect, base_size, new_scale);
+                        self.zoom_at(pane_rect.center().to_vec2(), &pane_rect, base_size, new_scale);
                     }
                     if ui.input(|i| i.key_pressed(Key::Minus)) {
                         let new_scale = (self.scale / KEY_ZOOM_STEP).clamp(MIN_SCALE, MAX_SCALE);
-                        self.zoom_at(panel_rect.center().to_vec2(), &panel_rect, base_size, new_scale);
+                        self.zoom_at(pane_rect.center().to_vec2(), &pane_rect, base_size, new_scale);
                     }
 
                     let wheel_delta = ui.input(|i| i.raw_scroll_delta.y);
@@ -1954,11 +4945,9 @@ impl ImageViewer {
                         let factor = 1.0 + wheel_delta * self.config.wheel_zoom_factor;
                         let new_scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
                         // マウスカーソル位置を基準に拡大縮小
-                        let anchor = response
-                            .hover_pos()
-                            .map(|p| p.to_vec2())
-                            .unwrap_or_else(|| panel_rect.center().to_vec2());
-                        self.zoom_at(anchor, &panel_rect, base_size, new_scale);
+                        let anchor = response.hover_pos().unwrap_or_else(|| panel_rect.center());
+                        let (pane_rect, base_size) = self.pane_at(panel_rect, anchor);
+                        self.zoom_at(anchor.to_vec2(), &pane_rect, base_size, new_scale);
                     }
 
                     // ダブルクリックで フィット⇔100% をトグル
@@ -1971,9 +4960,23 @@ impl ImageViewer {
                         }
                     }
 
-                    // 左ドラッグでパン（右ドラッグはジェスチャー）
+                    // 左ドラッグでパン（右ドラッグはジェスチャー）。ワイプの境界線をつかんだときは境界線を動かす
+                    let wiping = self.compare_mode == CompareMode::Wipe && !self.compare.is_empty();
+                    let split_x = self.wipe_split_x(panel_rect);
+                    let near_split = |pos: Option<Pos2>| pos.is_some_and(|p| (p.x - split_x).abs() <= WIPE_GRAB_WIDTH);
+                    if wiping && (self.wipe_dragging || near_split(response.hover_pos())) {
+                        ctx.set_cursor_icon(egui::CursorIcon::ResizeHorizontal);
+                    }
+                    if response.drag_started() {
+                        self.wipe_dragging = wiping && near_split(ui.input(|i| i.pointer.press_origin()));
+                    }
                     if response.dragged() && !ui.input(|i| i.pointer.secondary_down()) {
-                        self.pan_offset += response.drag_delta();
+                        match response.interact_pointer_pos() {
+                            Some(pos) if self.wipe_dragging => {
+                                self.wipe_split = ((pos.x - panel_rect.left()) / panel_rect.width()).clamp(0.0, 1.0);
+                            }
+                            _ => self.pan_offset += response.drag_delta(),
+                        }
                     }
                 }
 
@@ -1982,159 +4985,86 @@ impl ImageViewer {
 
                 let scale = self.scale;
                 let rotation = self.rotation;
+                let flip = self.flip;
                 let pan = self.pan_offset;
                 let base_size = self.display_base_size();
-                let ppp = ctx.pixels_per_point();
-                let max_dim = ctx.input(|i| i.max_texture_side).max(1) as f32;
-
-                if let Some(image) = &mut self.current_image {
+                let draw_params = DrawParams {
+                    scale,
+                    rotation,
+                    flip,
+                    ppp: ctx.pixels_per_point(),
+                    max_dim: ctx.input(|i| i.max_texture_side).max(1) as f32,
+                    hdr_gamma: self.config.hdr_gamma,
+                    tone_map: self.config.tone_mapping,
+                    raster_options: self.raster_texture_options(),
+                };
+                let spread_layout = self.spread_layout(self.page_base_size());
+                let spread_texture = self.spread.as_ref().and_then(|s| s.texture.clone());
+
+                if !self.compare.is_empty() && self.compare_mode == CompareMode::SideBySide {
+                    self.draw_compare(ui, panel_rect, &draw_params);
+                } else if !self.compare.is_empty() {
+                    self.draw_overlay(ui, panel_rect, &draw_params);
+                } else if let Some(image) = &mut self.current_image {
                     let scaled_size = base_size * scale;
                     let origin = panel_rect.min + (panel_rect.size() - scaled_size) * 0.5 + pan;
-                    let image_rect = Rect::from_min_size(origin, scaled_size);
+                    let mut image_rect = Rect::from_min_size(origin, scaled_size);
+
+                    // 見開き表示: 2 ページを 1 枚として回転・反転し、それぞれの位置に描く
+                    if let (Some((unit, page, other)), Some(texture)) = (spread_layout, &spread_texture) {
+                        let to_view = |rect| {
+                            let [x, y, w, h] = map_image_rect_to_view(rotation, flip, unit.x, unit.y, rect);
+                            Rect::from_min_size(origin + egui::vec2(x, y) * scale, egui::vec2(w, h) * scale)
+                        };
+                        draw_texture_rotated(ui.painter(), texture, to_view(other), rotation, flip);
+                        image_rect = to_view(page);
+                    }
+                    self.last_image_rect = Some(image_rect);
 
-                    match image {
-                        LoadedImage::Raster { texture, .. } => {
-                            draw_texture_rotated(ui.painter(), texture, image_rect, rotation);
-                        }
-                        LoadedImage::Svg {
-                            size,
-                            texture,
-                            view,
-                            job_tx,
-                            result_rx,
-                            last_requested,
-                            ..
-                        } => {
-                            // SVG はベクターなので、可視領域（＋余白）だけを表示解像度ちょうどで
-                            // ラスタライズする（どんな拡大率でも 1px=1texel の鮮明さ）。
-                            // ラスタライズはワーカースレッドで行い、UI はブロックしない。
-                            // 完成までは手持ちのテクスチャを引き伸ばして表示する
-                            // （精密な SVG ではズーム中に一瞬ぼやけ、止まると鮮明になる）。
-
-                            // ワーカーからの完成テクスチャを受け取る（最後の 1 件だけ反映すれば十分）
-                            let mut arrived: Option<SvgRenderResult> = None;
-                            while let Ok(res) = result_rx.try_recv() {
-                                arrived = Some(res);
-                            }
-                            if let Some(res) = arrived {
-                                match texture.as_mut() {
-                                    Some(t) => t.set(res.image, egui::TextureOptions::LINEAR),
-                                    None => {
-                                        *texture = Some(ctx.load_texture(
-                                            "svg_view",
-                                            res.image,
-                                            egui::TextureOptions::LINEAR,
-                                        ))
-                                    }
-                                }
-                                *view = Some(res.view);
-                            }
+                    image.draw(ui, image_rect, panel_rect, &draw_params);
+                }
+                self.draw_grids(ui, panel_rect);
 
-                            let scale_px = scale * ppp;
-                            let visible = image_rect.intersect(panel_rect);
-                            if visible.is_positive() && scale_px > 0.0 {
-                                let (full_w, full_h) = rotated_dims(size[0], size[1], rotation);
-                                let full_w_px = full_w * scale_px;
-                                let full_h_px = full_h * scale_px;
-                                // 可視部分（画像原点基準の物理px）
-                                let nx0 = ((visible.min.x - image_rect.min.x) * ppp)
-                                    .floor()
-                                    .clamp(0.0, full_w_px);
-                                let ny0 = ((visible.min.y - image_rect.min.y) * ppp)
-                                    .floor()
-                                    .clamp(0.0, full_h_px);
-                                let nx1 = ((visible.max.x - image_rect.min.x) * ppp)
-                                    .ceil()
-                                    .clamp(0.0, full_w_px);
-                                let ny1 = ((visible.max.y - image_rect.min.y) * ppp)
-                                    .ceil()
-                                    .clamp(0.0, full_h_px);
-
-                                // このフレームで必要な crop（入力から決定的に計算される）
-                                let (tx, tw) =
-                                    crop_axis(nx0, nx1, full_w_px, SVG_RENDER_MARGIN_PX, max_dim);
-                                let (ty, th) =
-                                    crop_axis(ny0, ny1, full_h_px, SVG_RENDER_MARGIN_PX, max_dim);
-                                let target = SvgView {
-                                    scale_px,
-                                    rot: rotation,
-                                    crop: [tx, ty, tw, th],
-                                };
+                // スライドショーのクロスフェード: 次の画像の上に直前の画像を重ねて徐々に消す
+                if let Some(fade) = &mut self.fade {
+                    if fade.start.is_none() && !self.loader.is_loading() {
+                        fade.start = Some(std::time::Instant::now());
+                    }
+                    if let Some(start) = fade.start {
+                        let t = start.elapsed().as_secs_f32() / slideshow::FADE_DURATION.as_secs_f32();
+                        if t < 1.0 {
+                            let tint = Color32::from_white_alpha(((1.0 - t) * 255.0) as u8);
+                            draw_texture_tinted(ui.painter(), &fade.texture, fade.rect, fade.rotation, fade.flip, tint);
+                            ctx.request_repaint();
+                        } else {
+                            self.fade = None;
+                        }
+                    }
+                }
 
-                                // いまのテクスチャで十分か: スケール・回転が一致し、必要領域を
-                                // 丸ごと含む（パン余白内）か、必要 crop と一致（可視領域が GPU 上限を
-                                // 超えるケース）していれば追加のラスタライズは不要。
-                                let satisfied = texture.is_some()
-                                    && view.as_ref().map_or(false, |v| {
-                                        v.rot == rotation
-                                            && (v.scale_px - scale_px).abs() <= scale_px * 1e-4
-                                            && (v.crop == target.crop
-                                                || (v.crop[0] as f32 <= nx0
-                                                    && v.crop[1] as f32 <= ny0
-                                                    && (v.crop[0] + v.crop[2]) as f32 >= nx1
-                                                    && (v.crop[1] + v.crop[3]) as f32 >= ny1))
-                                    });
-
-                                // 足りなければワーカーに依頼（同一要求の重複送信はしない）
-                                if !satisfied && *last_requested != Some(target) {
-                                    let svg_crop = map_display_crop_to_svg(
-                                        rotation,
-                                        size[0] * scale_px,
-                                        size[1] * scale_px,
-                                        [
-                                            target.crop[0] as f32,
-                                            target.crop[1] as f32,
-                                            target.crop[2] as f32,
-                                            target.crop[3] as f32,
-                                        ],
-                                    );
-                                    let job = SvgRenderJob {
-                                        scale_px,
-                                        rot: rotation,
-                                        crop: target.crop,
-                                        svg_crop,
-                                    };
-                                    if job_tx.send(job).is_ok() {
-                                        *last_requested = Some(target);
-                                    }
-                                }
+                // スライドショーの一時停止中は操作方法を下部に出す
+                if self.slideshow.is_paused() {
+                    ui.painter().text(
+                        panel_rect.center_bottom() + egui::vec2(0.0, -24.0),
+                        egui::Align2::CENTER_BOTTOM,
+                        "Slideshow paused (S: resume / Esc: stop)",
+                        egui::FontId::proportional(14.0),
+                        Color32::from_white_alpha(200),
+                    );
+                }
 
-                                // 描画: テクスチャが保持する領域を現在のビューへ写像して描く。
-                                // スケールが一致していれば 1px=1texel の等倍描画。新しい結果が
-                                // まだ届いていない間は旧テクスチャが引き伸ばされる（ボケるが固まらない）。
-                                let mut drawn = false;
-                                if let (Some(t), Some(v)) = (texture.as_ref(), view.as_ref()) {
-                                    if v.rot == rotation && v.scale_px > 0.0 {
-                                        let factor = scale_px / v.scale_px;
-                                        let tex_rect = Rect::from_min_size(
-                                            image_rect.min
-                                                + egui::vec2(v.crop[0] as f32, v.crop[1] as f32)
-                                                    * factor
-                                                    / ppp,
-                                            egui::vec2(v.crop[2] as f32, v.crop[3] as f32) * factor
-                                                / ppp,
-                                        );
-                                        draw_texture_rotated(ui.painter(), t, tex_rect, v.rot);
-                                        drawn = true;
-                                    }
-                                }
+                // デコード待ちの間は前の画像を表示したまま、右上にスピナーを出す
+                if self.loader.is_loading() {
+                    let spinner_rect = Rect::from_center_size(
+                        panel_rect.right_top() + egui::vec2(-24.0, 24.0),
+                        Vec2::splat(18.0),
+                    );
+                    ui.put(spinner_rect, egui::Spinner::new());
+                }
 
-                                if !satisfied {
-                                    // レンダリング待ちを示すスピナー（未描画なら中央、描画済みなら右上に小さく）
-                                    let spinner_rect = if drawn {
-                                        Rect::from_center_size(
-                                            panel_rect.right_top() + egui::vec2(-24.0, 24.0),
-                                            Vec2::splat(18.0),
-                                        )
-                                    } else {
-                                        Rect::from_center_size(panel_rect.center(), Vec2::splat(32.0))
-                                    };
-                                    ui.put(spinner_rect, egui::Spinner::new());
-                                    // 結果の取りこぼし防止の保険（通常はワーカーが repaint を要求する）
-                                    ctx.request_repaint_after(std::time::Duration::from_millis(100));
-                                }
-                            }
-                        }
+                if self.config.pixel_inspector && !self.mouse_gesture.is_active {
+                    if let Some(cursor) = response.hover_pos() {
+                        self.show_pixel_inspector(ui, panel_rect, cursor);
                     }
                 }
 
@@ -2144,18 +5074,39 @@ impl ImageViewer {
                 }
             });
 
-        // タイトルバーに [位置/総数] サイズ 回転 拡大率 ファイルパスを表示（変化時のみ送信）
+        // タイトルバーに [位置/総数] ページ サイズ 色変換 アニメ状態 回転 拡大率 ファイルパスを表示（変化時のみ送信）
+        if let Some(image) = &self.current_image {
+            let path = image.path();
+            if self.title_path.as_ref().is_none_or(|(p, _)| p != path) {
+                self.title_path = Some((path.to_path_buf(), archive::display(path)));
+            }
+        }
         let title = if let Some(image) = &self.current_image {
-            let path = match image {
-                LoadedImage::Raster { path, .. } => path,
-                LoadedImage::Svg { path, .. } => path,
-            };
+            let path_label = self.title_path.as_ref().map_or("", |(_, label)| label.as_str());
+            // 見開き表示で 2 ページ並べているときは [3-4/120] のように出す
             let pos_str = self
                 .current_path
                 .as_ref()
                 .and_then(|p| self.image_paths.iter().position(|x| x == p))
-                .map(|i| format!("[{}/{}] ", i + 1, self.image_paths.len()))
+                .map(|i| match &self.spread {
+                    Some(SpreadPage { texture: Some(_), .. }) => {
+                        format!("[{}-{}/{}] ", i + 1, i + 2, self.image_paths.len())
+                    }
+                    _ => format!("[{}/{}] ", i + 1, self.image_paths.len()),
+                })
                 .unwrap_or_default();
+            let page_str = if self.page_info.count > 1 {
+                format!("page {}/{} ", self.page + 1, self.page_info.count)
+            } else {
+                String::new()
+            };
+            // マルチレイヤー EXR またはチャンネル表示中は、表示中のレイヤー名を出す
+            let layer_str = match &self.exr {
+                Some(file) if file.layers.len() > 1 || self.exr_selection.channel.is_some() => {
+                    format!("<{}> ", file.label(self.exr_selection))
+                }
+                _ => String::new(),
+            };
             let dims = self
                 .image_size
                 .map(|s| format!("{}x{} ", s[0], s[1]))
@@ -2166,13 +5117,53 @@ impl ImageViewer {
                 3 => "270° ",
                 _ => "",
             };
+            // 反転は画像空間の軸で表示する
+            let flip = match (self.flip.h, self.flip.v) {
+                (true, true) => "H+V-flip ",
+                (true, false) => "H-flip ",
+                (false, true) => "V-flip ",
+                (false, false) => "",
+            };
+            // 埋め込みプロファイルがある場合だけ色変換の状態を表示する
+            let icc = match &self.icc_state {
+                color::IccState::None => String::new(),
+                color::IccState::Applied(name) => format!("ICC:{} ", name),
+                color::IccState::Unmanaged(_) => "ICC off ".to_string(),
+                color::IccState::Failed(_) => "ICC error ".to_string(),
+            };
+            // アニメーションは 再生状態 フレーム番号/総数 速度 を表示
+            let anim = match image {
+                LoadedImage::Animated { playback, .. } => format!(
+                    "{} {}/{} x{} ",
+                    if playback.paused() { "⏸" } else { "▶" },
+                    playback.current() + 1,
+                    playback.frame_count(),
+                    playback.speed()
+                ),
+                // HDR は 露出 トーンマッピング方式 を表示
+                LoadedImage::Hdr { exposure, .. } => {
+                    format!("EV{:+.1} {} ", exposure, self.config.tone_mapping.name())
+                }
+                _ => String::new(),
+            };
+            let slide = match (self.slideshow.is_active(), self.slideshow.is_paused()) {
+                (true, false) => "▶ slideshow ",
+                (true, true) => "⏸ slideshow ",
+                _ => "",
+            };
             format!(
-                "MSBT-yuina - {}{}{}{}% - {}",
+                "MSBT-yuina - {}{}{}{}{}{}{}{}{}{}% - {}",
+                slide,
                 pos_str,
+                page_str,
+                layer_str,
                 dims,
+                icc,
+                anim,
                 rot,
+                flip,
                 (self.scale * 100.0).round() as i32,
-                path.display()
+                path_label
             )
         } else {
             "MSBT-yuina".to_string()
@@ -2183,6 +5174,195 @@ impl ImageViewer {
         }
     }
 
+    /// 画面下部のフィルムストリップ（フォルダ内のサムネイルを横一列に並べ、表示中の画像を中央に寄せる）
+    fn show_filmstrip(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("filmstrip")
+            .exact_height(FILMSTRIP_CELL + 12.0)
+            .show(ctx, |ui| {
+                let mut area = egui::ScrollArea::horizontal().auto_shrink(false);
+                if self.filmstrip_synced != self.current_path {
+                    if let Some(index) = self.current_index() {
+                        let offset = index as f32 * FILMSTRIP_CELL
+                            - (ui.available_width() - FILMSTRIP_CELL) * 0.5;
+                        area = area.horizontal_scroll_offset(offset.max(0.0));
+                    }
+                    self.filmstrip_synced = self.current_path.clone();
+                }
+                let mut clicked = None;
+                area.show_viewport(ui, |ui, viewport| {
+                    let count = self.image_paths.len();
+                    ui.set_min_size(Vec2::new(count as f32 * FILMSTRIP_CELL, FILMSTRIP_CELL));
+                    let origin = ui.max_rect().min;
+                    let first = (viewport.min.x / FILMSTRIP_CELL).floor().max(0.0) as usize;
+                    let last = ((viewport.max.x / FILMSTRIP_CELL).ceil() as usize).min(count);
+                    for i in first..last {
+                        let rect = Rect::from_min_size(
+                            origin + Vec2::new(i as f32 * FILMSTRIP_CELL, 0.0),
+                            Vec2::splat(FILMSTRIP_CELL),
+                        );
+                        if self.thumbnail_cell(ui, i, rect, false) {
+                            clicked = Some(i);
+                        }
+                    }
+                });
+                if let Some(i) = clicked {
+                    // Ctrl+クリックは比較表示に追加する
+                    if ctx.input(|i| i.modifiers.command) {
+                        self.add_compare_pane(self.image_paths[i].clone(), ctx);
+                    } else {
+                        self.load_image_at(ctx, i);
+                    }
+                }
+            });
+    }
+
+    /// グリッド表示（フォルダ内のサムネイルをウィンドウ全体に並べる）。
+    /// クリックまたは Enter でその画像を開いて通常表示に戻る
+    fn show_grid(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let panel_rect = ui.available_rect_before_wrap();
+        ui.painter().rect_filled(panel_rect, 0.0, Color32::from_gray(24));
+        let count = self.image_paths.len();
+        let cols = ((panel_rect.width() / GRID_CELL).floor() as usize).max(1);
+        let rows = count.div_ceil(cols);
+
+        // ←→ は 1 つ、↑↓ は 1 行ずつ選択を動かす（選択は表示中の画像そのもの）
+        if let Some(index) = self.current_index() {
+            let target = ui.input(|i| {
+                if i.key_pressed(Key::ArrowRight) {
+                    Some((index + 1).min(count - 1))
+                } else if i.key_pressed(Key::ArrowLeft) {
+                    Some(index.saturating_sub(1))
+                } else if i.key_pressed(Key::ArrowDown) {
+                    Some((index + cols).min(count - 1))
+                } else if i.key_pressed(Key::ArrowUp) {
+                    Some(index.saturating_sub(cols))
+                } else {
+                    None
+                }
+            });
+            if let Some(target) = target {
+                self.load_image_at(ctx, target);
+            }
+        }
+        if ui.input(|i| i.key_pressed(Key::Enter)) {
+            self.grid_view = false;
+        }
+
+        let mut area = egui::ScrollArea::vertical().auto_shrink(false);
+        if self.grid_synced != self.current_path {
+            if let Some(index) = self.current_index() {
+                let offset =
+                    (index / cols) as f32 * GRID_CELL - (panel_rect.height() - GRID_CELL) * 0.5;
+                area = area.vertical_scroll_offset(offset.max(0.0));
+            }
+            self.grid_synced = self.current_path.clone();
+        }
+        let mut clicked = None;
+        area.show_viewport(ui, |ui, viewport| {
+            ui.set_min_size(Vec2::new(cols as f32 * GRID_CELL, rows as f32 * GRID_CELL));
+            let origin = ui.max_rect().min;
+            let first = (viewport.min.y / GRID_CELL).floor().max(0.0) as usize;
+            let last = ((viewport.max.y / GRID_CELL).ceil() as usize).min(rows);
+            for row in first..last {
+                for col in 0..cols {
+                    let i = row * cols + col;
+                    if i >= count {
+                        break;
+                    }
+                    let rect = Rect::from_min_size(
+                        origin + Vec2::new(col as f32, row as f32) * GRID_CELL,
+                        Vec2::splat(GRID_CELL),
+                    );
+                    if self.thumbnail_cell(ui, i, rect, true) {
+                        clicked = Some(i);
+                    }
+                }
+            }
+        });
+        if let Some(i) = clicked {
+            if ctx.input(|i| i.modifiers.command) {
+                self.add_compare_pane(self.image_paths[i].clone(), ctx);
+            } else {
+                self.load_image_at(ctx, i);
+            }
+            self.grid_view = false;
+        }
+    }
+
+    /// サムネイルのセルを 1 つ描く（with_name ならファイル名も下に出す）。クリックされたら true
+    fn thumbnail_cell(&mut self, ui: &mut egui::Ui, index: usize, rect: Rect, with_name: bool) -> bool {
+        let path = &self.image_paths[index];
+        let response = ui.interact(rect, ui.id().with(("thumbnail", index)), egui::Sense::click());
+        let painter = ui.painter();
+        let background = if self.current_path.as_ref() == Some(path) {
+            ui.visuals().selection.bg_fill
+        } else if response.hovered() {
+            Color32::from_gray(64)
+        } else {
+            Color32::from_gray(40)
+        };
+        painter.rect_filled(rect.shrink(2.0), 4.0, background);
+
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name_height = if with_name { 18.0 } else { 0.0 };
+        let image_area = Rect::from_min_max(rect.min, rect.max - Vec2::new(0.0, name_height)).shrink(6.0);
+        match self.thumbnails.get(path) {
+            thumbs::Thumb::Ready(texture) => {
+                let size = texture.size_vec2();
+                let fit = (image_area.width() / size.x).min(image_area.height() / size.y);
+                painter.image(
+                    texture.id(),
+                    Rect::from_center_size(image_area.center(), size * fit),
+                    Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                    Color32::WHITE,
+                );
+            }
+            thumbs::Thumb::Pending => {}
+            thumbs::Thumb::Failed => {
+                painter.text(
+                    image_area.center(),
+                    egui::Align2::CENTER_CENTER,
+                    "?",
+                    egui::FontId::proportional(24.0),
+                    Color32::GRAY,
+                );
+            }
+        }
+        if with_name {
+            let mut job = egui::text::LayoutJob::simple_singleline(
+                name.clone(),
+                egui::FontId::proportional(11.0),
+                Color32::LIGHT_GRAY,
+            );
+            job.wrap = egui::text::TextWrapping::truncate_at_width(rect.width() - 12.0);
+            let galley = painter.layout_job(job);
+            let pos = Pos2::new(
+                rect.center().x - galley.size().x * 0.5,
+                rect.max.y - name_height - 2.0,
+            );
+            painter.galley(pos, galley, Color32::LIGHT_GRAY);
+        }
+        response.on_hover_text(name).clicked()
+    }
+
+    /// 前後へ移るときの起点（画像とページ）。デコード待ちの要求があればそれを起点にして、
+    /// 連打したぶんだけ先へ進めるようにする。なければ表示中の画像
+    fn navigation_origin(&self) -> Option<(PathBuf, usize)> {
+        self.loader
+            .wanted()
+            .cloned()
+            .or_else(|| Some((self.current_path.clone()?, self.page)))
+    }
+
+    /// 表示中の画像の image_paths 内の位置
+    fn current_index(&self) -> Option<usize> {
+        let current = self.current_path.as_ref()?;
+        self.image_paths.iter().position(|p| p == current)
+    }
+
     /// チェッカーボード風の背景を描画
     fn draw_checker_background(&self, ui: &mut egui::Ui) {
         let rect = ui.max_rect();
@@ -2213,17 +5393,37 @@ impl eframe::App for ImageViewer {
     }
 }
 
+/// 拡張子が SVG（.svg / .svgz）かどうか
+fn is_svg_path(path: &Path) -> bool {
+    path.extension().is_some_and(|e| {
+        let ext = e.to_string_lossy().to_lowercase();
+        ext == "svg" || ext == "svgz"
+    })
+}
+
 /// 画像を Windows の WIC（OS が持つ画像コーデック）でデコードして RGBA 画像を返す。
 /// image クレートが非対応の形式（HEIC/HEIF/AVIF/JPEG XR/カメラRAW 等）のフォールバックに使う。
 /// 形式によっては Microsoft Store の拡張機能（例: HEIF 画像拡張機能 / AV1 ビデオ拡張機能）が必要。
-#[cfg(windows)]
 fn decode_via_wic(path: &Path) -> Result<image::DynamicImage, String> {
+    decode_via_wic_frame(path, 0)
+}
+
+/// WIC のファクトリとデコーダを生成する（フォーマット変換器の生成にファクトリも要るので組で返す）
+#[cfg(windows)]
+unsafe fn wic_decoder(
+    path: &Path,
+) -> Result<
+    (
+        windows::Win32::Graphics::Imaging::IWICImagingFactory,
+        windows::Win32::Graphics::Imaging::IWICBitmapDecoder,
+    ),
+    String,
+> {
     use std::os::windows::ffi::OsStrExt;
     use windows::core::PCWSTR;
     use windows::Win32::Foundation::GENERIC_READ;
     use windows::Win32::Graphics::Imaging::{
-        CLSID_WICImagingFactory, GUID_WICPixelFormat32bppRGBA, IWICImagingFactory,
-        WICBitmapDitherTypeNone, WICBitmapPaletteTypeCustom, WICDecodeMetadataCacheOnDemand,
+        CLSID_WICImagingFactory, IWICImagingFactory, WICDecodeMetadataCacheOnDemand,
     };
     use windows::Win32::System::Com::{
         CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
@@ -2235,27 +5435,49 @@ fn decode_via_wic(path: &Path) -> Result<image::DynamicImage, String> {
         .chain(std::iter::once(0))
         .collect();
 
+    // winit が COM を初期化済みのはずだが念のため。初期化済み／モード差異のエラーは無視。
+    let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+
+    let factory: IWICImagingFactory =
+        CoCreateInstance(&CLSID_WICImagingFactory, None, CLSCTX_INPROC_SERVER)
+            .map_err(|e| format!("WIC ファクトリの生成に失敗: {e}"))?;
+
+    let decoder = factory
+        .CreateDecoderFromFilename(
+            PCWSTR(wide.as_ptr()),
+            None,
+            GENERIC_READ,
+            WICDecodeMetadataCacheOnDemand,
+        )
+        .map_err(|e| {
+            format!("この形式をデコードできません（対応コーデック/拡張機能が未導入の可能性）: {e}")
+        })?;
+    Ok((factory, decoder))
+}
+
+/// WIC で開いたときのフレーム数（マルチページ TIFF・DDS 配列・HEIF コレクション等）
+#[cfg(windows)]
+fn wic_frame_count(path: &Path) -> Result<u32, String> {
     unsafe {
-        // winit が COM を初期化済みのはずだが念のため。初期化済み／モード差異のエラーは無視。
-        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        let (_factory, decoder) = wic_decoder(path)?;
+        decoder
+            .GetFrameCount()
+            .map_err(|e| format!("フレーム数の取得に失敗: {e}"))
+    }
+}
 
-        let factory: IWICImagingFactory =
-            CoCreateInstance(&CLSID_WICImagingFactory, None, CLSCTX_INPROC_SERVER)
-                .map_err(|e| format!("WIC ファクトリの生成に失敗: {e}"))?;
+/// WIC で index 番目のフレームをデコードする
+#[cfg(windows)]
+fn decode_via_wic_frame(path: &Path, index: u32) -> Result<image::DynamicImage, String> {
+    use windows::Win32::Graphics::Imaging::{
+        GUID_WICPixelFormat32bppRGBA, WICBitmapDitherTypeNone, WICBitmapPaletteTypeCustom,
+    };
 
-        let decoder = factory
-            .CreateDecoderFromFilename(
-                PCWSTR(wide.as_ptr()),
-                None,
-                GENERIC_READ,
-                WICDecodeMetadataCacheOnDemand,
-            )
-            .map_err(|e| {
-                format!("この形式をデコードできません（対応コーデック/拡張機能が未導入の可能性）: {e}")
-            })?;
+    unsafe {
+        let (factory, decoder) = wic_decoder(path)?;
 
         let frame = decoder
-            .GetFrame(0)
+            .GetFrame(index)
             .map_err(|e| format!("フレーム取得に失敗: {e}"))?;
 
         // どのピクセル形式の HEIC でも 32bpp RGBA に変換してから取り出す。
@@ -2298,7 +5520,12 @@ fn decode_via_wic(path: &Path) -> Result<image::DynamicImage, String> {
 
 /// 非 Windows では WIC フォールバック非対応（このアプリは Windows 専用だが、cfg を明示しておく）。
 #[cfg(not(windows))]
-fn decode_via_wic(_path: &Path) -> Result<image::DynamicImage, String> {
+fn decode_via_wic_frame(_path: &Path, _index: u32) -> Result<image::DynamicImage, String> {
+    Err("この形式は Windows でのみ対応しています".into())
+}
+
+#[cfg(not(windows))]
+fn wic_frame_count(_path: &Path) -> Result<u32, String> {
     Err("この形式は Windows でのみ対応しています".into())
 }
 
@@ -2404,6 +5631,199 @@ mod tests {
         );
     }
 
+    #[test]
+    fn crop_mapping_with_flip_full_rect_is_identity() {
+        let (ws, hs) = (100.0, 50.0);
+        for rot in 0..4u8 {
+            for (h, v) in [(false, false), (true, false), (false, true), (true, true)] {
+                let (dw, dh) = rotated_dims(ws, hs, rot);
+                let mapped = map_view_crop_to_svg(rot, Flip { h, v }, ws, hs, [0.0, 0.0, dw, dh]);
+                assert_eq!(mapped, [0.0, 0.0, ws, hs], "rot={rot} h={h} v={v}");
+            }
+        }
+    }
+
+    #[test]
+    fn crop_mapping_with_flip_corners() {
+        let (ws, hs) = (100.0, 50.0);
+        let h = Flip { h: true, v: false };
+        let v = Flip { h: false, v: true };
+        // 左右反転のみ: 表示の左上の小片は SVG の右上
+        assert_eq!(
+            map_view_crop_to_svg(0, h, ws, hs, [0.0, 0.0, 10.0, 20.0]),
+            [90.0, 0.0, 10.0, 20.0]
+        );
+        // 上下反転のみ: 表示の左上は SVG の左下
+        assert_eq!(
+            map_view_crop_to_svg(0, v, ws, hs, [0.0, 0.0, 10.0, 20.0]),
+            [0.0, 30.0, 10.0, 20.0]
+        );
+        // rot=1 ＋ 左右反転: 回転だけなら左下（[0,40]）だが、左右反転で右下になる
+        assert_eq!(
+            map_view_crop_to_svg(1, h, ws, hs, [0.0, 0.0, 10.0, 20.0]),
+            [80.0, 40.0, 20.0, 10.0]
+        );
+        // 両方反転は 180° 回転と同じ
+        assert_eq!(
+            map_view_crop_to_svg(0, Flip { h: true, v: true }, ws, hs, [0.0, 0.0, 10.0, 20.0]),
+            map_display_crop_to_svg(2, ws, hs, [0.0, 0.0, 10.0, 20.0])
+        );
+    }
+
+    /// タイルの配置に使う逆写像が、すべての回転・反転で crop の写像と往復一致すること
+    #[test]
+    fn image_rect_to_view_inverts_crop_mapping() {
+        let (ws, hs) = (100.0, 50.0);
+        let rect = [10.0, 5.0, 30.0, 20.0];
+        for rot in 0..4u8 {
+            for (h, v) in [(false, false), (true, false), (false, true), (true, true)] {
+                let flip = Flip { h, v };
+                let view = map_image_rect_to_view(rot, flip, ws, hs, rect);
+                assert_eq!(map_view_crop_to_svg(rot, flip, ws, hs, view), rect, "rot={rot} {flip:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn grid_positions_are_step_multiples_in_range() {
+        let lines: Vec<f32> = grid_positions(0.5, 33.0, 16.0).collect();
+        assert_eq!(lines, [16.0, 32.0]);
+        let lines: Vec<f32> = grid_positions(0.0, 2.0, 1.0).collect();
+        assert_eq!(lines, [0.0, 1.0, 2.0]);
+        assert_eq!(grid_positions(3.2, 3.8, 1.0).count(), 0);
+    }
+
+    /// ピクセルインスペクター: 画面上の画素の枠の中心が、回転・反転・拡大しても元の画素に戻ること
+    #[test]
+    fn screen_to_image_finds_pixel_under_cursor() {
+        let size = Vec2::new(10.0, 4.0);
+        for rot in 0..4u8 {
+            for (h, v) in [(false, false), (true, false), (false, true), (true, true)] {
+                let flip = Flip { h, v };
+                let (vw, vh) = rotated_dims(size.x, size.y, rot);
+                let image_rect = Rect::from_min_size(egui::pos2(-30.0, 12.0), Vec2::new(vw, vh) * 8.0);
+                let cell = image_rect_to_screen([3.0, 1.0, 1.0, 1.0], image_rect, size, rot, flip);
+                assert_eq!(cell.size(), Vec2::splat(8.0));
+                let pos = screen_to_image(cell.center(), image_rect, size, rot, flip);
+                assert_eq!((pos.x, pos.y), (3.5, 1.5), "rot={rot} {flip:?}");
+            }
+        }
+    }
+
+    /// テクスチャ描画（UV）と SVG の crop 写像が同じ変換を表していること:
+    /// 表示の左上隅に UV が割り当てる画像上の点は、表示左上の小片を写像した矩形の角にある
+    #[test]
+    fn texture_uvs_agree_with_crop_mapping() {
+        let (ws, hs) = (100.0, 50.0);
+        for rot in 0..4u8 {
+            for (h, v) in [(false, false), (true, false), (false, true), (true, true)] {
+                let flip = Flip { h, v };
+                let uv = texture_uvs(rot, flip)[0];
+                let p = (uv.x * ws, uv.y * hs);
+                let [x, y, w, hh] = map_view_crop_to_svg(rot, flip, ws, hs, [0.0, 0.0, 1.0, 1.0]);
+                let on_x = p.0 == x || p.0 == x + w;
+                let on_y = p.1 == y || p.1 == y + hh;
+                assert!(on_x && on_y, "rot={rot} flip={flip:?}: uv点 {p:?} が矩形 {:?} の角にない", [x, y, w, hh]);
+            }
+        }
+    }
+
+    #[test]
+    fn pane_rects_split_the_panel_evenly() {
+        let panel = Rect::from_min_size(egui::pos2(10.0, 20.0), Vec2::new(300.0, 100.0));
+        assert_eq!(pane_rects(panel, 1), [panel]);
+        let rects = pane_rects(panel, 3);
+        assert_eq!(rects.len(), 3);
+        assert_eq!(rects[0], Rect::from_min_size(egui::pos2(10.0, 20.0), Vec2::new(100.0, 100.0)));
+        assert_eq!(rects[2], Rect::from_min_size(egui::pos2(210.0, 20.0), Vec2::new(100.0, 100.0)));
+        // 隙間なく並び、全体でパネルを覆う
+        assert_eq!(rects[0].max.x, rects[1].min.x);
+        assert_eq!(rects[1].max.x, rects[2].min.x);
+        assert_eq!(rects[2].max, panel.max);
+    }
+
+    #[test]
+    fn pane_index_finds_the_pane_under_the_pointer() {
+        let panel = Rect::from_min_size(egui::pos2(0.0, 0.0), Vec2::new(300.0, 100.0));
+        let rects = pane_rects(panel, 3);
+        assert_eq!(pane_index(&rects, egui::pos2(50.0, 50.0)), 0);
+        assert_eq!(pane_index(&rects, egui::pos2(150.0, 50.0)), 1);
+        assert_eq!(pane_index(&rects, egui::pos2(299.0, 99.0)), 2);
+        // パネルの外は表示中の画像のペイン
+        assert_eq!(pane_index(&rects, egui::pos2(500.0, 50.0)), 0);
+        assert_eq!(pane_index(&rects, egui::pos2(150.0, -1.0)), 0);
+    }
+
+    #[test]
+    fn fit_scale_fits_the_largest_image_into_one_pane() {
+        let avail = Vec2::new(800.0, 400.0);
+        // 1 ペイン: 幅で決まる
+        assert_eq!(fit_scale(avail, 1, &[Vec2::new(1600.0, 400.0)]), Some(0.5));
+        // 2 ペイン（400x400 ずつ）: いちばん大きい画像（幅 800）が収まる
+        let bases = [Vec2::new(200.0, 100.0), Vec2::new(800.0, 200.0)];
+        assert_eq!(fit_scale(avail, 2, &bases), Some(0.5));
+        // 高さで決まる
+        let bases = [Vec2::new(100.0, 800.0), Vec2::new(100.0, 100.0)];
+        assert_eq!(fit_scale(avail, 2, &bases), Some(0.5));
+        // 読み込み中（大きさ 0）のペインは無視する
+        assert_eq!(fit_scale(avail, 2, &[Vec2::ZERO, Vec2::new(200.0, 200.0)]), Some(2.0));
+        assert_eq!(fit_scale(avail, 2, &[Vec2::ZERO]), None);
+        assert_eq!(fit_scale(Vec2::ZERO, 1, &[Vec2::new(10.0, 10.0)]), None);
+    }
+
+    /// 差分モード: HDR は表示中の露出でトーンマッピングした画素どうしを比べる
+    #[test]
+    fn difference_uses_the_current_exposure() {
+        let image = hdr::HdrImage::new(image::Rgba32FImage::from_pixel(4, 2, image::Rgba([0.25, 0.5, 0.125, 1.0])));
+        let params = |exposure| hdr::ToneParams {
+            exposure,
+            gamma: default_hdr_gamma(),
+            tone_map: hdr::ToneMap::default(),
+        };
+        let shown = DifferenceSource::Color(Some(Arc::new(image.render(params(2.0)))));
+        let same = compute_difference(&DifferenceSource::Hdr(image.clone(), params(2.0)), &shown).unwrap();
+        assert_eq!(same.changed, 0);
+        let initial = compute_difference(&DifferenceSource::Hdr(image, params(0.0)), &shown).unwrap();
+        assert_eq!(initial.changed, 8);
+    }
+
+    #[test]
+    fn slideshow_interval_is_clamped_to_a_usable_duration() {
+        assert_eq!(clamp_slideshow_interval(5.0), 5.0);
+        assert_eq!(clamp_slideshow_interval(0.0), MIN_SLIDESHOW_INTERVAL);
+        assert_eq!(clamp_slideshow_interval(-3.0), MIN_SLIDESHOW_INTERVAL);
+        assert_eq!(clamp_slideshow_interval(1e30), MAX_SLIDESHOW_INTERVAL);
+        assert_eq!(clamp_slideshow_interval(f32::INFINITY), MAX_SLIDESHOW_INTERVAL);
+        assert_eq!(clamp_slideshow_interval(f32::NEG_INFINITY), MIN_SLIDESHOW_INTERVAL);
+        assert_eq!(clamp_slideshow_interval(f32::NAN), default_slideshow_interval());
+        // from_secs_f32 が panic しない
+        let _ = std::time::Duration::from_secs_f32(clamp_slideshow_interval(f32::INFINITY));
+    }
+
+    /// 保存した設定ファイルを読み直せること（ICC プロファイルのパスは制御文字や引用符を含み得る）
+    #[test]
+    fn saved_config_parses_back() {
+        let config = ViewerConfig {
+            output_icc_profile: "C:\\Profiles\\\"wide\" \u{1}\u{7f}ß.icc".to_string(),
+            ..ViewerConfig::default()
+        };
+        let parsed: ViewerConfig = toml::from_str(&config.to_toml()).unwrap();
+        assert_eq!(parsed.output_icc_profile, config.output_icc_profile);
+        assert_eq!(parsed.slideshow_interval, config.slideshow_interval);
+        assert_eq!(parsed.histogram_dock, config.histogram_dock);
+    }
+
+    #[test]
+    fn flip_toggle_follows_visible_axis() {
+        // 回転なし: 表示の左右＝画像の左右
+        assert_eq!(Flip::default().toggle(true, 0), Flip { h: true, v: false });
+        // 90° 回転中: 表示の左右反転は画像の上下反転
+        assert_eq!(Flip::default().toggle(true, 1), Flip { h: false, v: true });
+        assert_eq!(Flip::default().toggle(false, 3), Flip { h: true, v: false });
+        // 2 回押すと元に戻る
+        assert_eq!(Flip::default().toggle(false, 2).toggle(false, 2), Flip::default());
+    }
+
     #[test]
     fn crop_axis_covers_visible_and_respects_max() {
         // 通常ケース: 可視区間＋余白、画像端でクランプ
@@ -2680,6 +6100,7 @@ mod tests {
         let job = SvgRenderJob {
             scale_px: 2.0,
             rot: 0,
+            flip: Flip::default(),
             crop: [0, 0, 200, 200],
             svg_crop: [0.0, 0.0, 200.0, 200.0],
         };
@@ -2730,6 +6151,7 @@ mod tests {
             let job = SvgRenderJob {
                 scale_px: scale,
                 rot: 0,
+                flip: Flip::default(),
                 crop,
                 svg_crop: [crop[0] as f32, crop[1] as f32, crop[2] as f32, crop[3] as f32],
             };
@@ -2762,6 +6184,7 @@ mod tests {
             let job = SvgRenderJob {
                 scale_px: scale,
                 rot: 0,
+                flip: Flip::default(),
                 crop,
                 svg_crop: [crop[0] as f32, crop[1] as f32, crop[2] as f32, crop[3] as f32],
             };
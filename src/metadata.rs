@@ -0,0 +1,142 @@
+//! EXIF メタデータの読み取り。
+//!
+//! kamadak-exif はコンテナ（JPEG / TIFF / HEIF / PNG / WebP）から EXIF を直接取り出せるので、
+//! image クレート・WIC どちらでデコードした画像にも同じ情報を使える。
+
+use image::metadata::Orientation;
+use std::path::Path;
+
+fn read_exif(path: &Path) -> Option<exif::Exif> {
+    let mut source = crate::archive::open(path).ok()?;
+    exif::Reader::new().read_from_container(&mut source).ok()
+}
+
+/// EXIF の Orientation タグ（1〜8、左右反転を含む）を読む。
+/// タグが無い・読めない・1（無変換）の場合は None
+pub fn read_orientation(path: &Path) -> Option<Orientation> {
+    let exif = read_exif(path)?;
+    let value = exif
+        .get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+        .value
+        .get_uint(0)?;
+    match Orientation::from_exif(u8::try_from(value).ok()?)? {
+        Orientation::NoTransforms => None,
+        o => Some(o),
+    }
+}
+
+/// 撮影日時（DateTimeOriginal、無ければ DateTime）を "YYYY:MM:DD HH:MM:SS" の形で読む。
+/// この形式は文字列のまま比較すれば時刻順になる
+pub fn read_capture_date(path: &Path) -> Option<String> {
+    let exif = read_exif(path)?;
+    let field = exif
+        .get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)
+        .or_else(|| exif.get_field(exif::Tag::DateTime, exif::In::PRIMARY))?;
+    match &field.value {
+        exif::Value::Ascii(values) => {
+            let text = String::from_utf8_lossy(values.first()?).trim().to_string();
+            // 空や "0000:00:00 00:00:00"（未設定）は日時なし扱い
+            (!text.is_empty() && !text.starts_with("0000")).then_some(text)
+        }
+        _ => None,
+    }
+}
+
+/// テスト用: JPEG の SOI の直後に、fields を持つ EXIF（APP1）を差し込む
+#[cfg(test)]
+pub(crate) fn with_exif(jpeg: &[u8], fields: &[exif::Field]) -> Vec<u8> {
+    let mut writer = exif::experimental::Writer::new();
+    for field in fields {
+        writer.push_field(field);
+    }
+    let mut tiff = std::io::Cursor::new(Vec::new());
+    writer.write(&mut tiff, false).unwrap();
+    let payload = [&b"Exif\0\0"[..], tiff.get_ref()].concat();
+    let mut out = jpeg[..2].to_vec();
+    out.extend_from_slice(&[0xFF, 0xE1]);
+    out.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+    out.extend_from_slice(&payload);
+    out.extend_from_slice(&jpeg[2..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// EXIF の Orientation 付きの JPEG を実際に書き出し、向きの読み取りと、
+    /// 読み込んだ画素が補正されていることを確かめる。
+    /// 元の画像は 4 つの象限 [A B / C D]（32x16、JPEG のブロックに揃えた濃さの違う灰色）
+    #[test]
+    fn jpeg_orientation_is_read_and_applied_when_loading() {
+        use crate::{hdr, loader};
+        use std::sync::Arc;
+
+        let [a, b, c, d] = [0u8, 80, 160, 240];
+        let src = image::GrayImage::from_fn(32, 16, |x, y| {
+            image::Luma([match (x < 16, y < 8) {
+                (true, true) => a,
+                (false, true) => b,
+                (true, false) => c,
+                (false, false) => d,
+            }])
+        });
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::ImageLuma8(src)
+            .write_to(&mut jpeg, image::ImageFormat::Jpeg)
+            .unwrap();
+        let settings = loader::DecodeSettings {
+            auto_orient: true,
+            color_management: true,
+            output_profile: Arc::new(moxcms::ColorProfile::new_srgb()),
+            max_texture_side: 8192,
+            texture_options: eframe::egui::TextureOptions::LINEAR,
+            hdr_gamma: 2.2,
+            tone_mapping: hdr::ToneMap::default(),
+            auto_exposure: true,
+        };
+        let dir = std::env::temp_dir().join(format!("msbt-orientation-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // 補正後の大きさと、左上・右上・左下・右下の象限の濃さ
+        let cases = [
+            (1, [32, 16], [a, b, c, d]),
+            (2, [32, 16], [b, a, d, c]), // 左右反転
+            (3, [32, 16], [d, c, b, a]), // 180°
+            (4, [32, 16], [c, d, a, b]), // 上下反転
+            (5, [16, 32], [a, c, b, d]), // 転置（左上を軸に反転）
+            (6, [16, 32], [c, a, d, b]), // 時計回り 90°
+            (7, [16, 32], [d, b, c, a]), // 反転置
+            (8, [16, 32], [b, d, a, c]), // 反時計回り 90°
+        ];
+        for (exif_value, size, quadrants) in cases {
+            let field = exif::Field {
+                tag: exif::Tag::Orientation,
+                ifd_num: exif::In::PRIMARY,
+                value: exif::Value::Short(vec![exif_value]),
+            };
+            let path = dir.join(format!("o{exif_value}.jpg"));
+            std::fs::write(&path, with_exif(jpeg.get_ref(), &[field])).unwrap();
+
+            let expected = Orientation::from_exif(exif_value as u8).filter(|&o| o != Orientation::NoTransforms);
+            assert_eq!(read_orientation(&path), expected, "Orientation={exif_value}");
+
+            let decoded = loader::decode(&path, 0, &settings).unwrap();
+            let pixels = decoded.prepared.still().unwrap();
+            assert_eq!(pixels.size, size, "Orientation={exif_value}");
+            let [w, h] = size;
+            let at = |x: usize, y: usize| pixels.pixels[y * w + x].r();
+            let corners = [at(0, 0), at(w - 1, 0), at(0, h - 1), at(w - 1, h - 1)];
+            for (got, want) in corners.into_iter().zip(quadrants) {
+                assert!(got.abs_diff(want) <= 8, "Orientation={exif_value}: {corners:?} != {quadrants:?}");
+            }
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_file_has_no_orientation() {
+        assert_eq!(read_orientation(Path::new("no/such/file.jpg")), None);
+        assert_eq!(read_capture_date(Path::new("no/such/file.jpg")), None);
+    }
+}
@@ -0,0 +1,277 @@
+//! 1 ファイルに複数の画像を持つ形式（マルチページ TIFF、ICO/CUR の複数解像度、
+//! WIC が複数フレームとして公開する DDS 配列・HEIF コレクション等）のページ切り替え。
+//!
+//! 先頭ページは従来どおり `load_raster` の通常経路（image → WIC）で開き、
+//! 2 ページ目以降と ICO/CUR の解像度選択だけをここで扱う。
+
+use crate::archive;
+use log::info;
+use std::io::Cursor;
+use std::path::Path;
+
+/// ページを持ち得る形式の種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PagedFormat {
+    /// マルチページ TIFF（tiff クレートで IFD を辿る）
+    Tiff,
+    /// ICO/CUR（ico クレートでエントリを列挙。ページ＝解像度）
+    Icon,
+    /// WIC のフレーム（DDS 配列・HEIF 等）
+    Wic,
+    /// 常に 1 ページ
+    Single,
+}
+
+impl PagedFormat {
+    pub fn from_path(path: &Path) -> Self {
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "tif" | "tiff" => Self::Tiff,
+            "ico" | "cur" => Self::Icon,
+            "dds" | "heic" | "heif" | "avif" | "jxr" | "wdp" => Self::Wic,
+            _ => Self::Single,
+        }
+    }
+}
+
+/// ファイル内のページ数と、ページごとの表示名（ICO/CUR の解像度など。不要なら空）
+#[derive(Clone)]
+pub struct PageInfo {
+    pub count: usize,
+    pub labels: Vec<String>,
+}
+
+impl PageInfo {
+    pub fn single() -> Self {
+        Self {
+            count: 1,
+            labels: Vec::new(),
+        }
+    }
+}
+
+/// ヘッダだけを読んでページ数を調べる。調べられない場合は 1 ページ扱い
+pub fn probe(path: &Path) -> PageInfo {
+    let result = match PagedFormat::from_path(path) {
+        PagedFormat::Tiff => tiff_page_count(path).map(|count| PageInfo {
+            count,
+            labels: Vec::new(),
+        }),
+        PagedFormat::Icon => icon_entries(path).map(|entries| PageInfo {
+            count: entries.len(),
+            labels: entries.iter().map(icon_label).collect(),
+        }),
+        PagedFormat::Wic => super::wic_frame_count(path).map(|count| PageInfo {
+            count: count as usize,
+            labels: Vec::new(),
+        }),
+        PagedFormat::Single => Ok(PageInfo::single()),
+    };
+    match result {
+        Ok(info) if info.count > 0 => info,
+        Ok(_) => PageInfo::single(),
+        Err(e) => {
+            info!("ページ数を取得できないため 1 ページとして扱います: {} ({})", path.display(), e);
+            PageInfo::single()
+        }
+    }
+}
+
+/// 指定ページをデコードする。ICO/CUR は 0 ページ目（最大解像度）もここで読む
+pub fn decode_page(path: &Path, index: usize) -> Result<image::DynamicImage, String> {
+    match PagedFormat::from_path(path) {
+        PagedFormat::Tiff => decode_tiff_page(path, index).or_else(|e| {
+            // tiff クレートが扱えない圧縮・色形式は WIC のフレームで試す
+            info!("TIFF ページ {} を WIC で再試行: {}", index, e);
+            super::decode_via_wic_frame(path, index as u32)
+                .map_err(|werr| format!("{e} / WIC: {werr}"))
+        }),
+        PagedFormat::Icon => decode_icon_entry(path, index),
+        PagedFormat::Wic => super::decode_via_wic_frame(path, index as u32),
+        PagedFormat::Single if index == 0 => crate::color::decode_with_icc(path).map(|(image, _)| image),
+        PagedFormat::Single => Err("この形式は複数ページに対応していません".into()),
+    }
+}
+
+/// tiff クレートの既定の上限（デコード後 256MB まで）のまま開く。
+/// 小さなファイルが巨大な寸法を名乗って何 GB も確保させることを防ぐ
+fn open_tiff(path: &Path) -> Result<tiff::decoder::Decoder<archive::Source>, String> {
+    let source = archive::open(path).map_err(|e| format!("ファイルを開けません: {e}"))?;
+    tiff::decoder::Decoder::new(source)
+        .map_err(|e| format!("TIFF の解析に失敗: {e}"))
+}
+
+fn tiff_page_count(path: &Path) -> Result<usize, String> {
+    let mut decoder = open_tiff(path)?;
+    let mut count = 1;
+    while decoder.more_images() {
+        decoder
+            .next_image()
+            .map_err(|e| format!("TIFF の IFD を辿れません: {e}"))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn decode_tiff_page(path: &Path, index: usize) -> Result<image::DynamicImage, String> {
+    use tiff::decoder::DecodingResult;
+    use tiff::ColorType;
+    let mut decoder = open_tiff(path)?;
+    decoder
+        .seek_to_image(index)
+        .map_err(|e| format!("ページ {} へ移動できません: {e}", index + 1))?;
+    let (w, h) = decoder.dimensions().map_err(|e| e.to_string())?;
+    let color = decoder.colortype().map_err(|e| e.to_string())?;
+    let data = decoder
+        .read_image()
+        .map_err(|e| format!("ページ {} のデコードに失敗: {e}", index + 1))?;
+    let img = match (color, data) {
+        (ColorType::Gray(1), DecodingResult::U8(buf)) => {
+            image::GrayImage::from_raw(w, h, unpack_bilevel(&buf, w, h)?).map(Into::into)
+        }
+        (ColorType::Gray(8), DecodingResult::U8(buf)) => {
+            image::GrayImage::from_raw(w, h, buf).map(Into::into)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(buf)) => {
+            image::ImageBuffer::<image::Luma<u16>, _>::from_raw(w, h, buf).map(Into::into)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(buf)) => {
+            image::GrayAlphaImage::from_raw(w, h, buf).map(Into::into)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(buf)) => {
+            image::RgbImage::from_raw(w, h, buf).map(Into::into)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(buf)) => {
+            image::ImageBuffer::<image::Rgb<u16>, _>::from_raw(w, h, buf).map(Into::into)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(buf)) => {
+            image::RgbaImage::from_raw(w, h, buf).map(Into::into)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(buf)) => {
+            image::ImageBuffer::<image::Rgba<u16>, _>::from_raw(w, h, buf).map(Into::into)
+        }
+        (ColorType::CMYK(8), DecodingResult::U8(buf)) => {
+            image::RgbImage::from_raw(w, h, cmyk_to_rgb(&buf)).map(Into::into)
+        }
+        (color, _) => return Err(format!("未対応の TIFF 色形式です: {color:?}")),
+    };
+    img.ok_or_else(|| "TIFF のバッファサイズが不一致".to_string())
+}
+
+/// 1bit（行ごとにバイト境界へ詰められた）グレースケールを 8bit へ展開する。
+/// tiff クレートが WhiteIsZero を反転済みなので 1=白 として扱う。データが足りなければエラー
+fn unpack_bilevel(packed: &[u8], w: u32, h: u32) -> Result<Vec<u8>, String> {
+    let row_bytes = (w as usize).div_ceil(8);
+    if packed.len() < row_bytes * h as usize {
+        return Err(format!(
+            "1bit の画素データが足りません: {} バイト（{}x{} には {} バイト必要）",
+            packed.len(),
+            w,
+            h,
+            row_bytes * h as usize
+        ));
+    }
+    let mut out = Vec::with_capacity(w as usize * h as usize);
+    for row in packed.chunks(row_bytes).take(h as usize) {
+        for x in 0..w as usize {
+            let bit = (row[x / 8] >> (7 - x % 8)) & 1;
+            out.push(if bit == 1 { 255 } else { 0 });
+        }
+    }
+    Ok(out)
+}
+
+/// 素朴な CMYK → RGB 変換（プロファイルを使わない近似）
+fn cmyk_to_rgb(cmyk: &[u8]) -> Vec<u8> {
+    cmyk.chunks_exact(4)
+        .flat_map(|p| {
+            let k = 255 - p[3] as u32;
+            [
+                ((255 - p[0] as u32) * k / 255) as u8,
+                ((255 - p[1] as u32) * k / 255) as u8,
+                ((255 - p[2] as u32) * k / 255) as u8,
+            ]
+        })
+        .collect()
+}
+
+/// ICO/CUR のエントリを、大きい解像度・高い色深度の順に並べて返す
+/// （先頭が image クレートの既定の選択と同じ「いちばん良いアイコン」になる）
+fn icon_entries(path: &Path) -> Result<Vec<ico::IconDirEntry>, String> {
+    let bytes = archive::read(path).map_err(|e| format!("ファイルを開けません: {e}"))?;
+    let dir = ico::IconDir::read(Cursor::new(bytes)).map_err(|e| format!("ICO の解析に失敗: {e}"))?;
+    let mut entries = dir.entries().to_vec();
+    entries.sort_by_key(|e| {
+        std::cmp::Reverse((e.width() as u64 * e.height() as u64, e.bits_per_pixel()))
+    });
+    Ok(entries)
+}
+
+fn icon_label(entry: &ico::IconDirEntry) -> String {
+    format!("{}x{} ({}bpp)", entry.width(), entry.height(), entry.bits_per_pixel())
+}
+
+fn decode_icon_entry(path: &Path, index: usize) -> Result<image::DynamicImage, String> {
+    let entries = icon_entries(path)?;
+    let entry = entries
+        .get(index)
+        .ok_or_else(|| format!("アイコン {} は存在しません", index + 1))?;
+    let icon = entry
+        .decode()
+        .map_err(|e| format!("アイコンのデコードに失敗: {e}"))?;
+    image::RgbaImage::from_raw(icon.width(), icon.height(), icon.rgba_data().to_vec())
+        .map(image::DynamicImage::ImageRgba8)
+        .ok_or_else(|| "アイコンのバッファサイズが不一致".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_detection_by_extension() {
+        assert_eq!(PagedFormat::from_path(Path::new("scan.TIF")), PagedFormat::Tiff);
+        assert_eq!(PagedFormat::from_path(Path::new("a.cur")), PagedFormat::Icon);
+        assert_eq!(PagedFormat::from_path(Path::new("tex.dds")), PagedFormat::Wic);
+        assert_eq!(PagedFormat::from_path(Path::new("a.png")), PagedFormat::Single);
+    }
+
+    #[test]
+    fn bilevel_rows_are_byte_aligned() {
+        // 幅 10px → 1 行 2 バイト。1 行目は先頭と末尾だけ白、2 行目は全部黒
+        let packed = [0b1000_0000, 0b0100_0000, 0, 0];
+        let out = unpack_bilevel(&packed, 10, 2).unwrap();
+        assert_eq!(out.len(), 20);
+        assert_eq!(out[0], 255);
+        assert_eq!(out[9], 255);
+        assert!(out[1..9].iter().all(|&v| v == 0));
+        assert!(out[10..].iter().all(|&v| v == 0));
+        // 最後の行が途中で切れていれば panic せずにエラー
+        assert!(unpack_bilevel(&packed[..3], 10, 2).is_err());
+        assert!(unpack_bilevel(&[], 10, 2).is_err());
+    }
+
+    #[test]
+    fn multipage_tiff_roundtrip() {
+        use tiff::encoder::{colortype, TiffEncoder};
+        let dir = std::env::temp_dir().join(format!("msbt-pages-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("multi.tif");
+        {
+            let file = std::fs::File::create(&path).unwrap();
+            let mut enc = TiffEncoder::new(std::io::BufWriter::new(file)).unwrap();
+            for v in [10u8, 20, 30] {
+                enc.write_image::<colortype::Gray8>(4, 3, &[v; 12]).unwrap();
+            }
+        }
+        let info = probe(&path);
+        assert_eq!(info.count, 3);
+        let page = decode_page(&path, 2).unwrap().to_luma8();
+        assert_eq!(page.dimensions(), (4, 3));
+        assert_eq!(page.get_pixel(0, 0).0, [30]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,202 @@
+//! 複数ファイル・リストファイル・標準入力からのプレイリスト。
+//!
+//! 1 ファイルだけを開いたときは従来どおりそのフォルダの画像を一覧にし、フォルダを開いたときは
+//! その中の画像を一覧にして先頭を表示する。複数のファイル
+//! （ドロップ・コマンドライン引数・ファイルダイアログ）や .m3u/.txt のリスト、標準入力（"-"）を
+//! 開いたときは、その一覧をそのままの順で image_paths として使う。
+//! ZIP/CBZ・tar/CBT のアーカイブはフォルダと同じように扱い、複数指定されたときは中の画像を並べる。
+
+use crate::{archive, folders};
+use log::{error, info};
+use percent_encoding::percent_decode_str;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// リストファイルとして読む拡張子
+pub const LIST_EXTS: &[&str] = &["m3u", "m3u8", "txt", "lst"];
+
+/// 開く対象
+#[derive(Debug, PartialEq)]
+pub enum Open {
+    /// 1 ファイル（同じフォルダの画像を一覧にする）
+    File(PathBuf),
+    /// フォルダまたはアーカイブ（その中の画像を並び順・サブフォルダの設定に従って一覧にし、先頭を開く）
+    Folder(PathBuf),
+    /// 明示されたファイルの一覧（フォルダの一覧の代わりに使う）
+    Playlist(Vec<PathBuf>),
+}
+
+/// 開くよう指定されたパスを解釈する。"-" は標準入力から読んだリスト。
+/// 読めない・見つからない項目はエラーログに残して飛ばし、何も残らなければ None
+pub fn resolve(inputs: &[PathBuf]) -> Option<Open> {
+    if let [single] = inputs {
+        if single.is_dir() || archive::is_archive_path(single) {
+            return Some(Open::Folder(single.clone()));
+        }
+        if !is_stdin(single) && !is_list_path(single) {
+            return Some(Open::File(single.clone()));
+        }
+    }
+    let mut paths = Vec::new();
+    for input in inputs {
+        let entries = if archive::is_archive_path(input) {
+            // アーカイブ内の画像は実在するファイルではないので usable() を通さない
+            match archive::list(input) {
+                Ok(images) => paths.extend(images),
+                Err(e) => error!("{}", e),
+            }
+            continue;
+        } else if is_stdin(input) {
+            read_stdin()
+        } else if is_list_path(input) {
+            read_list(input)
+        } else {
+            Ok(vec![input.clone()])
+        };
+        match entries {
+            Ok(entries) => paths.extend(entries.into_iter().filter(|p| usable(p))),
+            Err(e) => error!("{}", e),
+        }
+    }
+    if paths.is_empty() {
+        error!("開ける画像がありません: {:?}", inputs);
+        return None;
+    }
+    info!("プレイリストを開きます: {} 件", paths.len());
+    Some(Open::Playlist(paths))
+}
+
+fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+fn is_list_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        let ext = ext.to_string_lossy().to_lowercase();
+        LIST_EXTS.contains(&ext.as_str())
+    })
+}
+
+/// プレイリストに入れられるファイルか（無いもの・フォルダ・画像でないものはログに残して除く）。
+/// 拡張子はフォルダを開いたときの一覧と同じ基準で見る
+fn usable(path: &Path) -> bool {
+    if path.is_file() {
+        if folders::has_image_ext(path) {
+            return true;
+        }
+        info!("画像ではないのでプレイリストから除きます: {}", path.display());
+        return false;
+    }
+    if path.is_dir() {
+        error!("フォルダはプレイリストに入れられません: {}", path.display());
+    } else {
+        error!("プレイリストの項目が見つかりません: {}", path.display());
+    }
+    false
+}
+
+fn read_list(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("リストファイルを読み込めません: {} - {}", path.display(), e))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    Ok(parse_list(&String::from_utf8_lossy(&bytes), base))
+}
+
+fn read_stdin() -> Result<Vec<PathBuf>, String> {
+    let mut bytes = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("標準入力を読み込めません: {}", e))?;
+    let base = std::env::current_dir().unwrap_or_default();
+    Ok(parse_list(&String::from_utf8_lossy(&bytes), &base))
+}
+
+/// 1 行 1 パスのリスト（m3u を含む）を解釈する。空行と # で始まる行（#EXTINF 等）は飛ばす。
+/// 相対パスは base から、file:// の URI はローカルパスとして、引用符で囲まれたパスは外して読む
+pub fn parse_list(text: &str, base: &Path) -> Vec<PathBuf> {
+    text.trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let line = line
+                .strip_prefix('"')
+                .and_then(|l| l.strip_suffix('"'))
+                .unwrap_or(line);
+            let path = match line.strip_prefix("file://") {
+                Some(uri) => file_uri_path(uri),
+                None => PathBuf::from(line),
+            };
+            if path.is_absolute() {
+                path
+            } else {
+                base.join(path)
+            }
+        })
+        .collect()
+}
+
+/// file:// の後ろ（"/C:/a%20b.png" や "localhost/home/a.png"）をパスにする
+fn file_uri_path(uri: &str) -> PathBuf {
+    let uri = uri.strip_prefix("localhost").unwrap_or(uri);
+    let decoded = percent_decode_str(uri).decode_utf8_lossy();
+    // Windows のドライブ文字の前の "/" は外す
+    let bytes = decoded.as_bytes();
+    if bytes.len() >= 3 && bytes[0] == b'/' && bytes[1].is_ascii_alphabetic() && bytes[2] == b':' {
+        PathBuf::from(&decoded[1..])
+    } else {
+        PathBuf::from(decoded.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_m3u_and_plain_lists() {
+        let base = Path::new("/lists");
+        let text = "\u{feff}#EXTM3U\r\n#EXTINF:-1,cover\r\ncover.png\r\n\r\n  sub/a b.jpg  \r\n\"/abs/q.png\"\nfile:///home/u/%E7%94%BB%E5%83%8F.webp\nfile://localhost/tmp/x.png\n";
+        assert_eq!(
+            parse_list(text, base),
+            [
+                PathBuf::from("/lists/cover.png"),
+                PathBuf::from("/lists/sub/a b.jpg"),
+                PathBuf::from("/abs/q.png"),
+                PathBuf::from("/home/u/画像.webp"),
+                PathBuf::from("/tmp/x.png"),
+            ]
+        );
+        assert_eq!(file_uri_path("/C:/a%20b.png"), PathBuf::from("C:/a b.png"));
+    }
+
+    #[test]
+    fn single_file_opens_its_folder_and_lists_become_playlists() {
+        let dir = std::env::temp_dir().join(format!("msbt-playlist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.png");
+        let b = dir.join("b.png");
+        std::fs::write(&a, b"").unwrap();
+        std::fs::write(&b, b"").unwrap();
+        let doc = dir.join("readme.md");
+        std::fs::write(&doc, b"").unwrap();
+        let list = dir.join("list.m3u");
+        std::fs::write(&list, "b.png\nmissing.png\nreadme.md\na.png\n").unwrap();
+
+        assert_eq!(resolve(std::slice::from_ref(&a)), Some(Open::File(a.clone())));
+        assert_eq!(resolve(std::slice::from_ref(&dir)), Some(Open::Folder(dir.clone())));
+        assert_eq!(
+            resolve(&[b.clone(), a.clone()]),
+            Some(Open::Playlist(vec![b.clone(), a.clone()]))
+        );
+        // 複数指定・ドロップでも、フォルダの一覧と同じく画像でないファイルは除く
+        assert_eq!(
+            resolve(&[doc.clone(), b.clone(), a.clone()]),
+            Some(Open::Playlist(vec![b.clone(), a.clone()]))
+        );
+        assert_eq!(resolve(&[doc, dir.join("x.exe")]), None);
+        assert_eq!(resolve(&[list]), Some(Open::Playlist(vec![b, a])));
+        assert_eq!(resolve(&[dir.join("missing.png"), dir.clone()]), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
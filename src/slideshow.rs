@@ -0,0 +1,344 @@
+//! スライドショー（一定間隔で image_paths を順に表示する）。
+//!
+//! 再生順は開始時（ループするときは一周ごと）に作っておき、一覧から消えた画像は飛ばす。
+//! 表示間隔は画像の表示が終わってから数えるので、デコードに時間が掛かっても短くならない。
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// クロスフェードに掛ける時間
+pub const FADE_DURATION: Duration = Duration::from_millis(400);
+
+/// 再生順
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SlideOrder {
+    /// 一覧の順
+    #[default]
+    Sequential,
+    /// 一覧の逆順
+    Reverse,
+    /// ランダム（一周ごとに並べ直す）
+    Shuffle,
+}
+
+impl SlideOrder {
+    pub const ALL: [SlideOrder; 3] = [
+        SlideOrder::Sequential,
+        SlideOrder::Reverse,
+        SlideOrder::Shuffle,
+    ];
+
+    /// 設定ファイルでの名前
+    pub fn name(self) -> &'static str {
+        match self {
+            SlideOrder::Sequential => "sequential",
+            SlideOrder::Reverse => "reverse",
+            SlideOrder::Shuffle => "shuffle",
+        }
+    }
+
+    /// メニューの表示名
+    pub fn label(self) -> &'static str {
+        match self {
+            SlideOrder::Sequential => "Sequential",
+            SlideOrder::Reverse => "Reverse",
+            SlideOrder::Shuffle => "Shuffle",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Stopped,
+    Running,
+    /// ユーザー操作で一時停止中（S で再開）
+    Paused,
+}
+
+/// 再生中に変えられる設定（ViewerConfig から毎フレーム作る）
+#[derive(Clone, Copy)]
+pub struct Settings {
+    pub interval: Duration,
+    pub order: SlideOrder,
+    pub looping: bool,
+}
+
+/// tick() の結果
+#[derive(Debug, PartialEq)]
+pub enum Tick {
+    /// 何もしない（停止中・一時停止中・読み込み待ち）
+    Idle,
+    /// 指定時間後にもう一度呼ぶ
+    Wait(Duration),
+    /// 次の画像を表示する
+    Advance(PathBuf),
+    /// 最後まで表示した（ループしない設定）
+    Finished,
+}
+
+pub struct Slideshow {
+    state: State,
+    playlist: Vec<PathBuf>,
+    /// playlist 内の次に表示する位置
+    cursor: usize,
+    /// 表示間隔を数え始めた画像と、次へ進む時刻
+    shown: Option<PathBuf>,
+    deadline: Option<Instant>,
+    rng: u64,
+}
+
+impl Slideshow {
+    pub fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self {
+            state: State::Stopped,
+            playlist: Vec::new(),
+            cursor: 0,
+            shown: None,
+            deadline: None,
+            // xorshift は 0 から抜け出せないので奇数にする
+            rng: seed | 1,
+        }
+    }
+
+    /// 再生中または一時停止中か
+    pub fn is_active(&self) -> bool {
+        self.state != State::Stopped
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state == State::Paused
+    }
+
+    /// 表示中の画像から再生を始める
+    pub fn start(&mut self, paths: &[PathBuf], current: Option<&Path>, order: SlideOrder) {
+        self.build(paths, current, order);
+        self.state = State::Running;
+        self.shown = None;
+        self.deadline = None;
+    }
+
+    pub fn stop(&mut self) {
+        self.state = State::Stopped;
+        self.playlist.clear();
+    }
+
+    /// 再生中ならユーザー操作で一時停止する
+    pub fn pause(&mut self) {
+        if self.state == State::Running {
+            self.state = State::Paused;
+        }
+    }
+
+    /// 一時停止中に手動で移動した画像から、改めて間隔を数えて再開する
+    pub fn resume(&mut self, current: Option<&Path>) {
+        if self.state != State::Paused {
+            return;
+        }
+        if let Some(pos) = current.and_then(|c| self.playlist.iter().position(|p| p == c)) {
+            self.cursor = pos + 1;
+        }
+        self.state = State::Running;
+        self.shown = None;
+        self.deadline = None;
+    }
+
+    /// 毎フレーム呼ぶ。表示中の画像が変わったらそこから間隔を数え、時間が来たら次の画像を返す。
+    /// loading はデコード待ちの間 true（その間は数えない）
+    pub fn tick(
+        &mut self,
+        now: Instant,
+        current: Option<&Path>,
+        loading: bool,
+        paths: &[PathBuf],
+        settings: Settings,
+    ) -> Tick {
+        let interval = settings.interval;
+        if self.state != State::Running || loading {
+            return Tick::Idle;
+        }
+        if self.shown.as_deref() != current || self.deadline.is_none() {
+            self.shown = current.map(Path::to_path_buf);
+            self.deadline = Some(now + interval);
+        }
+        let deadline = self.deadline.unwrap_or(now);
+        if now < deadline {
+            return Tick::Wait(deadline - now);
+        }
+        match self.next(paths, settings.order, settings.looping) {
+            // 1 枚しかないときは表示し直さずに待つ
+            Some(next) if Some(next.as_path()) == current => {
+                self.deadline = Some(now + interval);
+                Tick::Wait(interval)
+            }
+            Some(next) => {
+                self.deadline = None;
+                Tick::Advance(next)
+            }
+            None => {
+                self.stop();
+                Tick::Finished
+            }
+        }
+    }
+
+    /// 再生順で次の画像。一覧から消えたものは飛ばし、最後まで来たらループする設定なら並べ直す
+    fn next(&mut self, paths: &[PathBuf], order: SlideOrder, looping: bool) -> Option<PathBuf> {
+        for lap in 0..2 {
+            while let Some(path) = self.playlist.get(self.cursor) {
+                self.cursor += 1;
+                if paths.contains(path) {
+                    return Some(path.clone());
+                }
+            }
+            if !looping || lap == 1 {
+                break;
+            }
+            // 一周目の最後と同じ画像から二周目を始めないようにする
+            let last = self.playlist.last().cloned();
+            self.build(paths, None, order);
+            if self.playlist.len() > 1 && self.playlist.first() == last.as_ref() {
+                self.playlist.swap(0, 1);
+            }
+        }
+        None
+    }
+
+    /// 再生順を作る。current があればそれを先頭にし、その次から表示する
+    fn build(&mut self, paths: &[PathBuf], current: Option<&Path>, order: SlideOrder) {
+        let index = current.and_then(|c| paths.iter().position(|p| p == c));
+        self.playlist = match (order, index) {
+            (SlideOrder::Sequential, Some(i)) => paths[i..].to_vec(),
+            (SlideOrder::Sequential, None) => paths.to_vec(),
+            (SlideOrder::Reverse, Some(i)) => paths[..=i].iter().rev().cloned().collect(),
+            (SlideOrder::Reverse, None) => paths.iter().rev().cloned().collect(),
+            (SlideOrder::Shuffle, _) => {
+                let mut rest = paths.to_vec();
+                let first = index.map(|i| rest.remove(i));
+                self.shuffle(&mut rest);
+                first.into_iter().chain(rest).collect()
+            }
+        };
+        self.cursor = usize::from(index.is_some());
+    }
+
+    /// Fisher–Yates（xorshift64 の乱数で十分）
+    fn shuffle(&mut self, items: &mut [PathBuf]) {
+        for i in (1..items.len()).rev() {
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            let j = (self.rng % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(n: usize) -> Vec<PathBuf> {
+        (0..n).map(|i| PathBuf::from(format!("{i}.png"))).collect()
+    }
+
+    /// 表示中の画像が current のときに時間を進めて、次に表示する画像を得る
+    fn step(
+        show: &mut Slideshow,
+        list: &[PathBuf],
+        current: &Path,
+        order: SlideOrder,
+        looping: bool,
+    ) -> Tick {
+        let settings = Settings {
+            interval: Duration::from_secs(1),
+            order,
+            looping,
+        };
+        let now = Instant::now();
+        assert_eq!(
+            show.tick(now, Some(current), false, list, settings),
+            Tick::Wait(settings.interval)
+        );
+        show.tick(
+            now + settings.interval,
+            Some(current),
+            false,
+            list,
+            settings,
+        )
+    }
+
+    #[test]
+    fn steps_in_order_and_stops_or_loops_at_the_end() {
+        let list = paths(3);
+        let mut show = Slideshow::new();
+        show.start(&list, Some(&list[1]), SlideOrder::Sequential);
+        assert_eq!(
+            step(&mut show, &list, &list[1], SlideOrder::Sequential, false),
+            Tick::Advance(list[2].clone())
+        );
+        assert_eq!(
+            step(&mut show, &list, &list[2], SlideOrder::Sequential, false),
+            Tick::Finished
+        );
+        assert!(!show.is_active());
+
+        show.start(&list, Some(&list[1]), SlideOrder::Reverse);
+        assert_eq!(
+            step(&mut show, &list, &list[1], SlideOrder::Reverse, true),
+            Tick::Advance(list[0].clone())
+        );
+        assert_eq!(
+            step(&mut show, &list, &list[0], SlideOrder::Reverse, true),
+            Tick::Advance(list[2].clone())
+        );
+
+        // 一時停止中は進まず、再開すると手動で移動した画像から続ける
+        show.pause();
+        let settings = Settings {
+            interval: Duration::from_secs(1),
+            order: SlideOrder::Reverse,
+            looping: true,
+        };
+        let later = Instant::now() + Duration::from_secs(10);
+        assert_eq!(
+            show.tick(later, Some(&list[2]), false, &list, settings),
+            Tick::Idle
+        );
+        show.resume(Some(&list[1]));
+        assert_eq!(
+            step(&mut show, &list, &list[1], SlideOrder::Reverse, true),
+            Tick::Advance(list[0].clone())
+        );
+    }
+
+    #[test]
+    fn shuffle_visits_every_image_once_per_lap() {
+        let list = paths(20);
+        let mut show = Slideshow::new();
+        show.start(&list, Some(&list[5]), SlideOrder::Shuffle);
+        let mut seen = vec![list[5].clone()];
+        let mut current = list[5].clone();
+        for _ in 1..list.len() {
+            let Tick::Advance(next) = step(&mut show, &list, &current, SlideOrder::Shuffle, true)
+            else {
+                panic!("次の画像があるはず");
+            };
+            assert!(!seen.contains(&next));
+            seen.push(next.clone());
+            current = next;
+        }
+        // 2 周目に入っても直前と同じ画像にはならない
+        let Tick::Advance(next) = step(&mut show, &list, &current, SlideOrder::Shuffle, true)
+        else {
+            panic!("ループするはず");
+        };
+        assert_ne!(next, current);
+    }
+}
@@ -0,0 +1,229 @@
+//! フォルダ内の画像の並び順（Options メニューで選び、設定ファイルに保存する）。
+//!
+//! 撮影日時はファイルを開いて EXIF を読むので、更新日時・サイズが変わらない間はキャッシュを使う
+//! （変更監視で一覧を作り直すたびに全ファイルを開き直して UI が止まらないように）。
+
+use crate::metadata;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// 撮影日時をキャッシュするファイル数（超えたら捨てて作り直す）
+const CAPTURE_DATE_CACHE_LEN: usize = 200_000;
+
+/// 並べ替えの基準
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// ファイル名の自然順（img2 < img10、エクスプローラーと同じ感覚）
+    #[default]
+    Natural,
+    /// ファイル名の文字コード順
+    Name,
+    /// 更新日時
+    Modified,
+    /// EXIF の撮影日時（無いものは最後）
+    Exif,
+    /// ファイルサイズ
+    Size,
+    /// 拡張子（同じ拡張子の中は自然順）
+    Extension,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 6] = [
+        SortKey::Natural,
+        SortKey::Name,
+        SortKey::Modified,
+        SortKey::Exif,
+        SortKey::Size,
+        SortKey::Extension,
+    ];
+
+    /// 設定ファイルでの名前
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Natural => "natural",
+            SortKey::Name => "name",
+            SortKey::Modified => "modified",
+            SortKey::Exif => "exif",
+            SortKey::Size => "size",
+            SortKey::Extension => "extension",
+        }
+    }
+
+    /// メニューの表示名
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Natural => "Name (natural)",
+            SortKey::Name => "Name (lexicographic)",
+            SortKey::Modified => "Modified time",
+            SortKey::Exif => "EXIF date taken",
+            SortKey::Size => "File size",
+            SortKey::Extension => "Extension",
+        }
+    }
+}
+
+/// 比較に使う値（ファイルごとに一度だけ調べる）
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Value {
+    None,
+    Time(Option<SystemTime>),
+    Date(Option<String>),
+    Size(u64),
+    Text(String),
+}
+
+/// key の順に並べる。descending なら逆順（撮影日時の無いものはどちらでも最後）。
+/// 同じ値のものはファイル名の自然順
+pub fn sort_paths(paths: &mut [PathBuf], key: SortKey, descending: bool) {
+    let by_name = |a: &str, b: &str| {
+        if key == SortKey::Name {
+            a.cmp(b)
+        } else {
+            super::natural_cmp(a, b)
+        }
+    };
+    let mut keyed: Vec<(Value, String, PathBuf)> = paths
+        .iter_mut()
+        .map(|path| {
+            let path = std::mem::take(path);
+            (value_of(&path, key), file_name(&path), path)
+        })
+        .collect();
+    keyed.sort_by(|a, b| {
+        let missing = match (&a.0, &b.0) {
+            (Value::Date(None), Value::Date(Some(_))) => Ordering::Greater,
+            (Value::Date(Some(_)), Value::Date(None)) => Ordering::Less,
+            _ => Ordering::Equal,
+        };
+        let primary = match key {
+            SortKey::Natural | SortKey::Name => by_name(&a.1, &b.1),
+            _ => a.0.cmp(&b.0),
+        };
+        let primary = if descending { primary.reverse() } else { primary };
+        missing
+            .then(primary)
+            .then_with(|| by_name(&a.1, &b.1))
+            .then_with(|| a.2.cmp(&b.2))
+    });
+    for (slot, (_, _, path)) in paths.iter_mut().zip(keyed) {
+        *slot = path;
+    }
+}
+
+fn value_of(path: &Path, key: SortKey) -> Value {
+    match key {
+        SortKey::Natural | SortKey::Name => Value::None,
+        SortKey::Modified => Value::Time(crate::archive::metadata(path).and_then(|m| m.modified()).ok()),
+        SortKey::Exif => Value::Date(capture_date(path)),
+        SortKey::Size => Value::Size(crate::archive::metadata(path).map(|m| m.len()).unwrap_or(0)),
+        SortKey::Extension => Value::Text(
+            path.extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+        ),
+    }
+}
+
+/// ファイルの状態（更新日時とサイズ）
+type Stamp = Option<(Option<SystemTime>, u64)>;
+
+/// 撮影日時。前回読んだときから更新日時・サイズが変わっていなければ EXIF を読み直さない
+fn capture_date(path: &Path) -> Option<String> {
+    type Cache = Mutex<HashMap<PathBuf, (Stamp, Option<String>)>>;
+    static CACHE: OnceLock<Cache> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    let stamp = crate::archive::metadata(path)
+        .ok()
+        .map(|m| (m.modified().ok(), m.len()));
+    if let Some((cached, date)) = cache.lock().unwrap().get(path) {
+        if *cached == stamp {
+            return date.clone();
+        }
+    }
+    let date = metadata::read_capture_date(path);
+    let mut cache = cache.lock().unwrap();
+    if cache.len() >= CAPTURE_DATE_CACHE_LEN {
+        cache.clear();
+    }
+    cache.insert(path.to_path_buf(), (stamp, date.clone()));
+    date
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(paths: &[PathBuf]) -> Vec<String> {
+        paths.iter().map(|p| file_name(p)).collect()
+    }
+
+    #[test]
+    fn orders_by_name_extension_and_size() {
+        let dir = std::env::temp_dir().join(format!("msbt-sort-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [("img10.png", 3), ("img2.jpg", 1), ("Img1.png", 2)];
+        let mut paths: Vec<PathBuf> = files
+            .iter()
+            .map(|(name, len)| {
+                let p = dir.join(name);
+                std::fs::write(&p, vec![0u8; *len]).unwrap();
+                p
+            })
+            .collect();
+
+        sort_paths(&mut paths, SortKey::Natural, false);
+        assert_eq!(names(&paths), ["Img1.png", "img2.jpg", "img10.png"]);
+        sort_paths(&mut paths, SortKey::Natural, true);
+        assert_eq!(names(&paths), ["img10.png", "img2.jpg", "Img1.png"]);
+        sort_paths(&mut paths, SortKey::Name, false);
+        assert_eq!(names(&paths), ["Img1.png", "img10.png", "img2.jpg"]);
+        sort_paths(&mut paths, SortKey::Extension, false);
+        assert_eq!(names(&paths), ["img2.jpg", "Img1.png", "img10.png"]);
+        sort_paths(&mut paths, SortKey::Size, true);
+        assert_eq!(names(&paths), ["img10.png", "Img1.png", "img2.jpg"]);
+        // EXIF の無いファイルだけなら自然順のまま
+        sort_paths(&mut paths, SortKey::Exif, false);
+        assert_eq!(names(&paths), ["Img1.png", "img2.jpg", "img10.png"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn capture_dates_are_reread_only_when_the_file_changes() {
+        let dir = std::env::temp_dir().join(format!("msbt-sort-exif-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("photo.jpg");
+        let jpeg = |date: &str| {
+            let field = exif::Field {
+                tag: exif::Tag::DateTimeOriginal,
+                ifd_num: exif::In::PRIMARY,
+                value: exif::Value::Ascii(vec![date.as_bytes().to_vec()]),
+            };
+            metadata::with_exif(&[0xFF, 0xD8, 0xFF, 0xD9], &[field])
+        };
+        std::fs::write(&path, jpeg("2020:01:01 00:00:00")).unwrap();
+        let mtime = std::fs::metadata(&path).unwrap().modified().unwrap();
+        assert_eq!(capture_date(&path).as_deref(), Some("2020:01:01 00:00:00"));
+
+        // 更新日時・サイズが同じなら読み直さない
+        std::fs::write(&path, jpeg("2021:01:01 00:00:00")).unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(mtime).unwrap();
+        assert_eq!(capture_date(&path).as_deref(), Some("2020:01:01 00:00:00"));
+
+        file.set_modified(mtime + std::time::Duration::from_secs(5)).unwrap();
+        assert_eq!(capture_date(&path).as_deref(), Some("2021:01:01 00:00:00"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
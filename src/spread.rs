@@ -0,0 +1,112 @@
+//! 見開き表示（本・漫画のスキャン画像を 2 ページずつ並べる）のページ割り。
+//!
+//! 一覧の先頭から順に、表紙（先頭ページ）と横長のページは 1 ページで、それ以外は 2 ページずつ
+//! 組んでいく。横長かどうかはデコード済みのページしか分からないので、分からないページは縦長として扱う。
+//! 右綴じ（右から左へ読む）かどうかは並べ方だけの違いなので、ここでは扱わない。
+
+/// ページの組み方（ViewerConfig から作る）
+#[derive(Clone, Copy)]
+pub struct Settings {
+    /// 先頭ページ（表紙）を 1 ページで表示する
+    pub cover_single: bool,
+    /// 横長のページを 1 ページで表示する
+    pub landscape_single: bool,
+}
+
+/// index 番目から始まる見開きに index+1 番目のページを並べるか。
+/// is_wide は i 番目のページが横長と分かっているとき true を返す
+pub fn pairs(
+    index: usize,
+    count: usize,
+    settings: Settings,
+    is_wide: impl Fn(usize) -> bool,
+) -> bool {
+    if index + 1 >= count || (settings.cover_single && index == 0) {
+        return false;
+    }
+    !(settings.landscape_single && (is_wide(index) || is_wide(index + 1)))
+}
+
+/// 次の見開きの先頭（末尾を越えたら先頭へ回る）
+pub fn next_start(
+    index: usize,
+    count: usize,
+    settings: Settings,
+    is_wide: impl Fn(usize) -> bool,
+) -> usize {
+    let next = index + span(index, count, settings, &is_wide);
+    if next >= count {
+        0
+    } else {
+        next
+    }
+}
+
+/// 前の見開きの先頭（先頭より前なら最後の見開きへ回る）。
+/// 途中のページから表示していても前後で組み方がずれないよう、一覧の先頭から組み直して求める
+pub fn previous_start(
+    index: usize,
+    count: usize,
+    settings: Settings,
+    is_wide: impl Fn(usize) -> bool,
+) -> usize {
+    let end = if index == 0 { count } else { index.min(count) };
+    let mut start = 0;
+    loop {
+        let next = start + span(start, count, settings, &is_wide);
+        if next >= end {
+            return start;
+        }
+        start = next;
+    }
+}
+
+fn span(index: usize, count: usize, settings: Settings, is_wide: &impl Fn(usize) -> bool) -> usize {
+    if pairs(index, count, settings, is_wide) {
+        2
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOK: Settings = Settings {
+        cover_single: true,
+        landscape_single: true,
+    };
+
+    #[test]
+    fn pairs_pages_after_cover_and_keeps_wide_pages_alone() {
+        // 0=表紙, 1-2, 3=横長, 4-5, 6
+        let wide = |i| i == 3;
+        let starts: Vec<usize> =
+            std::iter::successors(Some(0), |&i| Some(next_start(i, 7, BOOK, wide)))
+                .skip(1)
+                .take_while(|&i| i != 0)
+                .collect();
+        assert_eq!(starts, [1, 3, 4, 6]);
+        assert!(pairs(1, 7, BOOK, wide));
+        assert!(!pairs(2, 7, BOOK, wide));
+        assert!(!pairs(6, 7, BOOK, wide));
+
+        assert_eq!(previous_start(6, 7, BOOK, wide), 4);
+        assert_eq!(previous_start(4, 7, BOOK, wide), 3);
+        assert_eq!(previous_start(3, 7, BOOK, wide), 1);
+        assert_eq!(previous_start(1, 7, BOOK, wide), 0);
+        assert_eq!(previous_start(0, 7, BOOK, wide), 6);
+        // 途中のページ（2）から見ていても、前へ戻ると先頭から組んだ見開きになる
+        assert_eq!(previous_start(2, 7, BOOK, wide), 1);
+
+        let plain = Settings {
+            cover_single: false,
+            landscape_single: false,
+        };
+        assert!(pairs(0, 7, plain, wide));
+        assert_eq!(next_start(2, 7, plain, wide), 4);
+        assert_eq!(previous_start(0, 7, plain, wide), 6);
+        assert_eq!(previous_start(0, 6, plain, wide), 4);
+    }
+}
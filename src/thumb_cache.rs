@@ -0,0 +1,399 @@
+//! サムネイルのディスクキャッシュ。
+//!
+//! 形式は freedesktop.org の Thumbnail Managing Standard に合わせる：
+//! ファイル URI の MD5 を名前にした PNG に、元ファイルの URI・更新日時・サイズを tEXt で埋め込み、
+//! 読み出し時にそれらが元ファイルと一致しなければ古いものとして使わない。
+//! Linux では他のアプリと共有される `~/.cache/thumbnails/large` に置き、
+//! それ以外の OS ではユーザーのキャッシュディレクトリ配下の専用フォルダに置く。
+//! アーカイブ内の画像（book.cbz/001.jpg）の URI はディスク上に実在しないので、共有の場所には置かず
+//! （他のアプリに古いものとして消されないよう）、このアプリ専用のフォルダに置く。
+//! 上限を超えたときの削除と「キャッシュを消去」は、このアプリが作ったもの（Software が一致）だけを対象にする。
+
+use eframe::egui;
+use log::{debug, info};
+use md5::{Digest, Md5};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// tEXt の Software に書く名前（自分が作ったサムネイルの判別用）
+const SOFTWARE: &str = "MSBT-yuina";
+
+/// URI のパス部分でエスケープしない文字（GLib の g_filename_to_uri と同じ）
+const URI_PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'!')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'\'')
+    .remove(b'(')
+    .remove(b')')
+    .remove(b'*')
+    .remove(b'+')
+    .remove(b',')
+    .remove(b';')
+    .remove(b'=')
+    .remove(b':')
+    .remove(b'@')
+    .remove(b'/');
+
+pub struct DiskCache {
+    dir: PathBuf,
+    /// アーカイブ内の画像のサムネイルの置き場所（このアプリ専用）
+    archive_dir: PathBuf,
+    /// 上限（バイト）。prune で古いものから消す
+    cap: u64,
+}
+
+/// 元ファイルの識別情報（サムネイルに埋め込み、読み出し時に照合する）
+struct Source {
+    uri: String,
+    mtime: u64,
+    size: u64,
+}
+
+impl Source {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = crate::archive::metadata(path).ok()?;
+        let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        Some(Self {
+            uri: file_uri(path)?,
+            mtime,
+            size: meta.len(),
+        })
+    }
+}
+
+impl DiskCache {
+    /// OS ごとの既定の場所を使う。場所が決まらない環境では None
+    pub fn new(cap: u64) -> Option<Self> {
+        let (dir, archive_dir) = default_dirs()?;
+        Some(Self { dir, archive_dir, cap })
+    }
+
+    /// dir に置く（アーカイブ内の画像は dir/archive）
+    #[cfg(test)]
+    pub fn with_dir(dir: PathBuf, cap: u64) -> Self {
+        Self {
+            archive_dir: dir.join("archive"),
+            dir,
+            cap,
+        }
+    }
+
+    /// 元ファイルが変わっていなければ保存済みのサムネイルを返す
+    pub fn load(&self, path: &Path) -> Option<egui::ColorImage> {
+        let source = Source::of(path)?;
+        let bytes = fs::read(self.entry_path(path, &source.uri)).ok()?;
+        let texts = read_texts(&bytes)?;
+        let field = |key: &str| texts.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        if field("Thumb::URI") != Some(source.uri.as_str())
+            || field("Thumb::MTime").and_then(|v| v.parse().ok()) != Some(source.mtime)
+        {
+            return None;
+        }
+        // Size は任意項目なので、書かれている場合だけ照合する
+        if field("Thumb::Size").is_some_and(|v| v.parse().ok() != Some(source.size)) {
+            return None;
+        }
+        let image = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png)
+            .ok()?
+            .to_rgba8();
+        Some(egui::ColorImage::from_rgba_unmultiplied(
+            [image.width() as usize, image.height() as usize],
+            image.as_raw(),
+        ))
+    }
+
+    /// サムネイルを保存する（一時ファイルに書いてから置き換えるので、読み手が壊れたものを見ない）
+    pub fn store(&self, path: &Path, image: &egui::ColorImage) -> Result<(), String> {
+        let source = Source::of(path).ok_or("元ファイルの情報を取得できません")?;
+        let [w, h] = image.size;
+        let mut png_bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png_bytes, w as u32, h as u32);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            for (key, value) in [
+                ("Thumb::URI", source.uri.clone()),
+                ("Thumb::MTime", source.mtime.to_string()),
+                ("Thumb::Size", source.size.to_string()),
+                ("Software", SOFTWARE.to_string()),
+            ] {
+                encoder
+                    .add_text_chunk(key.to_string(), value)
+                    .map_err(|e| e.to_string())?;
+            }
+            let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+            let rgba: Vec<u8> = image.pixels.iter().flat_map(|c| c.to_srgba_unmultiplied()).collect();
+            writer.write_image_data(&rgba).map_err(|e| e.to_string())?;
+        }
+        let target = self.entry_path(path, &source.uri);
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let temp = target.with_extension(format!("{}.tmp", std::process::id()));
+        write_private(&temp, &png_bytes).map_err(|e| e.to_string())?;
+        fs::rename(&temp, &target).map_err(|e| {
+            let _ = fs::remove_file(&temp);
+            e.to_string()
+        })
+    }
+
+    /// 合計が上限を超えていれば、このアプリが作ったサムネイルを古い順に消す。消した数を返す
+    pub fn prune(&self) -> Result<usize, String> {
+        let mut entries = self.own_entries()?;
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        if total <= self.cap {
+            return Ok(0);
+        }
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        let mut removed = 0;
+        for (path, len, _) in entries {
+            if total <= self.cap {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= len;
+                removed += 1;
+            }
+        }
+        info!("サムネイルキャッシュを整理しました: {} 件削除", removed);
+        Ok(removed)
+    }
+
+    /// このアプリが作ったサムネイルをすべて消す。消した数を返す
+    pub fn clear(&self) -> Result<usize, String> {
+        let entries = self.own_entries()?;
+        let removed = entries
+            .iter()
+            .filter(|(path, _, _)| fs::remove_file(path).is_ok())
+            .count();
+        Ok(removed)
+    }
+
+    fn entry_path(&self, path: &Path, uri: &str) -> PathBuf {
+        let dir = if crate::archive::split(path).is_some() {
+            &self.archive_dir
+        } else {
+            &self.dir
+        };
+        dir.join(format!("{}.png", md5_hex(uri.as_bytes())))
+    }
+
+    /// 両方の置き場所の (パス, バイト数, 更新日時) の一覧。ディレクトリが無ければ空
+    fn own_entries(&self) -> Result<Vec<(PathBuf, u64, std::time::SystemTime)>, String> {
+        let mut entries = Vec::new();
+        for dir in [&self.dir, &self.archive_dir] {
+            own_entries_in(dir, &mut entries)?;
+        }
+        debug!("サムネイルキャッシュ: {} 件 ({})", entries.len(), self.dir.display());
+        Ok(entries)
+    }
+}
+
+/// dir にあるこのアプリのサムネイルを entries に加える
+fn own_entries_in(dir: &Path, entries: &mut Vec<(PathBuf, u64, std::time::SystemTime)>) -> Result<(), String> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(d) => d,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("{}: {}", dir.display(), e)),
+    };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|e| e != "png") {
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else { continue };
+        if !is_own(&path) {
+            continue;
+        }
+        let modified = meta.modified().unwrap_or(UNIX_EPOCH);
+        entries.push((path, meta.len(), modified));
+    }
+    Ok(())
+}
+
+/// Software が自分の名前になっているか（PNG のヘッダ部分だけを読む）
+fn is_own(path: &Path) -> bool {
+    let Ok(file) = fs::File::open(path) else {
+        return false;
+    };
+    let decoder = png::Decoder::new(std::io::BufReader::new(file));
+    let Ok(reader) = decoder.read_info() else {
+        return false;
+    };
+    reader
+        .info()
+        .uncompressed_latin1_text
+        .iter()
+        .any(|t| t.keyword == "Software" && t.text == SOFTWARE)
+}
+
+/// PNG の tEXt（キー, 値）の一覧
+fn read_texts(bytes: &[u8]) -> Option<Vec<(String, String)>> {
+    let reader = png::Decoder::new(Cursor::new(bytes)).read_info().ok()?;
+    Some(
+        reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .map(|t| (t.keyword.clone(), t.text.clone()))
+            .collect(),
+    )
+}
+
+/// サムネイルは他のユーザーから読めないようにする（規格の推奨）
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(bytes)
+}
+
+/// OS ごとのキャッシュの置き場所（ファイルのサムネイル, アーカイブ内の画像のサムネイル）
+fn default_dirs() -> Option<(PathBuf, PathBuf)> {
+    let own = |dir: PathBuf| {
+        let archive_dir = dir.join("archive");
+        (dir, archive_dir)
+    };
+    if cfg!(windows) {
+        let base = std::env::var_os("LOCALAPPDATA")?;
+        Some(own(PathBuf::from(base).join("MSBT-yuina").join("thumbnails")))
+    } else if cfg!(target_os = "macos") {
+        let home = std::env::var_os("HOME")?;
+        Some(own(PathBuf::from(home).join("Library/Caches/MSBT-yuina/thumbnails")))
+    } else {
+        let base = match std::env::var_os("XDG_CACHE_HOME").filter(|v| !v.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+        };
+        // 長辺 256px のサムネイルは "large" に置く。アーカイブ内の画像は共有しない
+        Some((
+            base.join("thumbnails").join("large"),
+            base.join("MSBT-yuina").join("thumbnails"),
+        ))
+    }
+}
+
+/// 絶対パスの file:// URI（Windows は file:///C:/... の形）
+fn file_uri(path: &Path) -> Option<String> {
+    let absolute = std::path::absolute(path).ok()?;
+    let text = absolute.to_str()?.replace('\\', "/");
+    let text = if text.starts_with('/') { text } else { format!("/{text}") };
+    Some(format!("file://{}", utf8_percent_encode(&text, URI_PATH)))
+}
+
+/// MD5 の 16 進表記。ファイル名の規約のためだけに使う
+fn md5_hex(data: &[u8]) -> String {
+    Md5::digest(data).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn md5_matches_known_digests() {
+        assert_eq!(md5_hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(
+            md5_hex(b"The quick brown fox jumps over the lazy dog"),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+        // 規格書の例: file:///home/jens/photos/me.png
+        assert_eq!(
+            md5_hex(b"file:///home/jens/photos/me.png"),
+            "c6ee772d9e49320e97ec29a7eb5b1697"
+        );
+    }
+
+    #[test]
+    fn uri_escapes_spaces_and_non_ascii() {
+        if cfg!(unix) {
+            assert_eq!(
+                file_uri(Path::new("/tmp/a b/画像.png")).unwrap(),
+                "file:///tmp/a%20b/%E7%94%BB%E5%83%8F.png"
+            );
+        }
+    }
+
+    #[test]
+    fn stored_thumbnail_is_reused_until_file_changes() {
+        let dir = std::env::temp_dir().join(format!("msbt-thumb-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("photo.png");
+        fs::write(&source, b"original").unwrap();
+        let cache = DiskCache::with_dir(dir.join("cache"), 1024 * 1024);
+        let thumb = egui::ColorImage::new([4, 2], egui::Color32::from_rgb(10, 20, 30));
+
+        assert!(cache.load(&source).is_none());
+        cache.store(&source, &thumb).unwrap();
+        let loaded = cache.load(&source).unwrap();
+        assert_eq!(loaded.size, [4, 2]);
+        assert_eq!(loaded.pixels[0], thumb.pixels[0]);
+
+        // サイズが変われば（更新日時が同じ秒でも）使わない
+        fs::write(&source, b"rewritten!").unwrap();
+        assert!(cache.load(&source).is_none());
+
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert_eq!(cache.own_entries().unwrap().len(), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// アーカイブ内の画像のサムネイルは、共有の置き場所ではなく専用のフォルダに置く
+    #[test]
+    fn archive_entries_are_kept_out_of_the_shared_directory() {
+        let dir = std::env::temp_dir().join(format!("msbt-thumb-archive-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let tar = dir.join("book.cbt");
+        fs::write(&tar, crate::archive::tar_bytes(&[("001.png", b"page")])).unwrap();
+        let entry = tar.join("001.png");
+        let cache = DiskCache::with_dir(dir.join("cache"), 1024 * 1024);
+        let thumb = egui::ColorImage::new([4, 2], egui::Color32::from_rgb(10, 20, 30));
+
+        cache.store(&entry, &thumb).unwrap();
+        assert!(cache.load(&entry).is_some());
+        let pngs = |d: &Path| {
+            fs::read_dir(d)
+                .map(|r| r.flatten().filter(|e| e.path().extension().is_some_and(|x| x == "png")).count())
+                .unwrap_or(0)
+        };
+        assert_eq!(pngs(&dir.join("cache")), 0);
+        assert_eq!(pngs(&dir.join("cache").join("archive")), 1);
+        assert_eq!(cache.clear().unwrap(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prune_removes_oldest_own_entries_over_cap() {
+        let dir = std::env::temp_dir().join(format!("msbt-thumb-prune-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let thumb = egui::ColorImage::new([64, 64], egui::Color32::from_rgb(200, 100, 50));
+        let big = DiskCache::with_dir(dir.join("cache"), u64::MAX);
+        for i in 0..3 {
+            let source = dir.join(format!("{i}.png"));
+            fs::write(&source, [i as u8]).unwrap();
+            big.store(&source, &thumb).unwrap();
+        }
+        // 他のアプリのサムネイルは対象外
+        fs::write(dir.join("cache").join("other.png"), b"not ours").unwrap();
+        let one = big.own_entries().unwrap()[0].1;
+        let small = DiskCache::with_dir(dir.join("cache"), one);
+        assert_eq!(small.prune().unwrap(), 2);
+        assert!(dir.join("cache").join("other.png").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
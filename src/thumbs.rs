@@ -0,0 +1,366 @@
+//! フィルムストリップ・グリッド表示用のサムネイル生成。
+//!
+//! サムネイルはワーカースレッドで縮小（SVG は resvg でラスタライズ）し、UI スレッドは
+//! 届いたものをテクスチャ化するだけにする。要求は後から来たものを先に処理するので、
+//! スクロールして見えてきたセルから順に埋まっていく。
+//! 作ったサムネイルはディスクキャッシュ（thumb_cache）にも保存し、次回の起動からはそれを読む。
+
+use crate::thumb_cache::DiskCache;
+use crate::{hdr, metadata};
+use eframe::egui;
+use log::{debug, error};
+use resvg::tiny_skia;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+
+/// サムネイルの長辺（px）。freedesktop の "large" と同じ大きさにしてキャッシュを共有できるようにする
+pub const THUMB_SIZE: u32 = 256;
+/// サムネイルを並行して作るワーカー数
+const THUMB_WORKERS: usize = 2;
+/// 1 フレームでテクスチャ化するサムネイル数の上限（残りは次フレーム以降）
+const UPLOADS_PER_FRAME: usize = 16;
+/// テクスチャとして持っておくサムネイル数の上限（256px 四方で 1 枚 256KB なので約 256MB）。
+/// 超えたら長く表示していないものから捨て、また見えたらディスクキャッシュから読み直す
+const MAX_TEXTURES: usize = 1024;
+
+/// セルに描くサムネイルの状態
+pub enum Thumb<'a> {
+    Ready(&'a egui::TextureHandle),
+    Pending,
+    Failed,
+}
+
+enum State {
+    Pending,
+    Ready(egui::TextureHandle),
+    Failed,
+}
+
+struct Entry {
+    state: State,
+    /// 最後に表示した（get した）フレーム
+    used: u64,
+}
+
+struct Queue {
+    /// 後から積んだものを先に処理する
+    stack: Vec<PathBuf>,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+}
+
+type ThumbResult = (PathBuf, Result<egui::ColorImage, String>);
+
+pub struct Thumbnails {
+    shared: Arc<Shared>,
+    disk: Option<Arc<DiskCache>>,
+    result_rx: mpsc::Receiver<ThumbResult>,
+    states: HashMap<PathBuf, Entry>,
+    /// poll のたびに進めるフレーム番号（Entry::used の比較用）
+    frame: u64,
+}
+
+impl Thumbnails {
+    /// disk が None ならディスクキャッシュを使わない
+    pub fn new(ctx: &egui::Context, disk: Option<DiskCache>) -> Self {
+        let disk = disk.map(Arc::new);
+        // 上限を超えた古いサムネイルの削除は起動時に一度だけ、裏で行う
+        if let Some(disk) = disk.clone() {
+            std::thread::spawn(move || {
+                if let Err(e) = disk.prune() {
+                    error!("サムネイルキャッシュの整理に失敗しました: {}", e);
+                }
+            });
+        }
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                stack: Vec::new(),
+                closed: false,
+            }),
+            ready: Condvar::new(),
+        });
+        let (result_tx, result_rx) = mpsc::channel();
+        for _ in 0..THUMB_WORKERS {
+            let shared = shared.clone();
+            let result_tx = result_tx.clone();
+            let ctx = ctx.clone();
+            let disk = disk.clone();
+            std::thread::spawn(move || worker_loop(shared, result_tx, disk, ctx));
+        }
+        Self {
+            shared,
+            disk,
+            result_rx,
+            states: HashMap::new(),
+            frame: 0,
+        }
+    }
+
+    /// 表示するセルのサムネイル。初めて見るものは生成を依頼する
+    pub fn get(&mut self, path: &Path) -> Thumb<'_> {
+        if !self.states.contains_key(path) {
+            self.states.insert(
+                path.to_path_buf(),
+                Entry {
+                    state: State::Pending,
+                    used: self.frame,
+                },
+            );
+            self.shared.queue.lock().unwrap().stack.push(path.to_path_buf());
+            self.shared.ready.notify_one();
+        }
+        let entry = self.states.get_mut(path).unwrap();
+        entry.used = self.frame;
+        match &entry.state {
+            State::Ready(texture) => Thumb::Ready(texture),
+            State::Pending => Thumb::Pending,
+            State::Failed => Thumb::Failed,
+        }
+    }
+
+    /// ワーカーの結果をテクスチャ化する（毎フレーム、セルを描く前に呼ぶ）
+    pub fn poll(&mut self, ctx: &egui::Context) {
+        self.frame += 1;
+        let mut uploads = 0;
+        while let Ok((path, result)) = self.result_rx.try_recv() {
+            // 一覧から外れた（retain で捨てた）ものの結果は使わない
+            let Some(entry) = self.states.get_mut(&path) else {
+                continue;
+            };
+            entry.state = match result {
+                Ok(image) => State::Ready(ctx.load_texture(
+                    format!("thumb:{}", path.display()),
+                    image,
+                    egui::TextureOptions::LINEAR,
+                )),
+                Err(e) => {
+                    debug!("サムネイルを作れません: {} ({})", path.display(), e);
+                    State::Failed
+                }
+            };
+            uploads += 1;
+            if uploads >= UPLOADS_PER_FRAME {
+                // 残りは次のフレームで受け取る
+                ctx.request_repaint();
+                break;
+            }
+        }
+        if uploads > 0 {
+            self.evict();
+        }
+    }
+
+    /// テクスチャが MAX_TEXTURES を超えていたら、最後に表示したのが古いものから捨てる。
+    /// 直前のフレームで表示したものは残す
+    fn evict(&mut self) {
+        let mut ready: Vec<(u64, &PathBuf)> = self
+            .states
+            .iter()
+            .filter(|(_, entry)| matches!(entry.state, State::Ready(_)))
+            .map(|(path, entry)| (entry.used, path))
+            .collect();
+        let Some(excess) = ready.len().checked_sub(MAX_TEXTURES).filter(|&n| n > 0) else {
+            return;
+        };
+        ready.sort_unstable_by_key(|&(used, _)| used);
+        let stale: Vec<PathBuf> = ready
+            .into_iter()
+            .take(excess)
+            .filter(|&(used, _)| used + 1 < self.frame)
+            .map(|(_, path)| path.clone())
+            .collect();
+        debug!("サムネイルのテクスチャを {} 枚捨てます", stale.len());
+        for path in stale {
+            self.states.remove(&path);
+        }
+    }
+
+    /// ファイルが書き換えられたとき、次に表示するときに作り直させる
+    pub fn invalidate(&mut self, path: &Path) {
+        self.states.remove(path);
+    }
+
+    /// 画像一覧が変わったとき、一覧にないもののサムネイルと未着手の依頼を捨てる
+    pub fn retain(&mut self, paths: &[PathBuf]) {
+        let keep: std::collections::HashSet<&PathBuf> = paths.iter().collect();
+        self.states.retain(|p, _| keep.contains(p));
+        self.shared.queue.lock().unwrap().stack.retain(|p| keep.contains(p));
+    }
+}
+
+impl Thumbnails {
+    /// ディスクキャッシュのうち、このアプリが作ったサムネイルをすべて消す。消した数を返す
+    pub fn clear_disk_cache(&self) -> Result<usize, String> {
+        match &self.disk {
+            Some(disk) => disk.clear(),
+            None => Ok(0),
+        }
+    }
+}
+
+impl Drop for Thumbnails {
+    fn drop(&mut self) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.closed = true;
+        queue.stack.clear();
+        drop(queue);
+        self.shared.ready.notify_all();
+    }
+}
+
+fn worker_loop(
+    shared: Arc<Shared>,
+    result_tx: mpsc::Sender<ThumbResult>,
+    disk: Option<Arc<DiskCache>>,
+    ctx: egui::Context,
+) {
+    loop {
+        let path = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if queue.closed {
+                    return;
+                }
+                if let Some(path) = queue.stack.pop() {
+                    break path;
+                }
+                queue = shared.ready.wait(queue).unwrap();
+            }
+        };
+        let result = load_or_generate(&path, disk.as_deref());
+        if result_tx.send((path, result)).is_err() {
+            return;
+        }
+        ctx.request_repaint();
+    }
+}
+
+/// ディスクキャッシュにあればそれを、なければ作って保存したものを返す
+fn load_or_generate(path: &Path, disk: Option<&DiskCache>) -> Result<egui::ColorImage, String> {
+    if let Some(image) = disk.and_then(|d| d.load(path)) {
+        return Ok(image);
+    }
+    let image = generate(path, THUMB_SIZE)?;
+    if let Some(disk) = disk {
+        if let Err(e) = disk.store(path, &image) {
+            debug!("サムネイルを保存できません: {} ({})", path.display(), e);
+        }
+    }
+    Ok(image)
+}
+
+/// 長辺が size 以下のサムネイルを作る（EXIF の向きは常に適用する）
+pub fn generate(path: &Path, size: u32) -> Result<egui::ColorImage, String> {
+    if super::is_svg_path(path) {
+        return render_svg(path, size);
+    }
+    let image = match crate::color::decode_with_icc(path) {
+        Ok((image, _)) => image,
+        Err(e) => super::decode_via_wic(path).map_err(|werr| format!("{e} / WIC: {werr}"))?,
+    };
+    let mut thumb = image.thumbnail(size, size);
+    if let Some(orientation) = metadata::read_orientation(path) {
+        thumb.apply_orientation(orientation);
+    }
+    // 浮動小数の画像は既定のトーンマッピングと自動露出で 8bit にする
+    if matches!(
+        thumb.color(),
+        image::ColorType::Rgb32F | image::ColorType::Rgba32F
+    ) {
+        let hdr_image = hdr::HdrImage::new(thumb.into_rgba32f());
+        return Ok(hdr_image.render(hdr::ToneParams {
+            exposure: hdr_image.auto_exposure,
+            gamma: 2.2,
+            tone_map: hdr::ToneMap::default(),
+        }));
+    }
+    let rgba = thumb.to_rgba8();
+    Ok(egui::ColorImage::from_rgba_unmultiplied(
+        [rgba.width() as usize, rgba.height() as usize],
+        rgba.as_raw(),
+    ))
+}
+
+/// SVG 全体を長辺 size に収まる倍率でラスタライズする
+fn render_svg(path: &Path, size: u32) -> Result<egui::ColorImage, String> {
+    let (tree, _) = super::parse_svg(path)?;
+    let svg_size = tree.size();
+    let scale = (size as f32 / svg_size.width()).min(size as f32 / svg_size.height());
+    let w = (svg_size.width() * scale).round().max(1.0) as u32;
+    let h = (svg_size.height() * scale).round().max(1.0) as u32;
+    let mut pixmap = tiny_skia::Pixmap::new(w, h).ok_or("サムネイル用のバッファを確保できません")?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    Ok(egui::ColorImage::from_rgba_premultiplied(
+        [w as usize, h as usize],
+        pixmap.data(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumbnails_fit_within_size() {
+        let dir = std::env::temp_dir().join(format!("msbt-thumbs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let png = dir.join("wide.png");
+        image::RgbaImage::from_pixel(400, 100, image::Rgba([255, 0, 0, 255]))
+            .save(&png)
+            .unwrap();
+        let svg = dir.join("tall.svg");
+        std::fs::write(
+            &svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="50" height="200"><rect width="50" height="200" fill="blue"/></svg>"#,
+        )
+        .unwrap();
+
+        let thumb = generate(&png, 64).unwrap();
+        assert_eq!(thumb.size, [64, 16]);
+        assert_eq!(thumb.pixels[0], egui::Color32::RED);
+        let thumb = generate(&svg, 64).unwrap();
+        assert_eq!(thumb.size, [16, 64]);
+        assert_eq!(thumb.pixels[0], egui::Color32::BLUE);
+        assert!(generate(&dir.join("missing.png"), 64).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn textures_not_shown_recently_are_evicted_over_the_limit() {
+        let ctx = egui::Context::default();
+        let mut thumbs = Thumbnails::new(&ctx, None);
+        let pixel = egui::ColorImage::new([1, 1], egui::Color32::WHITE);
+        let path = |i: usize| PathBuf::from(format!("/thumb/{i}.png"));
+        for i in 0..MAX_TEXTURES + 10 {
+            let texture = ctx.load_texture(format!("t{i}"), pixel.clone(), egui::TextureOptions::LINEAR);
+            thumbs.states.insert(
+                path(i),
+                Entry {
+                    state: State::Ready(texture),
+                    used: 0,
+                },
+            );
+        }
+        thumbs.frame = 5;
+        // 先頭の 5 枚は直前のフレームで表示したので、古くても残す
+        for i in 0..5 {
+            thumbs.states.get_mut(&path(i)).unwrap().used = 4;
+        }
+        thumbs.evict();
+        assert_eq!(thumbs.states.len(), MAX_TEXTURES);
+        assert!((0..5).all(|i| thumbs.states.contains_key(&path(i))));
+        // 上限以下なら何もしない
+        thumbs.frame = 100;
+        thumbs.evict();
+        assert_eq!(thumbs.states.len(), MAX_TEXTURES);
+    }
+}
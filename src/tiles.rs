@@ -0,0 +1,235 @@
+//! GPU のテクスチャ上限を超える巨大なラスタ画像のタイル表示（ディープズーム）。
+//!
+//! 画像全体をミップピラミッド（1/2 ずつ縮小した段）としてメモリに持ち、表示倍率に合った段の
+//! 可視タイルだけをテクスチャ化して描く（SVG が可視領域だけをラスタライズするのと同じ考え方）。
+//! 未アップロードのタイルは最も粗い段を下敷きにして表示し、1 フレームあたりの
+//! アップロード数を抑えてパン・ズーム中も UI を止めない。
+
+use eframe::egui;
+use image::RgbaImage;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// タイル 1 枚の辺（px）
+pub const TILE_SIZE: u32 = 512;
+/// 1 フレームで新たにアップロードするタイル数の上限（残りは次フレーム以降）
+const UPLOADS_PER_FRAME: usize = 8;
+/// 保持するタイルテクスチャ数の目安（512² RGBA で約 256MB）。超えたら画面外のものを捨てる
+const MAX_CACHED_TILES: usize = 256;
+
+/// (段, タイル列, タイル行)
+type TileKey = (usize, u32, u32);
+
+/// 描画すべきタイル 1 枚
+pub struct TileDraw {
+    pub texture: egui::TextureHandle,
+    /// 段 0（原寸）の画像座標でのタイル矩形 [x, y, w, h]
+    pub rect: [f32; 4],
+}
+
+/// 複製はピラミッドを共有する（先読みキャッシュと表示中の画像で同じ画素を使う）
+#[derive(Clone)]
+pub struct TiledImage {
+    /// levels[0] が原寸、以降 1/2 ずつ縮小。最後の段は 1 タイルに収まる
+    levels: Arc<Vec<RgbaImage>>,
+    textures: HashMap<TileKey, egui::TextureHandle>,
+    options: egui::TextureOptions,
+    name: String,
+}
+
+impl TiledImage {
+    /// 原寸画像からピラミッドを作る（name はテクスチャ名の接頭辞）
+    pub fn new(image: RgbaImage, name: String, options: egui::TextureOptions) -> Self {
+        let mut levels = vec![image];
+        while let Some(last) = levels.last() {
+            if last.width() <= TILE_SIZE && last.height() <= TILE_SIZE {
+                break;
+            }
+            let next = downsample_half(last);
+            levels.push(next);
+        }
+        Self {
+            levels: Arc::new(levels),
+            textures: HashMap::new(),
+            options,
+            name,
+        }
+    }
+
+    /// 原寸の (幅, 高さ)
+    pub fn size(&self) -> [u32; 2] {
+        let (w, h) = self.levels[0].dimensions();
+        [w, h]
+    }
+
+    /// 原寸の画素（比較表示の差分計算用）
+    pub fn original(&self) -> &RgbaImage {
+        &self.levels[0]
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// region（原寸の画像座標 [x, y, w, h]）を物理 px 倍率 scale_px で表示するためのタイルを返す。
+    /// 最も粗い段を下敷きに先頭へ並べ、続けて適した段の準備済みタイルを並べる。
+    /// 戻り値の bool は未アップロードのタイルが残っている（再描画が必要）かどうか
+    pub fn tiles_for_view(
+        &mut self,
+        ctx: &egui::Context,
+        region: [f32; 4],
+        scale_px: f32,
+    ) -> (Vec<TileDraw>, bool) {
+        let coarsest = self.levels.len() - 1;
+        let level = choose_level(scale_px, self.levels.len());
+        let mut wanted: Vec<TileKey> = tile_range(self.size(), coarsest, [0.0, 0.0, f32::MAX, f32::MAX]);
+        if level != coarsest {
+            wanted.extend(tile_range(self.size(), level, region));
+        }
+
+        let mut uploads = 0;
+        let mut pending = false;
+        let mut draws = Vec::with_capacity(wanted.len());
+        for &key in &wanted {
+            if !self.textures.contains_key(&key) {
+                // 下敷きの段は必ず用意する（何も描けないフレームを作らない）
+                if uploads >= UPLOADS_PER_FRAME && key.0 != coarsest {
+                    pending = true;
+                    continue;
+                }
+                let texture = self.upload(ctx, key);
+                self.textures.insert(key, texture);
+                uploads += 1;
+            }
+            draws.push(TileDraw {
+                texture: self.textures[&key].clone(),
+                rect: tile_rect(self.size(), key),
+            });
+        }
+
+        if self.textures.len() > MAX_CACHED_TILES {
+            let keep: HashSet<TileKey> = wanted.into_iter().collect();
+            self.textures.retain(|k, _| keep.contains(k));
+        }
+        (draws, pending)
+    }
+
+    fn upload(&self, ctx: &egui::Context, (level, tx, ty): TileKey) -> egui::TextureHandle {
+        let img = &self.levels[level];
+        let x = tx * TILE_SIZE;
+        let y = ty * TILE_SIZE;
+        let w = TILE_SIZE.min(img.width() - x);
+        let h = TILE_SIZE.min(img.height() - y);
+        let tile = image::imageops::crop_imm(img, x, y, w, h).to_image();
+        let color_image =
+            egui::ColorImage::from_rgba_unmultiplied([w as usize, h as usize], tile.as_raw());
+        ctx.load_texture(
+            format!("{}@{}:{},{}", self.name, level, tx, ty),
+            color_image,
+            self.options,
+        )
+    }
+}
+
+/// 物理 px 倍率に適した段。1 texel が 1 物理 px 以上になる最も粗い段を選ぶ
+/// （拡大表示中は常に原寸の段 0）
+fn choose_level(scale_px: f32, level_count: usize) -> usize {
+    if scale_px <= 0.0 || scale_px >= 1.0 {
+        return 0;
+    }
+    let level = (1.0 / scale_px).log2().floor() as usize;
+    level.min(level_count - 1)
+}
+
+/// region（原寸の画像座標）と交差する段 level のタイル一覧
+fn tile_range(size: [u32; 2], level: usize, region: [f32; 4]) -> Vec<TileKey> {
+    let span = (TILE_SIZE << level) as f32; // 原寸座標でのタイル 1 枚の幅
+    let cols = size[0].div_ceil(TILE_SIZE << level);
+    let rows = size[1].div_ceil(TILE_SIZE << level);
+    let [x, y, w, h] = region;
+    let x0 = (x.max(0.0) / span).floor() as u32;
+    let y0 = (y.max(0.0) / span).floor() as u32;
+    let x1 = (((x + w) / span).ceil() as u32).min(cols);
+    let y1 = (((y + h) / span).ceil() as u32).min(rows);
+    let mut keys = Vec::new();
+    for ty in y0..y1 {
+        for tx in x0..x1 {
+            keys.push((level, tx, ty));
+        }
+    }
+    keys
+}
+
+/// タイルの原寸座標での矩形。右端・下端のタイルは画像の端で切る
+fn tile_rect(size: [u32; 2], (level, tx, ty): TileKey) -> [f32; 4] {
+    let span = TILE_SIZE << level;
+    let x = tx * span;
+    let y = ty * span;
+    [
+        x as f32,
+        y as f32,
+        (size[0] - x).min(span) as f32,
+        (size[1] - y).min(span) as f32,
+    ]
+}
+
+/// 2x2 の平均で 1/2 に縮小する（奇数辺は切り上げ、端の画素はそのまま平均に使う）。
+/// 巨大画像では image の resize より大幅に速い
+fn downsample_half(src: &RgbaImage) -> RgbaImage {
+    let (w, h) = src.dimensions();
+    let nw = w.div_ceil(2).max(1);
+    let nh = h.div_ceil(2).max(1);
+    RgbaImage::from_fn(nw, nh, |x, y| {
+        let x0 = (x * 2).min(w - 1);
+        let y0 = (y * 2).min(h - 1);
+        let x1 = (x0 + 1).min(w - 1);
+        let y1 = (y0 + 1).min(h - 1);
+        let p = [
+            src.get_pixel(x0, y0).0,
+            src.get_pixel(x1, y0).0,
+            src.get_pixel(x0, y1).0,
+            src.get_pixel(x1, y1).0,
+        ];
+        image::Rgba(std::array::from_fn(|c| {
+            ((p[0][c] as u32 + p[1][c] as u32 + p[2][c] as u32 + p[3][c] as u32 + 2) / 4) as u8
+        }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_follows_scale() {
+        assert_eq!(choose_level(2.0, 6), 0);
+        assert_eq!(choose_level(1.0, 6), 0);
+        assert_eq!(choose_level(0.6, 6), 0);
+        assert_eq!(choose_level(0.5, 6), 1);
+        assert_eq!(choose_level(0.1, 6), 3);
+        assert_eq!(choose_level(0.0001, 6), 5, "最も粗い段で頭打ち");
+    }
+
+    #[test]
+    fn tile_range_and_rects_cover_edges() {
+        let size = [1300, 600];
+        // 全体: 段 0 は 3x2 タイル、右端の列は 276px 幅
+        let all = tile_range(size, 0, [0.0, 0.0, f32::MAX, f32::MAX]);
+        assert_eq!(all.len(), 6);
+        assert_eq!(tile_rect(size, (0, 2, 1)), [1024.0, 512.0, 276.0, 88.0]);
+        // 段 1 のタイルは原寸 1024px 分
+        assert_eq!(tile_range(size, 1, [0.0, 0.0, f32::MAX, f32::MAX]).len(), 2);
+        // 一部だけ見える場合は交差するタイルだけ
+        assert_eq!(tile_range(size, 0, [600.0, 100.0, 10.0, 10.0]), vec![(0, 1, 0)]);
+        assert_eq!(tile_range(size, 0, [500.0, 500.0, 30.0, 30.0]).len(), 4);
+    }
+
+    #[test]
+    fn downsample_averages_and_rounds_up_odd_sizes() {
+        let src = RgbaImage::from_fn(3, 1, |x, _| image::Rgba([(x * 100) as u8, 0, 0, 255]));
+        let half = downsample_half(&src);
+        assert_eq!(half.dimensions(), (2, 1));
+        assert_eq!(half.get_pixel(0, 0).0, [50, 0, 0, 255]);
+        assert_eq!(half.get_pixel(1, 0).0, [200, 0, 0, 255]);
+    }
+}
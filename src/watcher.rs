@@ -0,0 +1,292 @@
+//! 表示中のフォルダとファイルの変更監視。
+//!
+//! 監視スレッドが一定間隔でフォルダ内の画像の一覧と表示中のファイルの更新日時・サイズを調べ、
+//! 変化があれば UI スレッドへ知らせる。ネットワークドライブ等でも同じように動くよう、
+//! OS の通知ではなくポーリングで調べる。書き込み途中のファイルを読まないよう、
+//! 表示中のファイルの変更は 2 回続けて同じ状態になってから知らせる。
+//!
+//! 一覧の走査はフォルダを辿るぶん重いので、表示中のファイルより長い間隔（大きなフォルダでは
+//! 走査にかかった時間に比例してさらに長く）で行い、追加・削除されたパスだけを知らせる。
+
+use crate::{archive, folders};
+use eframe::egui;
+use log::debug;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// 表示中のファイルを調べる間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(700);
+/// フォルダ内の一覧を調べる最短の間隔
+const LIST_POLL_INTERVAL: Duration = Duration::from_secs(3);
+/// 一覧の走査にかかった時間の何倍を次の走査までに空けるか
+const LIST_POLL_COST_FACTOR: u32 = 20;
+
+/// 監視スレッドからの通知
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    /// フォルダ内の画像が追加・削除・リネームされた（リネームは削除＋追加）
+    ListChanged {
+        added: Vec<PathBuf>,
+        removed: Vec<PathBuf>,
+    },
+    /// 表示中のファイルが書き換えられた
+    Modified(PathBuf),
+    /// 表示中のファイルが削除（または移動）された
+    Removed(PathBuf),
+}
+
+/// 監視する対象
+#[derive(Clone, PartialEq, Default)]
+struct Target {
+    root: Option<PathBuf>,
+    recursive: bool,
+    current: Option<PathBuf>,
+}
+
+struct Shared {
+    target: Mutex<Option<Target>>,
+    changed: Condvar,
+}
+
+pub struct Watcher {
+    shared: Arc<Shared>,
+    /// 最後に渡した対象（毎フレーム呼ばれても変わったときだけスレッドを起こす）
+    last: Option<Target>,
+    event_rx: mpsc::Receiver<Event>,
+}
+
+impl Watcher {
+    pub fn new(ctx: &egui::Context) -> Self {
+        let shared = Arc::new(Shared {
+            target: Mutex::new(Some(Target::default())),
+            changed: Condvar::new(),
+        });
+        let (event_tx, event_rx) = mpsc::channel();
+        let thread_shared = shared.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || watch_loop(thread_shared, event_tx, ctx));
+        Self {
+            shared,
+            last: None,
+            event_rx,
+        }
+    }
+
+    /// 監視する対象を設定する。root が None なら何も監視しない
+    pub fn watch(&mut self, root: Option<&Path>, recursive: bool, current: Option<&Path>) {
+        let target = Target {
+            root: root.map(Path::to_path_buf),
+            recursive,
+            current: current.map(Path::to_path_buf),
+        };
+        if self.last.as_ref() == Some(&target) {
+            return;
+        }
+        self.last = Some(target.clone());
+        *self.shared.target.lock().unwrap() = Some(target);
+        self.shared.changed.notify_one();
+    }
+
+    /// 届いている通知を 1 件取り出す
+    pub fn poll(&self) -> Option<Event> {
+        self.event_rx.try_recv().ok()
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        *self.shared.target.lock().unwrap() = None;
+        self.shared.changed.notify_one();
+    }
+}
+
+/// ファイルの状態（更新日時とサイズ）
+type Stamp = Option<(Option<SystemTime>, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    archive::metadata(path).ok().map(|m| (m.modified().ok(), m.len()))
+}
+
+fn watch_loop(shared: Arc<Shared>, event_tx: mpsc::Sender<Event>, ctx: egui::Context) {
+    let mut state = WatchState::default();
+    let mut target = Target::default();
+    let mut next_scan = Instant::now();
+    loop {
+        {
+            let guard = shared.target.lock().unwrap();
+            let (guard, _) = shared.changed.wait_timeout(guard, POLL_INTERVAL).unwrap();
+            match &*guard {
+                None => return,
+                Some(new) if *new != target => {
+                    target = new.clone();
+                    // 対象が変わったら今の状態を基準にし直す（切り替え直後に通知しない）
+                    state = WatchState::default();
+                    next_scan = Instant::now();
+                }
+                Some(_) => {}
+            }
+        }
+        let started = Instant::now();
+        let scan_list = started >= next_scan;
+        let events = state.check(&target, scan_list);
+        if scan_list {
+            next_scan = Instant::now() + LIST_POLL_INTERVAL.max(started.elapsed() * LIST_POLL_COST_FACTOR);
+        }
+        for event in events {
+            debug!("変更を検出しました: {:?}", event);
+            if event_tx.send(event).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+        }
+    }
+}
+
+#[derive(Default)]
+struct WatchState {
+    /// 前回調べたフォルダ内の画像（None は未調査）
+    listing: Option<HashSet<PathBuf>>,
+    /// 表示中のファイルの最後に知らせた（または基準にした）状態
+    reported: Option<Stamp>,
+    /// 変化を見つけたがまだ知らせていない状態
+    pending: Option<Stamp>,
+}
+
+impl WatchState {
+    /// 表示中のファイルを調べる。scan_list ならフォルダ内の一覧も調べる
+    fn check(&mut self, target: &Target, scan_list: bool) -> Vec<Event> {
+        let mut events = Vec::new();
+        // 表示中のファイルが消えて Removed をまだ知らせていない間は、一覧の変化も知らせない
+        let mut awaiting_removal = false;
+        if let Some(current) = &target.current {
+            let now = stamp(current);
+            match self.reported {
+                None => self.reported = Some(now),
+                Some(reported) if reported == now => self.pending = None,
+                Some(_) if self.pending != Some(now) => self.pending = Some(now),
+                Some(_) => {
+                    self.reported = Some(now);
+                    self.pending = None;
+                    events.push(match now {
+                        Some(_) => Event::Modified(current.clone()),
+                        None => Event::Removed(current.clone()),
+                    });
+                }
+            }
+            awaiting_removal = now.is_none() && self.reported != Some(None);
+        }
+        if let (Some(root), false, true) = (&target.root, awaiting_removal, scan_list) {
+            let listing = scan(root, target.recursive);
+            let change = self.listing.as_ref().and_then(|old| {
+                let added: Vec<PathBuf> = listing.difference(old).cloned().collect();
+                let removed: Vec<PathBuf> = old.difference(&listing).cloned().collect();
+                (!added.is_empty() || !removed.is_empty()).then_some((added, removed))
+            });
+            self.listing = Some(listing);
+            // 表示中のファイルの削除は Removed で扱う（一覧の作り直しもそちらで行う）
+            if let Some((added, removed)) = change {
+                if !events.iter().any(|e| matches!(e, Event::Removed(_))) {
+                    events.push(Event::ListChanged { added, removed });
+                }
+            }
+        }
+        events
+    }
+}
+
+/// フォルダ（またはアーカイブ）内の画像のパス。読めないフォルダはエラーログを繰り返さないよう黙って飛ばす
+fn scan(root: &Path, recursive: bool) -> HashSet<PathBuf> {
+    if archive::is_archive_path(root) {
+        return archive::list(root).unwrap_or_default().into_iter().collect();
+    }
+    let mut images = HashSet::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(real) = fs::canonicalize(&dir) else { continue };
+        if !visited.insert(real) {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else { continue };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            // 種類は read_dir の結果から分かるので、ファイルごとに stat しない（リンクだけは辿って調べる）
+            match entry.file_type() {
+                Ok(kind) if kind.is_file() => {
+                    if folders::has_image_ext(&path) {
+                        images.insert(path);
+                    }
+                }
+                Ok(kind) if kind.is_dir() => {
+                    let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                    if recursive && !hidden {
+                        stack.push(path);
+                    }
+                }
+                _ => {
+                    if folders::is_image_path(&path) {
+                        images.insert(path);
+                    } else if recursive && folders::is_visible_dir(&path) {
+                        stack.push(path);
+                    }
+                }
+            }
+        }
+    }
+    images
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_settled_changes_of_current_file_and_folder() {
+        let dir = std::env::temp_dir().join(format!("msbt-watcher-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let current = dir.join("a.png");
+        fs::write(&current, b"1").unwrap();
+        let target = Target {
+            root: Some(dir.clone()),
+            recursive: false,
+            current: Some(current.clone()),
+        };
+        let mut state = WatchState::default();
+        assert!(state.check(&target, true).is_empty(), "初回は基準にするだけ");
+
+        fs::write(dir.join("b.png"), b"").unwrap();
+        fs::write(dir.join("notes.txt"), b"").unwrap();
+        assert_eq!(
+            state.check(&target, true),
+            [Event::ListChanged {
+                added: vec![dir.join("b.png")],
+                removed: Vec::new(),
+            }]
+        );
+        assert!(state.check(&target, true).is_empty());
+        // 一覧を調べない回は、表示中のファイルだけを見る
+        fs::remove_file(dir.join("b.png")).unwrap();
+        assert!(state.check(&target, false).is_empty());
+        assert_eq!(
+            state.check(&target, true),
+            [Event::ListChanged {
+                added: Vec::new(),
+                removed: vec![dir.join("b.png")],
+            }]
+        );
+
+        // 書き換えは 2 回続けて同じ状態になってから知らせる
+        fs::write(&current, b"22").unwrap();
+        assert!(state.check(&target, true).is_empty());
+        assert_eq!(state.check(&target, true), [Event::Modified(current.clone())]);
+        assert!(state.check(&target, true).is_empty());
+
+        fs::remove_file(&current).unwrap();
+        assert!(state.check(&target, true).is_empty());
+        assert_eq!(state.check(&target, true), [Event::Removed(current.clone())]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}